@@ -0,0 +1,7 @@
+fn main() {
+    rclap_build::Builder::new("build_config.toml")
+        .settings("serde = true")
+        .generate("build_config.rs");
+    // Stands in for the `git rev-parse --short HEAD` of a real build script.
+    println!("cargo:rustc-env=EXAMPLE_GIT_SHA=abc1234");
+}
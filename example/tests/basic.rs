@@ -9,6 +9,11 @@ enum MyEnum {
     B,
     C,
 }
+#[derive(Debug, Clone, PartialEq, clap::Args)]
+struct LoggingArgs {
+    #[arg(long = "log-level", default_value = "info")]
+    log_level: String,
+}
 #[config(path="second_config.toml" ,derives=[serde::Serialize, serde::Deserialize])]
 struct MySecondConfig;
 #[test]
@@ -108,3 +113,2157 @@ fn test_url_not_provided() {
         std::env::remove_var("URL");
     }
 }
+#[test]
+#[serial]
+fn test_ranges() {
+    #[config("ranges.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.port, 8080u16);
+    assert_eq!(config.workers, 4usize);
+    assert_eq!(config.offset, 0);
+    assert_eq!(config.bytes, 10_000_000_000_000_000_000u64);
+
+    let config = MyConfig::parse_from(["example", "--myconfig.bytes", "18446744073709551615"]);
+    assert_eq!(config.bytes, u64::MAX);
+    let config =
+        <MyConfig as clap::Parser>::try_parse_from(["example", "--myconfig.bytes", "1000"]);
+    assert!(config.is_err());
+
+    let config = <MyConfig as clap::Parser>::try_parse_from(["example", "--myconfig.port", "80"]);
+    assert!(config.is_err());
+    let config = <MyConfig as clap::Parser>::try_parse_from(["example", "--myconfig.workers", "0"]);
+    assert!(config.is_err());
+    let config =
+        <MyConfig as clap::Parser>::try_parse_from(["example", "--myconfig.offset", "101"]);
+    assert!(config.is_err());
+}
+#[test]
+#[serial]
+fn test_timezone() {
+    #[config("timezone.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.zone, rclap::chrono_tz::Tz::UTC);
+    assert_eq!(config.local_zone, None);
+
+    let config = MyConfig::parse_from(["example", "--myconfig.local_zone", "Africa/Tunis"]);
+    assert_eq!(config.local_zone, Some(rclap::chrono_tz::Tz::Africa__Tunis));
+
+    let config =
+        <MyConfig as clap::Parser>::try_parse_from(["example", "--myconfig.zone", "Mars/Olympus"]);
+    assert!(config.is_err());
+}
+#[test]
+#[serial]
+fn test_lang() {
+    #[config("lang.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.lang.to_string(), "en-US");
+    assert_eq!(config.fallback, None);
+
+    let config = MyConfig::parse_from(["example", "--myconfig.fallback", "fr-CA"]);
+    assert_eq!(config.fallback.unwrap().language.as_str(), "fr");
+
+    let config =
+        <MyConfig as clap::Parser>::try_parse_from(["example", "--myconfig.lang", "not a tag"]);
+    assert!(config.is_err());
+}
+#[test]
+#[serial]
+fn test_allowed_values() {
+    #[config("values.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.format, "json");
+    assert_eq!(config.theme, None);
+
+    let config = MyConfig::parse_from(["example", "--myconfig.format", "yaml"]);
+    assert_eq!(config.format, "yaml");
+
+    let error = <MyConfig as clap::Parser>::try_parse_from(["example", "--myconfig.format", "jsn"])
+        .unwrap_err();
+    assert_eq!(error.kind(), clap::error::ErrorKind::InvalidValue);
+    assert!(error.to_string().contains("json"));
+}
+#[test]
+#[serial]
+fn test_email_and_hostname() {
+    #[config("address.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.alert, "ops@example.com");
+    assert_eq!(config.smtp, "smtp.example.com");
+    assert_eq!(config.cc, vec!["team@example.com".to_string()]);
+
+    let config = MyConfig::parse_from(["example", "--myconfig.alert", "\"on call\"@[10.0.0.1]"]);
+    assert_eq!(config.alert, "\"on call\"@[10.0.0.1]");
+
+    for (flag, value) in [
+        ("--myconfig.alert", "ops.example.com"),
+        ("--myconfig.alert", "ops..team@example.com"),
+        ("--myconfig.smtp", "-smtp.example.com"),
+        ("--myconfig.smtp", "smtp_relay.example.com"),
+        ("--myconfig.cc", "nobody"),
+    ] {
+        let config = <MyConfig as clap::Parser>::try_parse_from(["example", flag, value]);
+        assert!(config.is_err(), "{value} should be rejected");
+    }
+}
+#[test]
+#[serial]
+fn test_pattern() {
+    #[config("pattern.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example", "--myconfig.slug", "api-v2"]);
+    assert_eq!(config.slug, "api-v2");
+
+    let error = <MyConfig as clap::Parser>::try_parse_from(["example", "--myconfig.slug", "API"])
+        .unwrap_err();
+    let message = error.to_string();
+    assert!(message.contains("myconfig.slug"));
+    assert!(message.contains("^[a-z0-9-]+$"));
+
+    let config = <MyConfig as clap::Parser>::try_parse_from(["example", "--myconfig.tags", "a1"]);
+    assert!(config.is_err());
+}
+#[test]
+#[serial]
+fn test_port() {
+    #[config("port.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example", "--myconfig.http", "8443"]);
+    assert_eq!(config.http, 8443u16);
+    assert_eq!(config.admin, 9000u16);
+
+    for (flag, value) in [("--myconfig.http", "70000"), ("--myconfig.admin", "1000")] {
+        let config = <MyConfig as clap::Parser>::try_parse_from(["example", flag, value]);
+        assert!(config.is_err(), "{value} should be rejected");
+    }
+}
+#[test]
+fn test_strict_port() {
+    use rclap::parsers::PortValueParser;
+
+    let parse = |parser: PortValueParser, port: &str| {
+        clap::Command::new("example")
+            .arg(clap::Arg::new("port").long("port").value_parser(parser))
+            .try_get_matches_from(["example", "--port", port])
+            .map(|matches| *matches.get_one::<u16>("port").unwrap())
+    };
+    let denied = PortValueParser::new().can_bind(|port| Some(port >= 1024));
+    let err = parse(denied.clone().strict(true), "80").unwrap_err();
+    assert_eq!(err.kind(), clap::error::ErrorKind::ValueValidation);
+    assert!(err.to_string().contains("port 80 is privileged"));
+    assert_eq!(parse(denied.clone().strict(true), "8080").unwrap(), 8080);
+    assert_eq!(parse(denied, "80").unwrap(), 80);
+
+    let unknown = PortValueParser::new().strict(true).can_bind(|_| None);
+    assert_eq!(parse(unknown, "80").unwrap(), 80);
+}
+#[test]
+#[serial]
+fn test_bytesize() {
+    #[config("bytesize.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.cache, 10_000_000);
+    assert_eq!(config.upload, None);
+
+    let config = MyConfig::parse_from(["example", "--myconfig.upload", "1GiB"]);
+    assert_eq!(config.upload, Some(1 << 30));
+    let config = MyConfig::parse_from(["example", "--myconfig.cache", "1.5 kb"]);
+    assert_eq!(config.cache, 1500);
+    let config = MyConfig::parse_from(["example", "--myconfig.cache", "4096"]);
+    assert_eq!(config.cache, 4096);
+
+    let config =
+        <MyConfig as clap::Parser>::try_parse_from(["example", "--myconfig.cache", "10XB"]);
+    assert!(config.is_err());
+}
+#[test]
+#[serial]
+fn test_cidr() {
+    #[config("cidr.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.allow.len(), 2);
+    assert_eq!(config.allow[0].to_string(), "10.0.0.0/8");
+    assert_eq!(config.gateway.to_string(), "192.168.1.1/32");
+
+    unsafe {
+        std::env::set_var("ALLOW_NETS", "172.16.0.0/12,fd00::/8");
+    }
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.allow.len(), 2);
+    assert_eq!(config.allow[1].to_string(), "fd00::/8");
+    unsafe {
+        std::env::remove_var("ALLOW_NETS");
+    }
+
+    let config = <MyConfig as clap::Parser>::try_parse_from([
+        "example",
+        "--myconfig.gateway",
+        "10.0.0.0/33",
+    ]);
+    assert!(config.is_err());
+}
+#[test]
+#[serial]
+fn test_color() {
+    #[config("color.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.accent, rclap::Color::new(0x1e, 0x90, 0xff));
+    assert_eq!(config.background.to_string(), "#000000");
+    assert_eq!(
+        config.palette,
+        vec![rclap::Color::new(255, 0, 0), rclap::Color::new(0, 255, 0)]
+    );
+
+    let config = MyConfig::parse_from(["example", "--myconfig.background", "Navy"]);
+    assert_eq!(config.background, rclap::Color::new(0, 0, 0x80));
+
+    let config =
+        <MyConfig as clap::Parser>::try_parse_from(["example", "--myconfig.accent", "#12345"]);
+    assert!(config.is_err());
+}
+#[test]
+#[serial]
+fn test_list_values() {
+    #[config("list_values.toml")]
+    struct MyConfig;
+
+    fn list(field: &str) -> String {
+        let mut cmd = rclap::cli::with_list_values(<MyConfig as clap::CommandFactory>::command());
+        let matches = cmd
+            .try_get_matches_from_mut(["example", "--list-values", field])
+            .unwrap();
+        match rclap::cli::list_values(&mut cmd, &matches) {
+            Ok(listing) => listing.unwrap(),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    assert_eq!(list("format"), "json\ntext\nyaml\n");
+    assert_eq!(list("myconfig.level"), "Debug\nInfo\nWarn\n");
+    assert!(list("url").contains("does not have a fixed set of values"));
+    assert!(list("missing").contains("unknown field"));
+
+    // Returned rather than printed, so a reload or a test does not exit the process.
+    let err = MyConfig::try_parse_from(["example", "--list-values", "format"]).unwrap_err();
+    assert_eq!(err.kind(), clap::error::ErrorKind::DisplayHelp);
+    assert_eq!(err.to_string(), "json\ntext\nyaml\n");
+}
+#[test]
+#[serial]
+fn test_dump_config() {
+    #[config("dump_config.toml")]
+    struct MyConfig;
+
+    fn dump(args: &[&str]) -> Option<String> {
+        let mut cmd = MyConfig::command();
+        let matches = cmd.try_get_matches_from_mut(args).unwrap();
+        let config = <MyConfig as clap::FromArgMatches>::from_arg_matches(&matches).unwrap();
+        let sensitive = ["myconfig.database.password"];
+        rclap::serialize::dump_config(&mut cmd, &matches, &config, &sensitive).unwrap()
+    }
+
+    assert_eq!(dump(&["example"]), None);
+    let toml = dump(&["example", "--dump-config", "--myconfig.port", "9090"]).unwrap();
+    assert!(toml.contains("port = 9090\n"));
+    assert!(toml.contains("[database]\n"));
+    assert!(toml.contains("password = \"[REDACTED]\""));
+    assert!(!toml.contains("t0k3n"));
+    assert!(!toml.contains("hunter2"));
+
+    let json = dump(&["example", "--dump-config=json"]).unwrap();
+    assert!(json.contains(r#""port":8080"#));
+    assert!(json.contains(r#""password":"[REDACTED]""#));
+    assert!(!json.contains("t0k3n"));
+
+    let hidden = MyConfig::command().render_help().to_string();
+    assert!(!hidden.contains("dump-config"));
+    let yaml = MyConfig::command().try_get_matches_from(["example", "--dump-config=yaml"]);
+    assert!(yaml.is_err());
+
+    let err = MyConfig::try_parse_from(["example", "--dump-config"]).unwrap_err();
+    assert_eq!(err.kind(), clap::error::ErrorKind::DisplayHelp);
+    assert!(
+        err.to_string()
+            .starts_with("port = 8080\ntoken = \"[REDACTED]\"")
+    );
+}
+#[test]
+#[serial]
+fn test_print_config_template() {
+    #[config("config_template.toml")]
+    struct MyConfig;
+
+    assert_eq!(
+        MyConfig::config_template(),
+        r#"# Service name
+# name =
+
+# Listen port
+# Env: PORT
+# port = 8080
+
+[database]
+# Env: DB_URL
+# url = "localhost:5432"
+"#
+    );
+    // `name` is required, but not to print the template.
+    let matches = MyConfig::command()
+        .try_get_matches_from(["example", "--print-config-template"])
+        .unwrap();
+    assert!(matches.get_flag(rclap::cli::CONFIG_TEMPLATE_ID));
+    let err = MyConfig::try_parse_from(["example", "--print-config-template"]).unwrap_err();
+    assert_eq!(err.kind(), clap::error::ErrorKind::DisplayHelp);
+    assert_eq!(err.to_string(), MyConfig::config_template());
+    let help = MyConfig::command().render_help().to_string();
+    assert!(help.contains("--print-config-template"));
+}
+#[test]
+#[serial]
+fn test_dotenv() {
+    #[config("dotenv.toml")]
+    struct MyConfig;
+
+    unsafe {
+        std::env::set_var("DOTENV_REGION", "ap-south-1");
+    }
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.greeting, "hello from .env");
+    // Variables already set win over the file.
+    assert_eq!(config.region, "ap-south-1");
+    unsafe {
+        std::env::remove_var("DOTENV_GREETING");
+        std::env::remove_var("DOTENV_REGION");
+    }
+
+    assert!(rclap::cli::load_dotenv("tests/missing.env").is_ok());
+}
+#[test]
+#[serial]
+fn test_env_file() {
+    #[config("env_file.toml")]
+    struct MyConfig;
+
+    let secret = std::env::temp_dir().join("rclap_test_env_file");
+    std::fs::write(&secret, "s3cret\n").unwrap();
+    unsafe {
+        std::env::set_var("EF_PASSWORD_FILE", &secret);
+    }
+    let config = MyConfig::try_parse_with_layers(["example"], &[]).unwrap();
+    assert_eq!(config.password, "s3cret");
+    assert_eq!(config.user, "admin");
+
+    unsafe {
+        std::env::set_var("EF_PASSWORD", "from-env");
+    }
+    let config = MyConfig::try_parse_with_layers(["example"], &[]).unwrap();
+    assert_eq!(config.password, "from-env");
+    let config =
+        MyConfig::try_parse_with_layers(["example", "--myconfig.password", "cli"], &[]).unwrap();
+    assert_eq!(config.password, "cli");
+
+    unsafe {
+        std::env::remove_var("EF_PASSWORD");
+        std::env::set_var("EF_PASSWORD_FILE", "/nonexistent/rclap");
+    }
+    let err = MyConfig::try_parse_with_layers(["example"], &[]).unwrap_err();
+    assert!(err.to_string().contains("cannot read EF_PASSWORD_FILE"));
+    unsafe {
+        std::env::remove_var("EF_PASSWORD_FILE");
+    }
+    std::fs::remove_file(secret).unwrap();
+    assert!(MyConfig::try_parse_with_layers(["example"], &[]).is_err());
+}
+#[test]
+#[serial]
+// `XDG_CONFIG_HOME` only moves the config directory on Linux.
+#[cfg(target_os = "linux")]
+fn test_config_file_lookup() {
+    #[config("config_lookup.toml")]
+    struct MyConfig;
+
+    let home = std::env::temp_dir().join("rclap_test_xdg");
+    let dir = home.join("rclap-lookup-test");
+    std::fs::create_dir_all(&dir).unwrap();
+    unsafe {
+        std::env::set_var("XDG_CONFIG_HOME", &home);
+    }
+    assert_eq!(MyConfig::parse_from(["example"]).port, 8080);
+
+    std::fs::write(dir.join("lookup.toml"), "port = 9000").unwrap();
+    assert_eq!(MyConfig::parse_from(["example"]).port, 9000);
+    let given = home.join("given.toml");
+    std::fs::write(&given, "port = 9100").unwrap();
+    let config = MyConfig::parse_from(["example", "--config", given.to_str().unwrap()]);
+    assert_eq!(config.port, 9100);
+    unsafe {
+        std::env::remove_var("XDG_CONFIG_HOME");
+    }
+    std::fs::remove_dir_all(home).unwrap();
+}
+#[test]
+#[serial]
+fn test_watch() {
+    #[config("watch.toml")]
+    struct MyConfig;
+
+    assert!(MyConfig::watch_from(["example"], |_| {}).is_err());
+
+    let path = std::env::temp_dir().join("rclap_test_watch.toml");
+    std::fs::write(&path, "port = 9000").unwrap();
+    let args = ["example", "--config", path.to_str().unwrap()];
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let watcher = MyConfig::watch_from(args, move |config| {
+        let _ = sender.send(config.map(|config| config.port));
+    })
+    .unwrap();
+
+    // Replaced whole, as editors and config map updates do, not seen half-written.
+    let replace = |content: &str| {
+        let next = path.with_extension("next");
+        std::fs::write(&next, content).unwrap();
+        std::fs::rename(next, &path).unwrap();
+    };
+    replace("port = 9100");
+    let timeout = std::time::Duration::from_secs(5);
+    assert_eq!(receiver.recv_timeout(timeout).unwrap().unwrap(), 9100);
+    replace("port = \"x\"");
+    assert!(receiver.recv_timeout(timeout).unwrap().is_err());
+    drop(watcher);
+    std::fs::remove_file(path).unwrap();
+}
+#[test]
+#[serial]
+fn test_global() {
+    #[config("global_config.toml")]
+    struct MyConfig;
+
+    fn port() -> u16 {
+        MyConfig::global().port
+    }
+
+    let config = MyConfig::parse_from(["example", "--myconfig.port", "9000"]);
+    assert_eq!(MyConfig::init_with(config).unwrap().port, 9000);
+    assert_eq!(port(), 9000);
+    let again = MyConfig::parse_from(["example"]);
+    assert_eq!(MyConfig::init_with(again).unwrap_err().port, 8080);
+    assert_eq!(MyConfig::init().port, 9000);
+}
+#[test]
+#[serial]
+fn test_try_parse_from_with_env() {
+    #[config("env_file.toml")]
+    struct MyConfig;
+
+    let user = std::env::temp_dir().join("rclap_test_env_map_user");
+    std::fs::write(&user, "root\n").unwrap();
+    let env = std::collections::HashMap::from([
+        ("EF_PASSWORD".to_string(), "from-map".to_string()),
+        (
+            "EF_USER_FILE".to_string(),
+            user.to_string_lossy().into_owned(),
+        ),
+    ]);
+    let config = MyConfig::try_parse_from_with_env(["example"], &env).unwrap();
+    assert_eq!(config.password, "from-map");
+    assert_eq!(config.user, "root");
+    let args = ["example", "--myconfig.password", "cli"];
+    let config = MyConfig::try_parse_from_with_env(args, &env).unwrap();
+    assert_eq!(config.password, "cli");
+
+    let empty = std::collections::HashMap::new();
+    assert!(MyConfig::try_parse_from_with_env(["example"], &empty).is_err());
+
+    // The process environment is ignored when a map is given.
+    unsafe { std::env::set_var("EF_PASSWORD", "from-process") };
+    let from_map = MyConfig::try_parse_from_with_env(["example"], &env);
+    let from_empty = MyConfig::try_parse_from_with_env(["example"], &empty);
+    unsafe { std::env::remove_var("EF_PASSWORD") };
+    assert_eq!(from_map.unwrap().password, "from-map");
+    assert!(from_empty.is_err());
+    std::fs::remove_file(user).unwrap();
+}
+#[test]
+#[serial]
+fn test_try_parse_from() {
+    #[config("watch.toml")]
+    struct MyConfig;
+
+    let path = std::env::temp_dir().join("rclap_test_try_parse_from.toml");
+    std::fs::write(&path, "port = 9000").unwrap();
+    let args = ["example", "--config", path.to_str().unwrap()];
+    assert_eq!(MyConfig::try_parse_from(args).unwrap().port, 9000);
+    std::fs::write(&path, "port = \"x\"").unwrap();
+    assert!(MyConfig::try_parse_from(args).is_err());
+    std::fs::remove_file(path).unwrap();
+
+    let err = MyConfig::try_parse_from(["example", "--myconfig.port", "x"]).unwrap_err();
+    assert_eq!(err.kind(), clap::error::ErrorKind::ValueValidation);
+}
+#[test]
+#[serial]
+fn test_try_parse_from_display_flags() {
+    #[config("display_flags.toml")]
+    struct MyConfig;
+
+    for flag in [
+        &["--list-values", "level"][..],
+        &["--dump-config", "--myconfig.name", "api"],
+        &["--print-config-template"],
+        &["--help"],
+    ] {
+        let args = std::iter::once(&"example").chain(flag);
+        let err = MyConfig::try_parse_from(args).unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::DisplayHelp, "{flag:?}");
+        assert!(!err.use_stderr());
+        assert_eq!(err.exit_code(), 0);
+    }
+    let err = MyConfig::try_parse_from(["example", "--list-values", "name"]).unwrap_err();
+    assert_eq!(err.kind(), clap::error::ErrorKind::InvalidValue);
+}
+#[test]
+#[serial]
+fn test_url() {
+    #[config("url.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.endpoint.host_str(), Some("api.example.com"));
+    assert_eq!(config.webhook, None);
+
+    let config = MyConfig::parse_from([
+        "example",
+        "--myconfig.webhook",
+        "http://localhost:8080/hook",
+    ]);
+    assert_eq!(config.webhook.unwrap().port(), Some(8080));
+
+    let config =
+        <MyConfig as clap::Parser>::try_parse_from(["example", "--myconfig.endpoint", "api"]);
+    assert!(config.is_err());
+}
+#[test]
+#[serial]
+fn test_error_json() {
+    #[config("error_json.toml")]
+    struct MyConfig;
+
+    let mut cmd = <MyConfig as clap::CommandFactory>::command();
+    let error = <MyConfig as clap::Parser>::try_parse_from(["example"]).unwrap_err();
+    let json = rclap::cli::error_json(&mut cmd, &error);
+    assert!(json.starts_with(
+        r#"{"error":"missing_required","field":"database.url","env":"DB_URL","value":null,"#
+    ));
+
+    let error = <MyConfig as clap::Parser>::try_parse_from(["example", "--myconfig.port", "http"])
+        .unwrap_err();
+    let json = rclap::cli::error_json(&mut cmd, &error);
+    assert!(json.starts_with(
+        r#"{"error":"value_validation","field":"port","env":"JSON_PORT","value":"http","#
+    ));
+}
+#[test]
+#[serial]
+fn test_uuid() {
+    #[config("uuid.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(
+        config.tenant,
+        rclap::uuid::Uuid::parse_str("67e55044-10b1-426f-9247-bb680e5fe0c8").unwrap()
+    );
+    assert_eq!(config.cluster, None);
+
+    let config = MyConfig::parse_from([
+        "example",
+        "--myconfig.cluster",
+        "936da01f9abd4d9d80c702af85c822a8",
+    ]);
+    assert_eq!(
+        config.cluster.unwrap().to_string(),
+        "936da01f-9abd-4d9d-80c7-02af85c822a8"
+    );
+
+    let config =
+        <MyConfig as clap::Parser>::try_parse_from(["example", "--myconfig.cluster", "42"]);
+    assert!(config.is_err());
+}
+#[test]
+#[serial]
+fn test_parse_early() {
+    #[config("ranges.toml")]
+    struct MyConfig;
+
+    #[derive(clap::Parser, Debug)]
+    struct EarlyFlags {
+        #[arg(long)]
+        config: Option<std::path::PathBuf>,
+        #[arg(long)]
+        profile: Option<String>,
+        #[arg(short, long)]
+        verbose: bool,
+    }
+
+    let early: EarlyFlags = MyConfig::parse_early_from([
+        "example",
+        "--myconfig.port",
+        "8080",
+        "--config",
+        "app.toml",
+        "-v",
+        "--unknown=1",
+        "positional",
+        "--profile=prod",
+        "--help",
+    ]);
+    assert_eq!(early.config, Some("app.toml".into()));
+    assert_eq!(early.profile.as_deref(), Some("prod"));
+    assert!(early.verbose);
+
+    let early: EarlyFlags = MyConfig::parse_early_from(["example", "--config"]);
+    assert_eq!(early.config, None);
+    assert!(!early.verbose);
+}
+#[test]
+#[serial]
+fn test_integer_widths() {
+    #[config("integers.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    let port: u16 = config.port;
+    assert_eq!(port, 8080);
+    assert_eq!(config.retries.get(), 3);
+    assert_eq!(config.offsets, vec![-1i32, 2]);
+    assert_eq!(
+        config.workers,
+        vec![
+            std::num::NonZeroUsize::new(1).unwrap(),
+            std::num::NonZeroUsize::new(4).unwrap()
+        ]
+    );
+
+    let config = <MyConfig as clap::Parser>::try_parse_from(["example", "--myconfig.retries", "0"]);
+    assert!(config.is_err());
+    let config =
+        <MyConfig as clap::Parser>::try_parse_from(["example", "--myconfig.port", "70000"]);
+    assert!(config.is_err());
+}
+#[test]
+#[serial]
+fn test_datetime() {
+    #[config("datetime.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.since.to_string(), "2024-01-31T08:30:00Z");
+    let date = config.day.date.unwrap();
+    assert_eq!((date.year, date.month, date.day), (2024, 1, 31));
+    assert!(config.day.time.is_none());
+    assert_eq!(config.holidays.len(), 2);
+
+    let config = MyConfig::parse_from(["example", "--myconfig.since", "1979-05-27T07:32:00-08:00"]);
+    assert_eq!(config.since.to_string(), "1979-05-27T07:32:00-08:00");
+
+    let config =
+        <MyConfig as clap::Parser>::try_parse_from(["example", "--myconfig.day", "31/01/2024"]);
+    assert!(config.is_err());
+}
+#[test]
+#[serial]
+fn test_map() {
+    #[config("map.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.labels.get("env").map(String::as_str), Some("dev"));
+    assert!(config.upstream.headers.is_empty());
+
+    let config = MyConfig::parse_from([
+        "example",
+        "--myconfig.labels",
+        "env=prod",
+        "--myconfig.labels",
+        "team=core,tier=",
+        "--header",
+        "Accept=text/plain; X-Id=a,b",
+    ]);
+    assert_eq!(config.labels.len(), 3);
+    assert_eq!(config.labels["env"], "prod");
+    assert_eq!(config.labels["tier"], "");
+    assert_eq!(config.upstream.headers["X-Id"], "a,b");
+    assert_eq!(config.iter_map()["labels"], "env=prod,team=core,tier=");
+
+    unsafe { std::env::set_var("APP_LABELS", "region=eu,zone=b") };
+    let config = MyConfig::parse_from(["example"]);
+    unsafe { std::env::remove_var("APP_LABELS") };
+    assert_eq!(config.labels.len(), 2);
+    assert_eq!(config.labels["zone"], "b");
+
+    let config =
+        <MyConfig as clap::Parser>::try_parse_from(["example", "--myconfig.labels", "noequals"]);
+    assert!(config.is_err());
+}
+#[test]
+#[serial]
+fn test_locked() {
+    #[config("locked.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example", "--myconfig.level", "debug"]);
+    assert!(config.audit);
+    assert_eq!(config.storage.retention_days, 30);
+
+    unsafe { std::env::set_var("LOCKED_AUDIT", "false") };
+    let config = MyConfig::parse_from(["example"]);
+    assert!(!config.audit);
+
+    let mut cmd = <MyConfig as clap::CommandFactory>::command();
+    let locked = ["myconfig.audit", "myconfig.storage.retention_days"];
+    let matches = cmd.try_get_matches_from_mut(["example"]).unwrap();
+    assert!(rclap::cli::check_locked(&mut cmd, &matches, &locked).is_ok());
+    unsafe { std::env::remove_var("LOCKED_AUDIT") };
+
+    let matches = cmd
+        .try_get_matches_from_mut(["example", "--myconfig.audit"])
+        .unwrap();
+    let err = rclap::cli::check_locked(&mut cmd, &matches, &locked).unwrap_err();
+    assert_eq!(err.kind(), clap::error::ErrorKind::ArgumentConflict);
+    let message = err.to_string();
+    assert!(message.contains("locked by policy"), "{message}");
+    assert!(message.contains("set LOCKED_AUDIT instead"), "{message}");
+
+    let matches = cmd
+        .try_get_matches_from_mut(["example", "--myconfig.storage.retention_days", "1"])
+        .unwrap();
+    let err = rclap::cli::check_locked(&mut cmd, &matches, &locked).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("set it in the config file instead")
+    );
+}
+#[test]
+#[serial]
+fn test_provenance() {
+    use rclap::provenance::Source;
+
+    #[config("provenance.toml")]
+    struct MyConfig;
+
+    unsafe { std::env::set_var("PROV_HOST", "db.internal") };
+    let (_config, report) =
+        MyConfig::try_parse_with_provenance(["example", "--myconfig.port", "9090"], &[]).unwrap();
+    unsafe { std::env::remove_var("PROV_HOST") };
+
+    let host = report.get("host").unwrap();
+    assert_eq!(host.source, Source::Env);
+    assert_eq!(host.location.as_deref(), Some("PROV_HOST"));
+    assert_eq!(host.raw.as_deref(), Some("db.internal"));
+
+    let port = report.get("port").unwrap();
+    assert_eq!(port.source, Source::CommandLine);
+    assert_eq!(port.location.as_deref(), Some("--myconfig.port"));
+    assert_eq!(port.raw.as_deref(), Some("9090"));
+
+    let token = report.get("token").unwrap();
+    assert_eq!(token.source, Source::Default);
+    assert_eq!(token.location.as_deref(), Some("provenance.toml"));
+    assert!(token.redacted);
+    assert_eq!(token.raw.as_deref(), Some(rclap::provenance::REDACTED));
+
+    let motd = report.get("motd").unwrap();
+    assert_eq!(motd.source, Source::Unset);
+    assert_eq!(motd.raw, None);
+
+    let json = report.to_json_lines();
+    assert!(!json.contains("dev-token"));
+    assert!(json.contains(
+        r#"{"field":"port","source":"cli","location":"--myconfig.port","raw":"9090","redacted":false}"#
+    ));
+    assert!(
+        report
+            .to_string()
+            .contains("host = \"db.internal\" (env: PROV_HOST)")
+    );
+}
+mod redis_lib {
+    #[rclap::config_section("redis_section.toml")]
+    pub struct RedisConfig;
+}
+
+#[test]
+#[serial]
+fn test_config_section() {
+    #[config("config_section.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.cache.url, "redis://localhost:6379");
+    assert_eq!(config.cache.pool.size, 8);
+
+    let config = MyConfig::parse_from([
+        "example",
+        "--redisconfig.url",
+        "redis://cache:6379",
+        "--redisconfig.labels",
+        "tier=hot",
+    ]);
+    assert_eq!(config.cache.url, "redis://cache:6379");
+    assert_eq!(config.cache.labels["tier"], "hot");
+    assert_eq!(config.iter_map()["cache.pool.size"], "8");
+
+    use rclap::section::ConfigSection;
+    assert_eq!(
+        redis_lib::RedisConfig::FIELDS.len(),
+        3,
+        "{:?}",
+        redis_lib::RedisConfig::FIELDS
+    );
+    assert!(rclap::section::has_field(
+        redis_lib::RedisConfig::FIELDS,
+        "pool.size"
+    ));
+    assert_eq!(
+        rclap::section::field_type(redis_lib::RedisConfig::FIELDS, "pool.size"),
+        Some("u16")
+    );
+    assert_eq!(
+        rclap::section::field_type(redis_lib::RedisConfig::FIELDS, "labels"),
+        Some("std::collections::HashMap<String,String>")
+    );
+}
+#[test]
+#[serial]
+fn test_path_vec() {
+    use std::path::PathBuf;
+
+    #[config("paths.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(
+        config.include,
+        vec![PathBuf::from("/etc/app"), PathBuf::from("conf.d")]
+    );
+    assert!(config.plugins.is_empty());
+
+    let joined = std::env::join_paths(["/opt/a", "/opt/b"]).unwrap();
+    unsafe { std::env::set_var("APP_INCLUDE", &joined) };
+    unsafe { std::env::set_var("APP_PLUGINS", "a.so,b.so") };
+    let config = MyConfig::parse_from(["example"]);
+    unsafe { std::env::remove_var("APP_INCLUDE") };
+    unsafe { std::env::remove_var("APP_PLUGINS") };
+    assert_eq!(
+        config.include,
+        vec![PathBuf::from("/opt/a"), PathBuf::from("/opt/b")]
+    );
+    assert_eq!(
+        config.plugins,
+        vec![PathBuf::from("a.so"), PathBuf::from("b.so")]
+    );
+
+    let cmd = <MyConfig as clap::CommandFactory>::command();
+    let include = cmd
+        .get_arguments()
+        .find(|a| a.get_id() == "myconfig.include")
+        .unwrap();
+    assert_eq!(include.get_value_hint(), clap::ValueHint::AnyPath);
+}
+#[test]
+#[serial]
+fn test_optional_vec() {
+    #[config("optional_vec.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.tags, None);
+    assert_eq!(config.ports, Some(vec![80, 443]));
+    assert_eq!(config.dirs, None);
+    assert_eq!(config.iter_map()["tags"], "");
+
+    let config = MyConfig::parse_from(["example", "--myconfig.tags", "--myconfig.ports", "8080"]);
+    assert_eq!(config.tags, Some(vec![]));
+    assert_eq!(config.ports, Some(vec![8080]));
+
+    unsafe { std::env::set_var("OPT_TAGS", "a,b") };
+    let config = MyConfig::parse_from(["example", "--myconfig.dirs", "/tmp"]);
+    unsafe { std::env::remove_var("OPT_TAGS") };
+    assert_eq!(config.tags, Some(vec!["a".to_string(), "b".to_string()]));
+    assert_eq!(config.dirs, Some(vec![std::path::PathBuf::from("/tmp")]));
+    assert_eq!(config.iter_map()["dirs"], "/tmp");
+}
+#[test]
+#[serial]
+fn test_vec_defaults() {
+    #[config("vec_defaults.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.weights, vec![0.5f32, 1.0]);
+    assert_eq!(config.levels, vec![1u8, 2]);
+    assert_eq!(config.flags, vec![true, false]);
+    assert_eq!(config.offsets, vec![-3i16, 4]);
+    assert_eq!(config.palette[1].to_string(), "#00ff00");
+    assert_eq!(config.addresses, vec!["ops@example.com".to_string()]);
+}
+#[test]
+#[serial]
+fn test_delimiter() {
+    #[config("delimiter.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from([
+        "example",
+        "--myconfig.hosts",
+        "a;b,c",
+        "--myconfig.notes",
+        "one, two",
+    ]);
+    assert_eq!(config.hosts, vec!["a", "b,c"]);
+    assert_eq!(config.notes, vec!["one, two"]);
+
+    unsafe { std::env::set_var("DELIM_HOSTS", "x;y") };
+    unsafe { std::env::set_var("DELIM_NOTES", "hello, world") };
+    let config = MyConfig::parse_from(["example"]);
+    unsafe { std::env::remove_var("DELIM_HOSTS") };
+    unsafe { std::env::remove_var("DELIM_NOTES") };
+    assert_eq!(config.hosts, vec!["x", "y"]);
+    assert_eq!(config.notes, vec!["hello, world"]);
+}
+#[test]
+#[serial]
+fn test_vec_items() {
+    #[config("vec_items.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example", "--myconfig.replicas", "x", "y", "z"]);
+    assert_eq!(config.replicas, vec!["x", "y", "z"]);
+    assert_eq!(config.pair, None);
+
+    let config = MyConfig::parse_from(["example", "--myconfig.pair", "1", "2"]);
+    assert_eq!(config.pair, Some(vec![1, 2]));
+
+    let parse = |args: &[&str]| <MyConfig as clap::Parser>::try_parse_from(args);
+    let err = parse(&["example", "--myconfig.replicas", "a", "b", "c", "d"]).unwrap_err();
+    assert_eq!(err.kind(), clap::error::ErrorKind::UnknownArgument);
+    let err = parse(&["example", "--myconfig.pair", "1"]).unwrap_err();
+    assert_eq!(err.kind(), clap::error::ErrorKind::WrongNumberOfValues);
+    assert!(parse(&["example", "--myconfig.replicas"]).is_err());
+}
+#[test]
+#[serial]
+fn test_array_of_tables() {
+    #[config("listeners.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert!(config.listeners.is_empty());
+
+    let config = MyConfig::parse_from([
+        "example",
+        "--myconfig.listeners",
+        "host=127.0.0.1,port=9000,tls=true",
+        "--myconfig.listeners",
+        "port=9001",
+    ]);
+    assert_eq!(config.listeners.len(), 2);
+    assert_eq!(config.listeners[0].host, "127.0.0.1");
+    assert_eq!(config.listeners[0].port, 9000);
+    assert!(config.listeners[0].tls);
+    assert_eq!(config.listeners[1].host, "0.0.0.0");
+    assert_eq!(config.listeners[1].port, 9001);
+    assert!(!config.listeners[1].tls);
+    assert_eq!(
+        config.iter_map()["listeners"],
+        "host=127.0.0.1,port=9000,tls=true;host=0.0.0.0,port=9001,tls=false"
+    );
+
+    unsafe { std::env::set_var("APP_LISTENERS", "port=1;port=2") };
+    let config = MyConfig::parse_from(["example"]);
+    unsafe { std::env::remove_var("APP_LISTENERS") };
+    let ports: Vec<u16> = config.listeners.iter().map(|l| l.port).collect();
+    assert_eq!(ports, vec![1, 2]);
+
+    let parse = |args: &[&str]| <MyConfig as clap::Parser>::try_parse_from(args);
+    let err = parse(&["example", "--myconfig.listeners", "port=http"]).unwrap_err();
+    assert!(err.to_string().contains("invalid value"), "{err}");
+    let err = parse(&["example", "--myconfig.listeners", "addr=x"]).unwrap_err();
+    assert!(err.to_string().contains("unknown key 'addr'"), "{err}");
+}
+#[test]
+#[serial]
+fn test_map_of() {
+    #[config("map_of.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert!(config.upstreams.is_empty());
+
+    let config = MyConfig::parse_from([
+        "example",
+        "--myconfig.upstreams",
+        "primary:url=http://a:80,weight=3",
+        "--myconfig.upstreams",
+        "backup:",
+    ]);
+    let primary: &myconfig::UpstreamConfig = &config.upstreams["primary"];
+    assert_eq!(primary.url, "http://a:80");
+    assert_eq!(primary.weight, 3);
+    assert_eq!(config.upstreams["backup"].url, "http://localhost");
+    assert_eq!(
+        config.iter_map()["upstreams"],
+        "backup:url=http://localhost,weight=1;primary:url=http://a:80,weight=3"
+    );
+
+    unsafe { std::env::set_var("APP_UPSTREAMS", "a:weight=1;b:weight=2") };
+    let config = MyConfig::parse_from(["example"]);
+    unsafe { std::env::remove_var("APP_UPSTREAMS") };
+    assert_eq!(config.upstreams["b"].weight, 2);
+
+    let err = <MyConfig as clap::Parser>::try_parse_from(["example", "--myconfig.upstreams", "x"]);
+    assert!(err.is_err());
+}
+#[test]
+#[serial]
+fn test_optional_section() {
+    #[config("optional_section.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.tls, None);
+    assert!(!config.iter_map().contains_key("tls.ciphers"));
+
+    let config = MyConfig::parse_from([
+        "example",
+        "--myconfig.tls.cert",
+        "cert.pem",
+        "--myconfig.tls.key",
+        "key.pem",
+    ]);
+    let tls = config.tls.unwrap();
+    assert_eq!(tls.cert, std::path::PathBuf::from("cert.pem"));
+    assert_eq!(tls.ciphers, "modern");
+
+    unsafe { std::env::set_var("TLS_CERT", "env.pem") };
+    unsafe { std::env::set_var("TLS_KEY", "env.key") };
+    let config = MyConfig::parse_from(["example"]);
+    unsafe { std::env::remove_var("TLS_CERT") };
+    unsafe { std::env::remove_var("TLS_KEY") };
+    assert_eq!(config.tls.unwrap().key, std::path::PathBuf::from("env.key"));
+
+    // A given section still needs its required fields
+    let result =
+        <MyConfig as clap::Parser>::try_parse_from(["example", "--myconfig.tls.cert", "cert.pem"]);
+    assert!(result.is_err());
+}
+#[test]
+#[serial]
+fn test_env_prefix() {
+    #[config("env_prefix.toml")]
+    struct MyConfig;
+
+    unsafe { std::env::set_var("PORT", "1") };
+    unsafe { std::env::set_var("PREFIXED_DB_URL", "db:5432") };
+    let config = MyConfig::parse_from(["example"]);
+    unsafe { std::env::remove_var("PORT") };
+    unsafe { std::env::remove_var("PREFIXED_DB_URL") };
+    assert_eq!(config.port, 8080);
+    assert_eq!(config.database.url, "db:5432");
+}
+#[test]
+#[serial]
+fn test_auto_env() {
+    #[config("auto_env.toml")]
+    struct MyConfig;
+
+    unsafe { std::env::set_var("DATABASE_URL", "db:5432") };
+    unsafe { std::env::set_var("DATABASE_HOSTS", "b,c") };
+    let config = MyConfig::parse_from(["example"]);
+    unsafe { std::env::remove_var("DATABASE_URL") };
+    unsafe { std::env::remove_var("DATABASE_HOSTS") };
+    assert_eq!(config.port, 8080);
+    assert_eq!(config.database.url, "db:5432");
+    assert_eq!(config.database.hosts, vec!["b", "c"]);
+}
+#[test]
+#[serial]
+fn test_sensitive() {
+    #[config("sensitive.toml")]
+    struct MyConfig;
+
+    unsafe { std::env::set_var("API_TOKEN", "s3cr3t") };
+    unsafe { std::env::set_var("REGION", "us-east-1") };
+    let help = <MyConfig as clap::CommandFactory>::command()
+        .render_help()
+        .to_string();
+    unsafe { std::env::remove_var("API_TOKEN") };
+    unsafe { std::env::remove_var("REGION") };
+    assert!(!help.contains("s3cr3t"));
+    assert!(!help.contains("dev-token"));
+    assert!(help.contains("us-east-1"));
+    assert!(help.contains("eu-west-1"));
+
+    let mut cmd = <MyConfig as clap::CommandFactory>::command();
+    let err = cmd
+        .try_get_matches_from_mut(["example", "--myconfig.pin", "98x6"])
+        .unwrap_err();
+    let err = rclap::cli::redact_error(&cmd, err, &["myconfig.pin"]);
+    assert!(!err.to_string().contains("98x6"));
+    assert!(err.to_string().contains(rclap::provenance::REDACTED));
+}
+#[cfg(feature = "secrecy")]
+#[test]
+#[serial]
+fn test_secret_string() {
+    use rclap::secrecy::ExposeSecret;
+
+    #[config("secret_string.toml")]
+    struct MyConfig;
+
+    unsafe { std::env::set_var("API_KEY", "k-123") };
+    let (config, provenance) = MyConfig::try_parse_with_provenance(["example"], &[]).unwrap();
+    unsafe { std::env::remove_var("API_KEY") };
+    assert_eq!(config.api_key.expose_secret(), "k-123");
+    assert_eq!(config.db_password.expose_secret(), "changeme");
+    assert!(!format!("{config:?}").contains("k-123"));
+    assert!(provenance.get("api_key").unwrap().redacted);
+}
+#[test]
+#[serial]
+fn test_config_file() {
+    #[config("config_file.toml")]
+    struct MyConfig;
+
+    let path = std::env::temp_dir().join("rclap_test_config_file.toml");
+    std::fs::write(
+        &path,
+        r#"
+        port = 9000
+        tags = ["x", "y"]
+        labels = { env = "prod" }
+        verbose = true
+
+        [database]
+        url = "postgres://file"
+
+        [upstreams.api]
+        url = "http://api"
+        weight = 3
+        "#,
+    )
+    .unwrap();
+    let config_arg = path.to_str().unwrap();
+
+    let (config, provenance) =
+        MyConfig::try_parse_with_provenance(["example", "--config", config_arg], &[]).unwrap();
+    assert_eq!(config.port, 9000);
+    assert_eq!(config.tags, vec!["x", "y"]);
+    assert_eq!(config.labels["env"], "prod");
+    assert!(config.verbose);
+    assert_eq!(config.database.url, "postgres://file");
+    assert_eq!(config.upstreams["api"].url, "http://api");
+    assert_eq!(config.upstreams["api"].weight, 3);
+    assert_eq!(
+        provenance.get("port").unwrap().source,
+        rclap::provenance::Source::File
+    );
+
+    // The command line and the environment beat the file
+    unsafe { std::env::set_var("FILE_PORT", "9100") };
+    let config = MyConfig::parse_from([
+        "example",
+        "--config",
+        config_arg,
+        "--myconfig.database.url",
+        "postgres://cli",
+    ]);
+    unsafe { std::env::remove_var("FILE_PORT") };
+    assert_eq!(config.port, 9100);
+    assert_eq!(config.database.url, "postgres://cli");
+
+    std::fs::write(&path, "[database]\nname = \"x\"\n").unwrap();
+    let mut cmd =
+        rclap::config_file::with_config_file(<MyConfig as clap::CommandFactory>::command());
+    let err = rclap::config_file::ConfigFile::read(&mut cmd, &path).unwrap_err();
+    assert!(err.to_string().contains("unknown field 'database.name'"));
+    std::fs::remove_file(&path).unwrap();
+
+    let path = std::env::temp_dir().join("rclap_test_config_file.yaml");
+    std::fs::write(
+        &path,
+        "port: 9000\ntags: [x, y]\nlabels:\n  env: prod\nverbose: true\ndatabase:\n  url: postgres://file\nupstreams:\n  api:\n    url: http://api\n    weight: 3\n",
+    )
+    .unwrap();
+    let config = MyConfig::parse_from(["example", "--config", path.to_str().unwrap()]);
+    assert_eq!(config.port, 9000);
+    assert_eq!(config.tags, vec!["x", "y"]);
+    assert_eq!(config.labels["env"], "prod");
+    assert!(config.verbose);
+    assert_eq!(config.database.url, "postgres://file");
+    assert_eq!(config.upstreams["api"].weight, 3);
+
+    std::fs::write(&path, "database:\n  name: x\n").unwrap();
+    let err = rclap::config_file::ConfigFile::read(&mut cmd, &path).unwrap_err();
+    assert!(err.to_string().contains("unknown field 'database.name'"));
+    std::fs::write(&path, "port: [\n").unwrap();
+    let err = rclap::config_file::ConfigFile::read(&mut cmd, &path).unwrap_err();
+    assert!(err.to_string().contains("invalid config file"));
+    std::fs::remove_file(&path).unwrap();
+}
+#[test]
+#[serial]
+fn test_precedence() {
+    #[config("precedence.toml")]
+    struct MyConfig;
+
+    let path = std::env::temp_dir().join("rclap_test_precedence.toml");
+    std::fs::write(&path, "port = 9000\n").unwrap();
+    let config_arg = path.to_str().unwrap();
+
+    unsafe { std::env::set_var("PRECEDENCE_PORT", "9100") };
+    unsafe { std::env::set_var("PRECEDENCE_HOST", "env-host") };
+    let config = MyConfig::parse_from(["example", "--config", config_arg]);
+    assert_eq!(config.port, 9000);
+    assert_eq!(config.host, "env-host");
+    let config = MyConfig::parse_from(["example", "--config", config_arg, "--myconfig.port", "1"]);
+    unsafe { std::env::remove_var("PRECEDENCE_PORT") };
+    unsafe { std::env::remove_var("PRECEDENCE_HOST") };
+    assert_eq!(config.port, 1);
+
+    // The same order with injected env vars.
+    let env = std::collections::HashMap::from([
+        ("PRECEDENCE_PORT".to_string(), "9100".to_string()),
+        ("PRECEDENCE_HOST".to_string(), "map-host".to_string()),
+    ]);
+    let config = MyConfig::try_parse_from_with_env(["example", "--config", config_arg], &env);
+    let config = config.unwrap();
+    assert_eq!(config.port, 9000);
+    assert_eq!(config.host, "map-host");
+    std::fs::remove_file(&path).unwrap();
+}
+#[test]
+#[serial]
+fn test_custom_layer() {
+    use rclap::layer::{Layer, Resolved};
+    use rclap::provenance::Source;
+
+    #[config("env_prefix.toml")]
+    struct MyConfig;
+
+    struct Vault;
+    impl Layer for Vault {
+        fn source(&self) -> Source {
+            Source::Custom("vault")
+        }
+        fn values(&self, arg: &clap::Arg) -> Option<Vec<String>> {
+            (arg.get_id() == "myconfig.database.url").then(|| vec!["vault:5432".to_string()])
+        }
+    }
+
+    let (config, provenance) = MyConfig::try_parse_with_provenance(["example"], &[&Vault]).unwrap();
+    assert_eq!(config.database.url, "vault:5432");
+    assert_eq!(config.port, 8080);
+    assert_eq!(
+        provenance.get("database.url").unwrap().source,
+        Source::Custom("vault")
+    );
+
+    // The environment still beats custom layers
+    unsafe { std::env::set_var("PREFIXED_DB_URL", "env:5432") };
+    let config = MyConfig::try_parse_with_layers(["example"], &[&Vault]).unwrap();
+    unsafe { std::env::remove_var("PREFIXED_DB_URL") };
+    assert_eq!(config.database.url, "env:5432");
+
+    let cmd = <MyConfig as clap::CommandFactory>::command();
+    let (_, resolved) = rclap::layer::resolve(cmd, &[&rclap::layer::CliLayer, &Vault]);
+    assert_eq!(
+        resolved,
+        [Resolved {
+            id: "myconfig.database.url".to_string(),
+            source: Source::Custom("vault"),
+            location: None,
+        }]
+    );
+}
+#[test]
+#[serial]
+fn test_default_impl() {
+    #[config(path = "default.toml", default = true)]
+    struct MyConfig;
+
+    unsafe { std::env::set_var("DEFAULT_PORT", "1") };
+    let config = MyConfig::default();
+    unsafe { std::env::remove_var("DEFAULT_PORT") };
+    assert_eq!(config.port, 8080);
+    assert_eq!(config.size, 1000);
+    assert!(!config.verbose);
+    assert_eq!(config.tags, vec!["a", "b"]);
+    assert_eq!(config.labels["env"], "dev");
+    assert_eq!(config.cache, None);
+    assert_eq!(config.database, DatabaseConfig::default());
+    assert_eq!(config, MyConfig::parse_from(["example"]));
+}
+#[test]
+#[serial]
+fn test_builder() {
+    #[config("builder.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::builder()
+        .host("example.org")
+        .database(DatabaseConfig::builder().pool(16).build().unwrap())
+        .build()
+        .unwrap();
+    assert_eq!(config.port, 8080);
+    assert_eq!(config.host, "example.org");
+    assert_eq!(config.tags, vec!["a"]);
+    assert_eq!(config.labels["env"], "dev");
+    assert_eq!(config.cache, None);
+    assert_eq!(config.database.url, "localhost:5432");
+    assert_eq!(config.database.pool, 16);
+
+    let config = MyConfig::builder()
+        .host("h")
+        .port(9000)
+        .tags(vec!["x".to_string(), "y".to_string()])
+        .cache("/tmp")
+        .build()
+        .unwrap();
+    assert_eq!(config.port, 9000);
+    assert_eq!(config.tags, vec!["x", "y"]);
+    assert_eq!(config.cache.as_deref(), Some("/tmp"));
+    assert_eq!(config.database.pool, 4);
+
+    let err = MyConfig::builder().build().unwrap_err();
+    assert!(err.to_string().contains("field 'host' has no default"));
+}
+#[test]
+#[serial]
+fn test_serialize() {
+    #[config(path = "serde.toml", serde = true)]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    let toml = config.to_toml_string().unwrap();
+    assert!(toml.contains("port = 8080\n"));
+    assert!(toml.contains("level = \"Info\"\n"));
+    assert!(toml.contains("[database]\nurl = \"localhost:5432\"\n"));
+    assert!(!toml.contains("t0k3n"));
+    assert!(!toml.contains("cache"));
+
+    let json = config.to_json_string().unwrap();
+    assert!(json.contains(r#""port":8080"#));
+    assert!(json.contains(r#""tags":["a","b"]"#));
+    assert!(json.contains(r#""database":{"url":"localhost:5432"}"#));
+    assert!(!json.contains("t0k3n"));
+    assert!(!json.contains("cache"));
+}
+
+#[test]
+#[serial]
+fn test_deserialize() {
+    #[config(path = "serde.toml", serde = true, deserialize = true)]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example", "--myconfig.port", "9090"]);
+    let saved = config.to_toml_string().unwrap();
+    let loaded = MyConfig::from_toml_str(&saved).unwrap();
+    assert_eq!(loaded.port, 9090);
+    assert_eq!(loaded.database, config.database);
+    assert_eq!(loaded.labels, config.labels);
+    assert_eq!(loaded.cache, None);
+
+    let dotted = MyConfig::from_toml_str(
+        r#"
+        port = 1
+        level = "Debug"
+        tags = []
+        labels = {}
+        token = "t"
+        database.url = "db:5432"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(dotted.level, myconfig::Level::Debug);
+    assert_eq!(dotted.database.url, "db:5432");
+    assert!(MyConfig::from_toml_str("port = 1").is_err());
+}
+
+#[test]
+#[serial]
+fn test_json_schema() {
+    #[config(path = "schema.toml", schema = true)]
+    struct MyConfig;
+
+    let schema = rclap::schemars::schema_for!(MyConfig);
+    let at = |pointer: &str| schema.pointer(pointer).and_then(|value| value.as_str());
+    assert_eq!(at("/properties/port/type"), Some("integer"));
+    assert_eq!(
+        at("/properties/port/description"),
+        Some("Port to listen on")
+    );
+    assert_eq!(at("/properties/zone/type/0"), Some("string"));
+    assert_eq!(at("/properties/endpoints/items/type"), Some("string"));
+    assert_eq!(at("/properties/token/type"), Some("string"));
+    assert_eq!(
+        schema
+            .pointer("/properties/token/writeOnly")
+            .and_then(|value| value.as_bool()),
+        Some(true)
+    );
+    assert_eq!(
+        at("/properties/database/$ref"),
+        Some("#/$defs/DatabaseConfig")
+    );
+    assert!(
+        schema
+            .as_value()
+            .to_string()
+            .contains(r#""enum":["Debug","Info"]"#)
+    );
+}
+
+#[test]
+#[serial]
+fn test_extra_derives() {
+    #[config(path = "derives.toml", derives = ["Eq", "Hash", "Debug"])]
+    struct MyConfig;
+
+    let set: std::collections::HashSet<MyConfig> = [
+        MyConfig::parse_from(["example"]),
+        MyConfig::parse_from(["example"]),
+        MyConfig::parse_from(["example", "--myconfig.port", "9090"]),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(set.len(), 2);
+
+    let first = MyConfig::parse_from(["example", "--myconfig.host", "a"]);
+    let second = MyConfig::parse_from(["example", "--myconfig.host", "b"]);
+    assert!(first.database <= second.database);
+    assert!(first < second);
+}
+
+#[test]
+#[serial]
+fn test_ignore_case() {
+    #[config("ignore_case.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example", "--myconfig.level", "warn"]);
+    assert_eq!(config.level, myconfig::Level::Warn);
+
+    unsafe { std::env::set_var("LOG_LEVEL", "DEBUG") };
+    let config = MyConfig::parse_from(["example"]);
+    unsafe { std::env::remove_var("LOG_LEVEL") };
+    assert_eq!(config.level, myconfig::Level::Debug);
+
+    assert!(
+        MyConfig::try_parse_with_layers(["example", "--myconfig.format", "json"], &[]).is_err()
+    );
+}
+
+#[test]
+#[serial]
+fn test_external_enum() {
+    #[config("external_enum.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.qualified, MyEnum::B);
+    assert_eq!(config.bare, MyEnum::C);
+    assert_eq!(config.missing, None);
+
+    unsafe { std::env::set_var("BARE_ENUM", "a") };
+    let config = MyConfig::parse_from(["example", "--myconfig.missing", "b"]);
+    unsafe { std::env::remove_var("BARE_ENUM") };
+    assert_eq!(config.bare, MyEnum::A);
+    assert_eq!(config.missing, Some(MyEnum::B));
+}
+
+#[test]
+#[serial]
+fn test_external_args() {
+    #[config("external_args.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example", "--log-level", "debug"]);
+    assert_eq!(config.logging.log_level, "debug");
+    assert_eq!(config.port, 8080);
+    assert!(config.iter_map()["logging"].contains("debug"));
+
+    let config = MyConfig::builder().build().unwrap();
+    assert_eq!(config.logging.log_level, "info");
+}
+
+#[test]
+#[serial]
+fn test_reused_section_type() {
+    #[config("reuse.toml")]
+    struct MyConfig;
+
+    fn pool(connection: &myconfig::ConnectionConfig) -> u32 {
+        connection.pool_size
+    }
+
+    unsafe { std::env::set_var("REPLICA_URL", "replica:6432") };
+    let config = MyConfig::parse_from(["example", "--myconfig.database.primary.pool_size", "20"]);
+    unsafe { std::env::remove_var("REPLICA_URL") };
+    assert_eq!(pool(&config.database.primary), 20);
+    assert_eq!(pool(&config.database.replica), 5);
+    assert_eq!(config.database.primary.url, "primary:5432");
+    assert_eq!(config.database.replica.url, "replica:6432");
+    assert_eq!(config.database.primary.labels["role"], "rw");
+    assert_eq!(config.database.replica.labels["role"], "ro");
+    assert_eq!(config.iter_map()["database.replica.pool_size"], "5");
+
+    let config = MyConfig::builder().build().unwrap();
+    assert_eq!(config.database.replica.pool_size, 5);
+}
+
+#[test]
+#[serial]
+fn test_extends() {
+    #[config("extends.toml")]
+    struct MyConfig;
+
+    unsafe { std::env::set_var("PROD_URL", "prod.example.org") };
+    let config = MyConfig::parse_from(["example", "--myconfig.prod.timeout", "60"]);
+    unsafe { std::env::remove_var("PROD_URL") };
+    assert_eq!(config.staging.url, "staging.internal");
+    assert_eq!(config.staging.timeout, 30);
+    assert_eq!(config.prod.url, "prod.example.org");
+    assert_eq!(config.prod.timeout, 60);
+    assert_eq!(config.prod.retries, 3);
+}
+
+#[test]
+#[serial]
+fn test_include() {
+    #[config("include.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example", "--myconfig.logging.json"]);
+    assert_eq!(config.port, 8080);
+    assert_eq!(config.logging.level, "warn");
+    assert!(config.logging.json);
+}
+
+#[test]
+#[serial]
+fn test_profile() {
+    #[config(path = "profiles.toml", profile = "prod")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.port, 80);
+    assert_eq!(config.log_level, "warn");
+}
+
+#[test]
+#[serial]
+fn test_cfg_fields() {
+    #[config(path = "cfg.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.port, 8080);
+    #[cfg(feature = "metrics")]
+    {
+        assert_eq!(config.metrics_port, 9100);
+        assert_eq!(config.labels.get("env").map(String::as_str), Some("dev"));
+        assert!(!config.iter_map().contains_key("tracing.endpoint"));
+    }
+    #[cfg(not(feature = "metrics"))]
+    {
+        assert_eq!(config.tracing.endpoint, "http://localhost:4317");
+        assert!(!config.iter_map().contains_key("metrics_port"));
+        let (_, provenance) = MyConfig::try_parse_with_provenance(["example"], &[]).unwrap();
+        assert!(provenance.get("metrics_port").is_none());
+        assert!(
+            MyConfig::try_parse_with_layers(["example", "--myconfig.metrics_port", "9200"], &[])
+                .is_err()
+        );
+    }
+}
+
+#[test]
+#[serial]
+fn test_attribute_overrides() {
+    #[config("port.toml", override(http.default = "9090", admin.env = "ADMIN_PORT"))]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.http, 9090u16);
+    assert_eq!(config.admin, 9000u16);
+
+    unsafe {
+        std::env::set_var("ADMIN_PORT", "9443");
+    }
+    let config = MyConfig::parse_from(["example"]);
+    unsafe {
+        std::env::remove_var("ADMIN_PORT");
+    }
+    assert_eq!(config.admin, 9443u16);
+}
+
+#[test]
+#[serial]
+fn test_multiple_paths() {
+    #[config(paths = ["port.toml", "port_overrides.toml"])]
+    struct MyConfig;
+
+    let (config, provenance) =
+        MyConfig::try_parse_with_provenance(["example", "--myconfig.tls"], &[]).unwrap();
+    assert_eq!(config.http, 8443u16);
+    assert_eq!(config.admin, 9000u16);
+    assert!(config.tls);
+    assert_eq!(
+        provenance.get("http").unwrap().location.as_deref(),
+        Some("port.toml, port_overrides.toml")
+    );
+}
+
+#[test]
+#[serial]
+fn test_env_path_fallback() {
+    #[config(env_path = "RCLAP_EXAMPLE_UNSET_SPEC", path = "port.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.http, 8080u16);
+}
+
+#[test]
+#[serial]
+fn test_source_relative_path() {
+    #[config("./source_relative.toml", relative = "source")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.greeting, "hello from tests/");
+}
+
+#[test]
+#[serial]
+fn test_custom_module() {
+    #[config(path = "port.toml", module = "port_config")]
+    struct MyConfig;
+    #[config(path = "url.toml", module = "url_config")]
+    struct Myconfig;
+
+    let config = port_config::MyConfig::parse_from(["example"]);
+    assert_eq!(config.http, 8080u16);
+    assert!(url_config::Myconfig::try_parse_with_layers(["example"], &[]).is_ok());
+}
+
+#[test]
+#[serial]
+fn test_user_fields() {
+    #[config("port.toml")]
+    struct MyConfig {
+        /// Print every request
+        #[arg(long, short)]
+        verbose: bool,
+        #[arg(long, default_value = "server")]
+        name: String,
+    }
+
+    let config = MyConfig::parse_from(["example", "-v", "--myconfig.http", "8443"]);
+    assert!(config.verbose);
+    assert_eq!(config.name, "server");
+    assert_eq!(config.http, 8443u16);
+    assert!(!config.iter_map().contains_key("verbose"));
+
+    let config = MyConfig::builder().verbose(true).build().unwrap();
+    assert!(config.verbose);
+    assert_eq!(config.name, "");
+    assert_eq!(config.http, 8080u16);
+}
+
+#[test]
+#[serial]
+fn test_derive_config() {
+    #[derive(Debug, PartialEq, rclap::Config)]
+    #[config(path = "derive_config.toml")]
+    struct Settings {
+        host: String,
+        port: u16,
+        tags: Vec<String>,
+        token: Option<String>,
+        verbose: bool,
+        #[config(id = "database.url")]
+        database_url: String,
+    }
+
+    let settings = <Settings as clap::Parser>::parse_from(["example"]);
+    assert_eq!(
+        settings,
+        Settings {
+            host: "localhost".to_string(),
+            port: 8080,
+            tags: vec!["a".to_string(), "b".to_string()],
+            token: None,
+            verbose: false,
+            database_url: "localhost:5432".to_string(),
+        }
+    );
+
+    unsafe {
+        std::env::set_var("DERIVE_HOST", "example.org");
+    }
+    let settings = <Settings as clap::Parser>::parse_from([
+        "example",
+        "-p",
+        "9000",
+        "--db-url",
+        "db:5432",
+        "--settings.verbose",
+        "--settings.token",
+        "t0k3n",
+    ]);
+    unsafe {
+        std::env::remove_var("DERIVE_HOST");
+    }
+    assert_eq!(settings.host, "example.org");
+    assert_eq!(settings.port, 9000);
+    assert_eq!(settings.database_url, "db:5432");
+    assert!(settings.verbose);
+    assert_eq!(settings.token.as_deref(), Some("t0k3n"));
+}
+
+#[test]
+#[serial]
+fn test_config_module() {
+    #[config(path = "settings.toml")]
+    mod settings {
+        pub fn pool_size(database: &DatabaseConfig) -> u32 {
+            database.pool_size
+        }
+    }
+
+    fn log_level(logging: &settings::LoggingConfig) -> &str {
+        &logging.level
+    }
+
+    let config = settings::Settings::parse_from(["example", "--settings.logging.level", "debug"]);
+    assert_eq!(config.name, "app");
+    assert_eq!(config.database.url, "localhost:5432");
+    assert_eq!(settings::pool_size(&config.database), 10);
+    assert_eq!(log_level(&config.logging), "debug");
+}
+#[test]
+#[serial]
+fn test_config_str() {
+    use rclap::provenance::Source;
+
+    rclap::config_str!(
+        InlineConfig,
+        r#"
+        port = { type = "u16", default = "8080", doc = "Listen port" }
+        [database]
+        url = { type = "String", default = "localhost:5432" }
+        "#,
+        profile = "prod"
+    );
+
+    let (config, provenance) =
+        InlineConfig::try_parse_with_provenance(["example", "--inlineconfig.port", "9090"], &[])
+            .unwrap();
+    assert_eq!(config.port, 9090);
+    assert_eq!(config.database.url, "localhost:5432");
+
+    let url = provenance.get("database.url").unwrap();
+    assert_eq!(url.source, Source::Default);
+    assert_eq!(url.location.as_deref(), Some("<inline spec>"));
+}
+mod build_script {
+    include!(concat!(env!("OUT_DIR"), "/build_config.rs"));
+}
+#[test]
+fn test_build_script_config() {
+    use build_script::BuildConfig;
+
+    let config = BuildConfig::parse_from(["example", "--buildconfig.port", "9090"]);
+    assert_eq!(config.port, 9090);
+    assert_eq!(config.database.url, "localhost:5432");
+    assert!(config.to_toml_string().unwrap().contains("port = 9090\n"));
+}
+#[test]
+fn test_debug_expand() {
+    #[config(path = "port.toml", debug_expand = true, module = "expanded")]
+    struct ExpandedConfig;
+
+    let expanded =
+        std::fs::read_to_string(concat!(env!("OUT_DIR"), "/rclap-expand/expanded.rs")).unwrap();
+    assert!(expanded.contains("pub struct ExpandedConfig {"));
+    assert!(expanded.contains("pub mod expanded {"));
+}
+#[test]
+fn test_app_metadata() {
+    #[config(path = "app_metadata.toml")]
+    struct MyConfig;
+
+    let cmd = MyConfig::command();
+    assert_eq!(cmd.get_name(), "myapp");
+    assert_eq!(cmd.get_version(), Some("1.4.0"));
+    assert_eq!(cmd.get_about().unwrap().to_string(), "Serves the API");
+    assert_eq!(
+        cmd.get_long_about().unwrap().to_string(),
+        "Serves the API over HTTP."
+    );
+    assert_eq!(cmd.get_author(), Some("Jane Doe"));
+    assert_eq!(
+        cmd.get_before_help().unwrap().to_string(),
+        "Part of the payments platform."
+    );
+    let after_help = cmd.get_after_help().unwrap().to_string();
+    assert!(after_help.starts_with("Examples:\n  myapp --myconfig.port 9090\n"));
+    assert!(
+        MyConfig::command()
+            .render_help()
+            .to_string()
+            .ends_with("Runbook: https://example.com/runbooks/myapp\n")
+    );
+
+    let err = MyConfig::try_parse_with_layers(["myapp", "--version"], &[]).unwrap_err();
+    assert_eq!(err.kind(), clap::error::ErrorKind::DisplayVersion);
+    assert_eq!(err.to_string(), "myapp 1.4.0\n");
+}
+#[test]
+fn test_cargo_version() {
+    #[config(path = "cargo_version.toml")]
+    struct MyConfig;
+
+    let err = MyConfig::try_parse_with_layers(["myapp", "-V"], &[]).unwrap_err();
+    assert_eq!(err.kind(), clap::error::ErrorKind::DisplayVersion);
+    assert_eq!(
+        err.to_string(),
+        format!("myapp {} (abc1234)\n", env!("CARGO_PKG_VERSION"))
+    );
+}
+#[test]
+fn test_help_headings() {
+    #[config(path = "headings.toml")]
+    struct Headings;
+
+    let help = Headings::command().render_help().to_string();
+    let section = |heading: &str| {
+        let start = help.find(&format!("{heading}:\n")).unwrap();
+        let rest = &help[start..];
+        rest[..rest[1..].find("\n\n").map_or(rest.len(), |end| end + 1)].to_string()
+    };
+    assert!(section("Options").contains("--headings.port"));
+    assert!(section("Options").contains("--headings.labels"));
+    assert!(section("Http Server").contains("--headings.http_server.timeout"));
+    assert!(section("Http Server").contains("--headings.http_server.headers"));
+    assert!(section("Storage").contains("--headings.database.url"));
+    assert!(!section("Storage").contains("replica"));
+    assert!(section("Replica").contains("--headings.database.replica.url"));
+}
+#[test]
+fn test_long_doc() {
+    #[config(path = "long_doc.toml")]
+    struct MyConfig;
+
+    let short = MyConfig::command().render_help().to_string();
+    assert!(short.contains("Listen port"));
+    assert!(!short.contains("elevated privileges"));
+
+    let long = MyConfig::command().render_long_help().to_string();
+    assert!(long.contains("Port the HTTP listener binds to.\n"));
+    assert!(long.contains("Ports below 1024 need elevated privileges on most systems."));
+    assert!(long.contains("Labels attached to every metric, as key=value pairs."));
+}
+#[test]
+fn test_help_format() {
+    #[config(path = "help_format.toml")]
+    struct MyConfig;
+
+    let help = MyConfig::command().render_help().to_string();
+    assert!(help.starts_with("myapp\nOptions:\n"));
+    assert!(help.contains(
+        "      --myconfig.port <myconfig.port>\n          Port the HTTP listener binds\n"
+    ));
+    assert!(help.lines().all(|line| line.len() <= 40));
+}
+#[test]
+fn test_help_style() {
+    #[config(path = "style.toml")]
+    struct MyConfig;
+
+    let help = MyConfig::command().render_help().ansi().to_string();
+    assert!(help.contains("\u{1b}[1m\u{1b}[32mOptions:\u{1b}[0m"));
+    assert!(help.contains("\u{1b}[4m\u{1b}[94mUsage:\u{1b}[0m"));
+    assert!(help.contains("\u{1b}[38;2;255;136;00m--myconfig.port\u{1b}[0m"));
+}
+#[test]
+fn test_long_style() {
+    #[config(path = "long_style.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from([
+        "example",
+        "--log-level",
+        "debug",
+        "--database-primary-url",
+        "db:5432",
+    ]);
+    assert_eq!(config.log_level, "debug");
+    assert_eq!(config.database.primary.url, "db:5432");
+}
+#[test]
+fn test_disable_help_flag() {
+    #[config(path = "own_help.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example", "-h", "example.com", "--help"]);
+    assert_eq!(config.host, "example.com");
+    assert!(config.help);
+}
+#[test]
+fn test_keyword_fields() {
+    #[config(path = "keywords.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from([
+        "example",
+        "--type",
+        "udp",
+        "--match",
+        "a",
+        "--loop-ref",
+        "dev",
+    ]);
+    assert_eq!(config.r#type, "udp");
+    assert_eq!(config.r#match, vec!["a".to_string()]);
+    assert_eq!(config.r#loop.r#ref, "dev");
+    assert_eq!(
+        config.iter_map().get("type").map(String::as_str),
+        Some("udp")
+    );
+}
+#[test]
+fn test_aliases() {
+    #[config(path = "aliases.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example", "--addr", "db:80", "--tag", "team=core"]);
+    assert_eq!(config.endpoint, "db:80");
+    assert_eq!(config.labels.get("team").map(String::as_str), Some("core"));
+    let config = MyConfig::parse_from(["example", "--address", "api:80"]);
+    assert_eq!(config.endpoint, "api:80");
+
+    let help = <MyConfig as clap::CommandFactory>::command()
+        .render_help()
+        .to_string();
+    assert!(help.contains("[aliases: --addr, --address]"));
+}
+#[test]
+fn test_deprecated_field() {
+    #[config(path = "deprecated.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example", "--addr", "db:80"]);
+    assert_eq!(config.addr.as_deref(), Some("db:80"));
+
+    let help = <MyConfig as clap::CommandFactory>::command()
+        .render_help()
+        .to_string();
+    assert!(help.contains("Server endpoint"));
+    assert!(!help.contains("--addr"));
+}
+#[test]
+fn test_default_if() {
+    #[config(path = "default_if.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.log_level, "debug");
+    let config = MyConfig::parse_from(["example", "--env", "prod"]);
+    assert_eq!(config.log_level, "warn");
+    let config = MyConfig::parse_from(["example", "--env", "prod", "--log-level", "info"]);
+    assert_eq!(config.log_level, "info");
+}
+#[test]
+fn test_default_missing_value() {
+    #[config(path = "default_missing.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.color, "auto");
+    let config = MyConfig::parse_from(["example", "--color"]);
+    assert_eq!(config.color, "always");
+    let config = MyConfig::parse_from(["example", "--color", "never", "--level"]);
+    assert_eq!(config.color, "never");
+    assert_eq!(config.level, myconfig::Level::Debug);
+}
+#[test]
+fn test_required_if() {
+    #[config(path = "required_if.toml")]
+    struct MyConfig;
+
+    let config = <MyConfig as clap::Parser>::try_parse_from(["example", "--token", "t"]).unwrap();
+    assert_eq!(config.cert, None);
+    let err = <MyConfig as clap::Parser>::try_parse_from(["example", "--token", "t", "--tls"])
+        .unwrap_err();
+    assert_eq!(err.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+    let config = <MyConfig as clap::Parser>::try_parse_from([
+        "example", "--token", "t", "--tls", "--cert", "c.pem",
+    ])
+    .unwrap();
+    assert_eq!(config.cert.as_deref(), Some("c.pem"));
+
+    let err = <MyConfig as clap::Parser>::try_parse_from(["example"]).unwrap_err();
+    assert!(err.to_string().contains("--password"));
+    let config =
+        <MyConfig as clap::Parser>::try_parse_from(["example", "--password", "p"]).unwrap();
+    assert_eq!(config.password.as_deref(), Some("p"));
+}
+#[test]
+fn test_overrides_with() {
+    #[config(path = "overrides.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example", "--verbose", "--quiet"]);
+    assert!(!config.verbose);
+    assert!(config.quiet);
+    let config = MyConfig::parse_from(["example", "--quiet", "--verbose"]);
+    assert!(config.verbose);
+    assert!(!config.quiet);
+}
+#[derive(Debug, Clone, PartialEq, clap::Subcommand)]
+enum GlobalCommand {
+    Deploy,
+}
+
+#[test]
+fn test_global_fields() {
+    #[config(path = "global.toml")]
+    struct MyConfig {
+        #[command(subcommand)]
+        command: Option<crate::GlobalCommand>,
+    }
+
+    let config = MyConfig::parse_from(["example", "deploy", "--verbose", "--region", "us-east-1"]);
+    assert_eq!(config.command, Some(GlobalCommand::Deploy));
+    assert!(config.verbose);
+    assert_eq!(config.region, "us-east-1");
+}
+#[test]
+fn test_trailing_args() {
+    #[config(path = "trailing.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example", "ls", "-la", "--color"]);
+    assert_eq!(config.program, vec!["ls", "-la", "--color"]);
+    let config = MyConfig::parse_from(["example"]);
+    assert!(config.program.is_empty());
+}
+
+#[test]
+fn test_raw_trailing_args() {
+    #[config(path = "passthrough.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example", "--verbose", "--", "--verbose", "-x"]);
+    assert!(config.verbose);
+    assert_eq!(config.args, vec!["--verbose", "-x"]);
+    assert!(<MyConfig as clap::Parser>::try_parse_from(["example", "child"]).is_err());
+}
+#[test]
+fn test_external_subcommands() {
+    #[config(path = "plugins.toml")]
+    struct MyConfig;
+
+    let config = MyConfig::parse_from(["example", "--verbose", "lint", "--fix", "src"]);
+    assert!(config.verbose);
+    assert_eq!(
+        config.command,
+        Some(myconfig::Commands::External(vec![
+            "lint".into(),
+            "--fix".into(),
+            "src".into()
+        ]))
+    );
+    let config = MyConfig::parse_from(["example"]);
+    assert_eq!(config.command, None);
+}
+#[test]
+fn test_value_hints() {
+    #[config(path = "value_hints.toml")]
+    struct MyConfig;
+
+    let cmd = <MyConfig as clap::CommandFactory>::command();
+    let hint = |id: &str| {
+        cmd.get_arguments()
+            .find(|arg| arg.get_id() == id)
+            .map(|arg| arg.get_value_hint())
+    };
+    assert_eq!(hint("myconfig.log_file"), Some(clap::ValueHint::FilePath));
+    assert_eq!(hint("myconfig.data_dir"), Some(clap::ValueHint::DirPath));
+    assert_eq!(hint("myconfig.endpoint"), Some(clap::ValueHint::Url));
+    assert_eq!(hint("myconfig.host"), Some(clap::ValueHint::Hostname));
+    assert_eq!(hint("myconfig.includes"), Some(clap::ValueHint::DirPath));
+}
+#[test]
+fn test_command() {
+    #[config(path = "config_file.toml")]
+    struct MyConfig;
+
+    let cmd = MyConfig::command().about("Embedded settings");
+    assert!(
+        cmd.get_arguments()
+            .any(|arg| arg.get_long() == Some("config"))
+    );
+    let cli = clap::Command::new("tool").subcommand(cmd.name("serve"));
+    let matches = cli
+        .try_get_matches_from(["tool", "serve", "--myconfig.database.url", "db:5432"])
+        .unwrap();
+    assert_eq!(matches.subcommand_name(), Some("serve"));
+}
+
+#[test]
+fn test_markdown_help() {
+    #[config(path = "markdown_help.toml")]
+    struct MyConfig;
+
+    assert_eq!(
+        MyConfig::markdown_help(),
+        "\
+## Options
+
+| Flag | Env | Type | Default | Description |
+|---|---|---|---|---|
+| `--myconfig.level` |  | `Level`: `Debug`, `Info` | `Info` | Log level |
+| `--myconfig.port`, `-p` | `PORT` | `u16` | `8080` | Listen port |
+| `--myconfig.tags` |  | `Vec<String>` | `a,b` | Tags, e.g. `a\\|b` |
+| `--myconfig.token` | `TOKEN` | `String` |  | API token |
+
+## Database
+
+| Flag | Env | Type | Default | Description |
+|---|---|---|---|---|
+| `--db-url` |  | `String` | `localhost:5432` | Connection URL |
+"
+    );
+}
@@ -0,0 +1,480 @@
+//! Helpers the generated `parse` functions run around clap's own parsing.
+
+use clap::error::{ContextKind, ContextValue, ErrorKind};
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, Command, Parser};
+use std::ffi::OsString;
+
+use crate::provenance::REDACTED;
+
+/// Separator of `split_paths = true` lists, as in `PATH`.
+pub const PATH_LIST_SEPARATOR: char = if cfg!(windows) { ';' } else { ':' };
+
+/// Id of the hidden `--list-values <FIELD>` argument.
+pub const LIST_VALUES_ID: &str = "rclap.list_values";
+
+/// Adds the hidden `--list-values <FIELD>` argument to `cmd`.
+pub fn with_list_values(cmd: Command) -> Command {
+    cmd.arg(
+        Arg::new(LIST_VALUES_ID)
+            .long("list-values")
+            .value_name("FIELD")
+            .help("Print the possible values of a field, one per line")
+            .action(ArgAction::Set)
+            .exclusive(true)
+            .hide(true),
+    )
+}
+
+/// Id of the `--print-config-template` flag of `[app] config_template = true`.
+pub const CONFIG_TEMPLATE_ID: &str = "rclap.print_config_template";
+
+/// Adds the `--print-config-template` flag to `cmd`.
+pub fn with_config_template(cmd: Command) -> Command {
+    cmd.arg(
+        Arg::new(CONFIG_TEMPLATE_ID)
+            .long("print-config-template")
+            .help("Print a config file listing every field with its default, and exit")
+            .action(ArgAction::SetTrue)
+            .exclusive(true),
+    )
+}
+
+/// Id of the hidden `--dump-config[=FORMAT]` argument of `[app] dump_config = true`.
+pub const DUMP_CONFIG_ID: &str = "rclap.dump_config";
+
+/// Adds the hidden `--dump-config[=FORMAT]` argument to `cmd`, `FORMAT` being `toml` (the
+/// default) or `json`.
+pub fn with_dump_config(cmd: Command) -> Command {
+    cmd.arg(
+        Arg::new(DUMP_CONFIG_ID)
+            .long("dump-config")
+            .value_name("FORMAT")
+            .help("Print the resolved configuration, secrets redacted, and exit")
+            .value_parser(["toml", "json"])
+            .num_args(0..=1)
+            .require_equals(true)
+            .default_missing_value("toml")
+            .action(ArgAction::Set)
+            .hide(true),
+    )
+}
+
+/// Looks up an argument by its id (`myconfig.database.url`), its path without the struct
+/// prefix (`database.url`) or its long flag.
+pub fn find_arg<'a>(cmd: &'a Command, field: &str) -> Option<&'a Arg> {
+    let field = field.trim_start_matches("--");
+    cmd.get_arguments().find(|arg| {
+        let id = arg.get_id().as_str();
+        id == field
+            || id.split_once('.').is_some_and(|(_, path)| path == field)
+            || arg.get_long() == Some(field)
+    })
+}
+
+/// Handles `--list-values`: returns the possible values of the requested field, one per
+/// line, or an error when the field is unknown or has no fixed set of values.
+pub fn list_values(cmd: &mut Command, matches: &ArgMatches) -> Result<Option<String>, clap::Error> {
+    let Some(field) = matches.get_one::<String>(LIST_VALUES_ID) else {
+        return Ok(None);
+    };
+    let Some(arg) = find_arg(cmd, field) else {
+        return Err(cmd.error(ErrorKind::InvalidValue, format!("unknown field '{field}'")));
+    };
+    let values: Vec<String> = arg
+        .get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| format!("{}\n", value.get_name()))
+        .collect();
+    if values.is_empty() {
+        return Err(cmd.error(
+            ErrorKind::InvalidValue,
+            format!("field '{field}' does not have a fixed set of values"),
+        ));
+    }
+    Ok(Some(values.concat()))
+}
+
+/// An error of kind `DisplayHelp` rendering as `output`, verbatim. Like `--help`, `exit()`
+/// prints it on stdout and exits successfully, and `try_parse` hands it back uncaught.
+pub fn display(output: String) -> clap::Error {
+    Command::new("rclap")
+        .disable_version_flag(true)
+        .override_help(output)
+        .try_get_matches_from(["rclap", "--help"])
+        .expect_err("--help is an error")
+}
+
+/// Loads the variables of the `.env` file at `path` not already set in the environment. A
+/// missing file is skipped; a malformed one is an error. clap reads the environment when the
+/// command is built, so this runs first.
+#[cfg(feature = "dotenv")]
+pub fn load_dotenv(path: &str) -> Result<(), clap::Error> {
+    match dotenvy::from_path(path) {
+        Err(e) if !e.not_found() => Err(clap::Error::raw(
+            ErrorKind::Io,
+            format!("cannot load {path}: {e}\n"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Rejects `locked = true` fields given on the command line: they may only come from the
+/// environment or a config file.
+pub fn check_locked(
+    cmd: &mut Command,
+    matches: &ArgMatches,
+    ids: &[&str],
+) -> Result<(), clap::Error> {
+    let Some(id) = ids
+        .iter()
+        .find(|id| matches.value_source(id) == Some(ValueSource::CommandLine))
+    else {
+        return Ok(());
+    };
+    let arg = cmd.get_arguments().find(|arg| arg.get_id() == *id);
+    let flag = arg
+        .and_then(|arg| arg.get_long())
+        .map_or_else(|| id.to_string(), |long| format!("--{long}"));
+    let hint = match arg.and_then(|arg| arg.get_env()) {
+        Some(env) => format!("set {} instead", env.to_string_lossy()),
+        None => "set it in the config file instead".to_string(),
+    };
+    Err(cmd.error(
+        ErrorKind::ArgumentConflict,
+        format!(
+            "this setting is locked by policy: '{flag}' cannot be set on the command line; {hint}"
+        ),
+    ))
+}
+
+/// Prints a warning to stderr for each `deprecated` field, given as `(id, note)` pairs, set on
+/// the command line or in the environment.
+pub fn warn_deprecated(cmd: &Command, matches: &ArgMatches, deprecated: &[(&str, &str)]) {
+    for (id, note) in deprecated {
+        let Some(arg) = cmd.get_arguments().find(|arg| arg.get_id() == *id) else {
+            continue;
+        };
+        let setting = match matches.value_source(id) {
+            Some(ValueSource::CommandLine) => arg
+                .get_long()
+                .map_or_else(|| id.to_string(), |long| format!("--{long}")),
+            Some(ValueSource::EnvVariable) => arg
+                .get_env()
+                .map_or_else(|| id.to_string(), |env| env.to_string_lossy().into_owned()),
+            _ => continue,
+        };
+        eprintln!("warning: '{setting}' is deprecated: {note}");
+    }
+}
+
+/// Parses one element of a `[[name]]` array-of-tables field, given as `key=value` pairs
+/// separated by `,` (e.g. `host=0.0.0.0,port=8080`). `cmd` is the element struct's command;
+/// its env vars are ignored so one variable cannot leak into every element.
+pub fn parse_element<T: clap::CommandFactory + clap::FromArgMatches>(
+    prefix: &str,
+    value: &str,
+) -> Result<(T, ArgMatches), String> {
+    let mut cmd = T::command()
+        .no_binary_name(true)
+        .mut_args(|arg| arg.env(None));
+    let mut args = Vec::new();
+    for pair in value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+    {
+        let (key, val) = pair.split_once('=').unwrap_or((pair, "true"));
+        let id = format!("{prefix}.{}", key.trim());
+        let Some(arg) = cmd.get_arguments().find(|arg| arg.get_id() == id.as_str()) else {
+            return Err(format!("unknown key '{}' in '{value}'", key.trim()));
+        };
+        let long = arg.get_long().unwrap_or(id.as_str());
+        if arg.get_action().takes_values() {
+            args.push(format!("--{long}={val}"));
+        } else if val
+            .trim()
+            .parse::<bool>()
+            .map_err(|_| format!("'{val}' is not a bool"))?
+        {
+            args.push(format!("--{long}"));
+        }
+    }
+    let matches = cmd
+        .try_get_matches_from_mut(args)
+        .map_err(|e| first_line(&e))?;
+    let element = T::from_arg_matches(&matches).map_err(|e| first_line(&e.format(&mut cmd)))?;
+    Ok((element, matches))
+}
+
+/// Builds `T` from the spec defaults alone, ignoring the command line and the environment.
+/// Backs the `Default` impls generated by `#[config(..., default = true)]`.
+pub fn from_defaults<T: clap::Args + clap::FromArgMatches>() -> Result<(T, ArgMatches), clap::Error>
+{
+    let mut cmd = T::augment_args(Command::new("rclap"))
+        .no_binary_name(true)
+        .mut_args(|arg| arg.env(None));
+    let matches = cmd.try_get_matches_from_mut(Vec::<OsString>::new())?;
+    let value = T::from_arg_matches(&matches).map_err(|e| e.format(&mut cmd))?;
+    Ok((value, matches))
+}
+
+/// The spec defaults of the argument `id` of `cmd`, parsed alone, env var ignored.
+fn default_matches(cmd: &Command, id: &str) -> Result<ArgMatches, clap::Error> {
+    let arg = cmd
+        .get_arguments()
+        .find(|arg| arg.get_id() == id)
+        .cloned()
+        .ok_or_else(|| {
+            clap::Error::raw(
+                ErrorKind::UnknownArgument,
+                format!("unknown field '{id}'\n"),
+            )
+        })?;
+    Command::new("rclap")
+        .no_binary_name(true)
+        .arg(arg.env(None).required(false))
+        .try_get_matches_from(Vec::<OsString>::new())
+}
+
+/// The default of a single-valued field, `None` when it has none. Used by the builders.
+pub fn default_one<T: Clone + Send + Sync + 'static>(
+    cmd: &Command,
+    id: &str,
+) -> Result<Option<T>, clap::Error> {
+    Ok(default_matches(cmd, id)?.get_one::<T>(id).cloned())
+}
+
+/// The defaults of a multi-valued field, `None` when it has none.
+pub fn default_many<T: Clone + Send + Sync + 'static>(
+    cmd: &Command,
+    id: &str,
+) -> Result<Option<Vec<T>>, clap::Error> {
+    Ok(default_matches(cmd, id)?
+        .get_many::<T>(id)
+        .map(|values| values.cloned().collect()))
+}
+
+/// Like [`default_one`], failing for a field without a default.
+pub fn required_default<T: Clone + Send + Sync + 'static>(
+    cmd: &Command,
+    id: &str,
+) -> Result<T, clap::Error> {
+    default_one(cmd, id)?.ok_or_else(|| {
+        let field = id.split_once('.').map_or(id, |(_, path)| path);
+        clap::Error::raw(
+            ErrorKind::MissingRequiredArgument,
+            format!("field '{field}' has no default and was not set\n"),
+        )
+    })
+}
+
+/// Parses one entry of a `map_of` section, given as `name:key=value,...`.
+pub fn named_element<T: std::str::FromStr<Err = String>>(
+    value: &str,
+) -> Result<(String, T), String> {
+    let Some((name, element)) = value.split_once(':') else {
+        return Err(format!("'{value}' is not a NAME:KEY=VALUE,... entry"));
+    };
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(format!("'{value}' has an empty name"));
+    }
+    Ok((name.to_string(), element.parse()?))
+}
+
+fn first_line(err: &clap::Error) -> String {
+    let rendered = err.to_string();
+    rendered
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches("error: ")
+        .to_string()
+}
+
+/// The `--version` of `git_sha_env`: `version`, followed by the commit when the build set it.
+pub fn version_with_sha(version: &str, sha: Option<&str>) -> String {
+    match sha.filter(|sha| !sha.is_empty()) {
+        Some(sha) => format!("{version} ({sha})"),
+        None => version.to_string(),
+    }
+}
+
+/// Extracts the handful of flags declared by `E` (e.g. `--config`, `--profile`, `--verbose`)
+/// from the full command line, ignoring every argument `E` does not know about.
+///
+/// `--help` and `--version` are left to the full parse, and missing or malformed values are
+/// tolerated (`ignore_errors`), so this can run before the full configuration is known.
+pub fn try_parse_early_from<E, I, T>(itr: I) -> Result<E, clap::Error>
+where
+    E: Parser,
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let mut cmd = E::command()
+        .ignore_errors(true)
+        .disable_help_flag(true)
+        .disable_version_flag(true);
+    cmd.build();
+    let args = early_args(&cmd, itr.into_iter().map(Into::into));
+    let matches = cmd.try_get_matches_from_mut(args)?;
+    E::from_arg_matches(&matches).map_err(|e| e.format(&mut cmd))
+}
+
+/// Keeps the binary name and the options known to `cmd`, with their values.
+pub(crate) fn early_args(cmd: &Command, args: impl Iterator<Item = OsString>) -> Vec<OsString> {
+    let takes_value = |arg: &Arg| arg.get_action().takes_values();
+    let mut args = args.peekable();
+    let mut kept: Vec<OsString> = args.next().into_iter().collect();
+    while let Some(raw) = args.next() {
+        let text = raw.to_string_lossy();
+        if text == "--" {
+            break;
+        }
+        let known = if let Some(long) = text.strip_prefix("--") {
+            let (name, inline) = long
+                .split_once('=')
+                .map_or((long, false), |(n, _)| (n, true));
+            cmd.get_arguments()
+                .find(|arg| {
+                    arg.get_long_and_visible_aliases()
+                        .is_some_and(|longs| longs.contains(&name))
+                })
+                .map(|arg| takes_value(arg) && !inline)
+        } else if let Some(shorts) = text.strip_prefix('-').filter(|s| !s.is_empty()) {
+            let find = |c: char| {
+                cmd.get_arguments().find(|arg| {
+                    arg.get_short_and_visible_aliases()
+                        .is_some_and(|s| s.contains(&c))
+                })
+            };
+            let mut chars = shorts.chars();
+            match chars.next().and_then(find) {
+                Some(arg) if takes_value(arg) => Some(chars.next().is_none()),
+                Some(_) if chars.all(|c| find(c).is_some_and(|arg| !takes_value(arg))) => {
+                    Some(false)
+                }
+                _ => None,
+            }
+        } else {
+            None
+        };
+        let Some(needs_value) = known else {
+            continue;
+        };
+        kept.push(raw);
+        if needs_value
+            && let Some(value) = args.next_if(|next| !next.to_string_lossy().starts_with('-'))
+        {
+            kept.push(value);
+        }
+    }
+    kept
+}
+
+/// Replaces the offending value of a parse error on one of the `sensitive` args with
+/// [`REDACTED`], so the message does not echo a mistyped secret.
+pub fn redact_error(cmd: &Command, mut err: clap::Error, sensitive: &[&str]) -> clap::Error {
+    let on_sensitive = match err.get(ContextKind::InvalidArg) {
+        Some(ContextValue::String(rendered)) => cmd
+            .get_arguments()
+            .filter(|a| sensitive.contains(&a.get_id().as_str()))
+            .any(|a| a.to_string() == *rendered),
+        _ => false,
+    };
+    if on_sensitive && err.get(ContextKind::InvalidValue).is_some() {
+        err.insert(
+            ContextKind::InvalidValue,
+            ContextValue::String(REDACTED.to_string()),
+        );
+    }
+    err
+}
+
+/// Renders a parse error as JSON lines for `[app] error_json = true`: one object per
+/// offending argument, e.g. `{"error":"missing_required","field":"database.url","env":"DB_URL"}`.
+pub fn error_json(cmd: &mut Command, err: &clap::Error) -> String {
+    cmd.build();
+    let kind = match err.kind() {
+        ErrorKind::InvalidValue => "invalid_value",
+        ErrorKind::UnknownArgument => "unknown_argument",
+        ErrorKind::InvalidSubcommand => "invalid_subcommand",
+        ErrorKind::NoEquals => "no_equals",
+        ErrorKind::ValueValidation => "value_validation",
+        ErrorKind::TooManyValues => "too_many_values",
+        ErrorKind::TooFewValues => "too_few_values",
+        ErrorKind::WrongNumberOfValues => "wrong_number_of_values",
+        ErrorKind::ArgumentConflict => "argument_conflict",
+        ErrorKind::MissingRequiredArgument => "missing_required",
+        ErrorKind::MissingSubcommand => "missing_subcommand",
+        ErrorKind::InvalidUtf8 => "invalid_utf8",
+        ErrorKind::Io => "io",
+        ErrorKind::Format => "format",
+        _ => "other",
+    };
+    let rendered: Vec<&str> = match err.get(ContextKind::InvalidArg) {
+        Some(ContextValue::String(arg)) => vec![arg.as_str()],
+        Some(ContextValue::Strings(args)) => args.iter().map(String::as_str).collect(),
+        _ => Vec::new(),
+    };
+    let value = match err.get(ContextKind::InvalidValue) {
+        Some(ContextValue::String(value)) => Some(value.as_str()),
+        _ => None,
+    };
+    let message = err.to_string();
+    let message = message
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches("error: ");
+    let object = |arg: Option<&str>| {
+        let arg = arg.and_then(|rendered| cmd.get_arguments().find(|a| a.to_string() == rendered));
+        let field = arg.map(|a| {
+            let id = a.get_id().as_str();
+            id.split_once('.').map_or(id, |(_, path)| path).to_string()
+        });
+        let env = arg
+            .and_then(|a| a.get_env())
+            .map(|env| env.to_string_lossy().into_owned());
+        format!(
+            "{{\"error\":{},\"field\":{},\"env\":{},\"value\":{},\"message\":{}}}",
+            json_string(Some(kind)),
+            json_string(field.as_deref()),
+            json_string(env.as_deref()),
+            json_string(value),
+            json_string(Some(message)),
+        )
+    };
+    if rendered.is_empty() {
+        object(None)
+    } else {
+        rendered
+            .into_iter()
+            .map(|arg| object(Some(arg)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+pub(crate) fn json_string(value: Option<&str>) -> String {
+    let Some(value) = value else {
+        return "null".to_string();
+    };
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
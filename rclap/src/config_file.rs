@@ -0,0 +1,216 @@
+//! The runtime `--config <PATH>` layer enabled by `[app] config_file = true`. With
+//! `config_file = { name = "mytool.toml", lookup = "xdg" }` and the `xdg` feature, the file
+//! is looked up in the platform config directory when `--config` is not given.
+//!
+//! The file is a [`Layer`]: its values fill every field not given on the command line or,
+//! unless `precedence` ranks the file first, in the environment, ahead of the spec defaults.
+//! A `.yaml` or `.yml` file is read as YAML with the `yaml` feature, any other as TOML.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use clap::error::ErrorKind;
+use clap::{Arg, ArgAction, Command};
+
+use crate::cli::early_args;
+use crate::layer::Layer;
+use crate::provenance::Source;
+
+/// Id of the `--config <PATH>` argument.
+pub const CONFIG_FILE_ID: &str = "rclap.config";
+
+fn config_arg() -> Arg {
+    Arg::new(CONFIG_FILE_ID)
+        .long("config")
+        .value_name("PATH")
+        .help(if cfg!(feature = "yaml") {
+            "Read settings not given on the command line or in the environment from a TOML or YAML file"
+        } else {
+            "Read settings not given on the command line or in the environment from a TOML file"
+        })
+        .value_parser(clap::value_parser!(PathBuf))
+        .value_hint(clap::ValueHint::FilePath)
+        .action(ArgAction::Set)
+}
+
+/// Adds the `--config <PATH>` argument to `cmd`.
+pub fn with_config_file(cmd: Command) -> Command {
+    cmd.arg(config_arg())
+}
+
+/// A parsed config file: the raw values of each argument it sets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigFile {
+    pub path: PathBuf,
+    values: Vec<(String, Vec<String>)>,
+}
+
+impl ConfigFile {
+    /// Reads the file named by `--config` in `args`, if given.
+    pub fn from_args(cmd: &mut Command, args: &[OsString]) -> Result<Option<Self>, clap::Error> {
+        let mut early = Command::new(cmd.get_name().to_string())
+            .arg(config_arg())
+            .ignore_errors(true)
+            .disable_help_flag(true)
+            .disable_version_flag(true);
+        early.build();
+        let matches = early.try_get_matches_from_mut(early_args(&early, args.iter().cloned()))?;
+        match matches.get_one::<PathBuf>(CONFIG_FILE_ID) {
+            Some(path) => Self::read(cmd, path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads `name` from the config directory of the command, when it exists:
+    /// `$XDG_CONFIG_HOME/<command>/` (or `~/.config/<command>/`) on Linux,
+    /// `~/Library/Application Support/<command>/` on macOS and `%APPDATA%\<command>\` on
+    /// Windows.
+    #[cfg(feature = "xdg")]
+    pub fn from_config_dir(cmd: &mut Command, name: &str) -> Result<Option<Self>, clap::Error> {
+        let Some(dirs) = directories::BaseDirs::new() else {
+            return Ok(None);
+        };
+        let path = dirs.config_dir().join(cmd.get_name()).join(name);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Self::read(cmd, &path).map(Some)
+    }
+
+    /// Reads `path`, rejecting keys that match no field of `cmd`.
+    pub fn read(cmd: &mut Command, path: &Path) -> Result<Self, clap::Error> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            cmd.error(
+                ErrorKind::Io,
+                format!("cannot read config file '{}': {e}", path.display()),
+            )
+        })?;
+        let table = parse(path, &content).map_err(|e| {
+            cmd.error(
+                ErrorKind::InvalidValue,
+                format!("invalid config file '{}': {e}", path.display()),
+            )
+        })?;
+        let mut values = Vec::new();
+        collect(cmd, &table, "", &mut values).map_err(|field| {
+            cmd.error(
+                ErrorKind::UnknownArgument,
+                format!(
+                    "unknown field '{field}' in config file '{}'",
+                    path.display()
+                ),
+            )
+        })?;
+        Ok(ConfigFile {
+            path: path.to_path_buf(),
+            values,
+        })
+    }
+
+    /// Ids of the arguments the file sets.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.values.iter().map(|(id, _)| id.as_str())
+    }
+}
+
+impl Layer for ConfigFile {
+    fn source(&self) -> Source {
+        Source::File
+    }
+
+    fn location(&self) -> Option<String> {
+        Some(self.path.display().to_string())
+    }
+
+    fn values(&self, arg: &Arg) -> Option<Vec<String>> {
+        self.values
+            .iter()
+            .find(|(id, _)| arg.get_id() == id.as_str())
+            .map(|(_, values)| values.clone())
+    }
+}
+
+/// The file as a TOML table, read as YAML when its extension is `yaml` or `yml`.
+fn parse(path: &Path, content: &str) -> Result<toml::Table, String> {
+    let yaml = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"));
+    if !yaml {
+        return toml::from_str(content).map_err(|e| e.to_string());
+    }
+    #[cfg(feature = "yaml")]
+    return serde_yaml_ng::from_str(content).map_err(|e| e.to_string());
+    #[cfg(not(feature = "yaml"))]
+    Err("YAML config files need the `yaml` feature of rclap".to_string())
+}
+
+/// The argument of a field path (`database.url`), ignoring rclap's own arguments.
+fn field_arg<'a>(cmd: &'a Command, path: &str) -> Option<&'a Arg> {
+    cmd.get_arguments().find(|arg| {
+        let id = arg.get_id().as_str();
+        !id.starts_with("rclap.") && id.split_once('.').is_some_and(|(_, p)| p == path)
+    })
+}
+
+/// Walks the file tables down to the field keys; `Err` holds the path of an unknown key.
+fn collect(
+    cmd: &Command,
+    table: &toml::Table,
+    prefix: &str,
+    values: &mut Vec<(String, Vec<String>)>,
+) -> Result<(), String> {
+    for (key, value) in table {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        if let Some(arg) = field_arg(cmd, &path) {
+            values.push((arg.get_id().to_string(), raw_values(value)));
+            continue;
+        }
+        let section = format!("{path}.");
+        let is_section = cmd.get_arguments().any(|arg| {
+            let id = arg.get_id().as_str();
+            id.split_once('.')
+                .is_some_and(|(_, p)| p.starts_with(&section))
+        });
+        match value {
+            toml::Value::Table(table) if is_section => collect(cmd, table, &path, values)?,
+            _ => return Err(path),
+        }
+    }
+    Ok(())
+}
+
+/// The values of a field as they would be given on the command line: one per array item,
+/// `key=value` per map entry, `name:key=value,...` per `map_of` entry.
+fn raw_values(value: &toml::Value) -> Vec<String> {
+    match value {
+        toml::Value::Array(items) => items.iter().map(raw_value).collect(),
+        toml::Value::Table(entries) => entries
+            .iter()
+            .map(|(key, value)| match value {
+                toml::Value::Table(element) => format!("{key}:{}", raw_element(element)),
+                value => format!("{key}={}", raw_value(value)),
+            })
+            .collect(),
+        value => vec![raw_value(value)],
+    }
+}
+
+fn raw_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Table(element) => raw_element(element),
+        value => value.to_string(),
+    }
+}
+
+fn raw_element(element: &toml::Table) -> String {
+    element
+        .iter()
+        .map(|(key, value)| format!("{key}={}", raw_value(value)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
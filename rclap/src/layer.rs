@@ -0,0 +1,219 @@
+//! Value sources ranked in the order a field takes its value from them.
+//!
+//! clap reads the command line, the environment and the spec defaults itself. Every other
+//! [`Layer`] (the `--config` file, or a source of your own) supplies the raw values of the
+//! fields it knows, which become the defaults of their arguments before the command line is
+//! parsed. A layer ranked above [`EnvLayer`] also hides the env var of the fields it sets.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+
+use clap::{Arg, Command};
+
+use crate::provenance::Source;
+
+/// A source of raw field values.
+pub trait Layer {
+    /// How provenance reports the values of this layer.
+    fn source(&self) -> Source;
+
+    /// Where the values come from (a file path, a service URL...), shown by provenance.
+    fn location(&self) -> Option<String> {
+        None
+    }
+
+    /// The values of `arg`, written as on the command line, or `None` when this layer does
+    /// not set it.
+    fn values(&self, arg: &Arg) -> Option<Vec<String>>;
+}
+
+/// The command line, parsed by clap. It always wins, wherever it is ranked.
+pub struct CliLayer;
+
+impl Layer for CliLayer {
+    fn source(&self) -> Source {
+        Source::CommandLine
+    }
+
+    fn values(&self, _arg: &Arg) -> Option<Vec<String>> {
+        None
+    }
+}
+
+/// The `env` vars of the spec, read by clap.
+pub struct EnvLayer;
+
+impl Layer for EnvLayer {
+    fn source(&self) -> Source {
+        Source::Env
+    }
+
+    fn values(&self, arg: &Arg) -> Option<Vec<String>> {
+        let value = std::env::var_os(arg.get_env()?)?;
+        Some(vec![value.to_string_lossy().into_owned()])
+    }
+}
+
+/// Env vars given as a map, e.g. by a test, in place of [`EnvLayer`]. The parse runs
+/// [`resolve_with_env_map`], which stops clap from reading the process environment.
+pub struct EnvMap<'a>(pub &'a HashMap<String, String>);
+
+impl Layer for EnvMap<'_> {
+    fn source(&self) -> Source {
+        Source::Env
+    }
+
+    fn values(&self, arg: &Arg) -> Option<Vec<String>> {
+        let value = self.0.get(arg.get_env()?.to_str()?)?;
+        Some(vec![value.clone()])
+    }
+}
+
+/// The files named by the `env_file` vars of the spec, e.g. `DB_PASSWORD_FILE` pointing at a
+/// mounted secret. Ranked right below [`EnvLayer`]: a field's own env var wins over its file.
+pub struct EnvFileLayer {
+    /// `(arg id, trimmed file content)`.
+    values: Vec<(String, String)>,
+}
+
+impl EnvFileLayer {
+    /// Reads the file of every `(arg id, env var)` whose var is set. A file that cannot be
+    /// read is an error rather than a silent fallback to the defaults.
+    pub fn read(files: &[(&str, &str)]) -> Result<Self, clap::Error> {
+        Self::read_vars(files, |var| std::env::var_os(var))
+    }
+
+    /// [`read`](Self::read) with the env vars of `vars` instead of the process environment.
+    pub fn read_from(
+        files: &[(&str, &str)],
+        vars: &HashMap<String, String>,
+    ) -> Result<Self, clap::Error> {
+        Self::read_vars(files, |var| vars.get(var).map(OsString::from))
+    }
+
+    fn read_vars(
+        files: &[(&str, &str)],
+        var_os: impl Fn(&str) -> Option<OsString>,
+    ) -> Result<Self, clap::Error> {
+        let mut values = Vec::new();
+        for (id, var) in files {
+            let Some(path) = var_os(var) else {
+                continue;
+            };
+            let content = std::fs::read_to_string(&path).map_err(|e| {
+                clap::Error::raw(
+                    clap::error::ErrorKind::Io,
+                    format!("cannot read {var} ({}): {e}\n", path.to_string_lossy()),
+                )
+            })?;
+            values.push((id.to_string(), content.trim().to_string()));
+        }
+        Ok(Self { values })
+    }
+}
+
+impl Layer for EnvFileLayer {
+    fn source(&self) -> Source {
+        Source::Env
+    }
+
+    fn values(&self, arg: &Arg) -> Option<Vec<String>> {
+        self.values
+            .iter()
+            .find(|(id, _)| arg.get_id() == id.as_str())
+            .map(|(_, value)| vec![value.clone()])
+    }
+}
+
+/// The spec defaults, applied by clap. Layers ranked below it are never used.
+pub struct DefaultsLayer;
+
+impl Layer for DefaultsLayer {
+    fn source(&self) -> Source {
+        Source::Default
+    }
+
+    fn values(&self, arg: &Arg) -> Option<Vec<String>> {
+        let defaults = arg.get_default_values();
+        (!defaults.is_empty()).then(|| {
+            defaults
+                .iter()
+                .map(|v| v.to_string_lossy().into_owned())
+                .collect()
+        })
+    }
+}
+
+#[cfg(feature = "config_file")]
+pub use crate::config_file::ConfigFile as FileLayer;
+
+/// A field whose value was supplied by a layer other than clap's own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolved {
+    pub id: String,
+    pub source: Source,
+    pub location: Option<String>,
+}
+
+/// Applies `layers`, highest first, to the arguments of `cmd`.
+pub fn resolve(cmd: Command, layers: &[&dyn Layer]) -> (Command, Vec<Resolved>) {
+    resolve_in(cmd, layers, true)
+}
+
+/// [`resolve`] for `layers` reading the env vars from an [`EnvMap`]: the `env` of every
+/// argument is dropped, so the process environment is never read.
+pub fn resolve_with_env_map(cmd: Command, layers: &[&dyn Layer]) -> (Command, Vec<Resolved>) {
+    resolve_in(cmd, layers, false)
+}
+
+fn resolve_in(cmd: Command, layers: &[&dyn Layer], process_env: bool) -> (Command, Vec<Resolved>) {
+    let mut resolved = Vec::new();
+    let mut picks = Vec::new();
+    for arg in cmd.get_arguments() {
+        if arg.get_id().as_str().starts_with("rclap.") {
+            continue;
+        }
+        for layer in layers {
+            let source = layer.source();
+            match source {
+                Source::CommandLine => continue,
+                Source::Default => break,
+                _ => {}
+            }
+            let Some(values) = layer.values(arg) else {
+                continue;
+            };
+            let env_set = process_env && EnvLayer.values(arg).is_some();
+            // clap reads the env vars itself.
+            if source == Source::Env && env_set {
+                break;
+            }
+            let id = arg.get_id().to_string();
+            picks.push((id.clone(), values, env_set));
+            resolved.push(Resolved {
+                id,
+                source,
+                location: layer.location(),
+            });
+            break;
+        }
+    }
+    let cmd = picks.into_iter().fold(cmd, |cmd, (id, values, env_set)| {
+        cmd.mut_arg(id, |arg| {
+            let arg = arg.default_values(values).required(false);
+            if env_set { arg.env(None) } else { arg }
+        })
+    });
+    if process_env {
+        return (cmd, resolved);
+    }
+    let with_env: Vec<String> = cmd
+        .get_arguments()
+        .filter(|arg| arg.get_env().is_some())
+        .map(|arg| arg.get_id().to_string())
+        .collect();
+    let cmd = with_env
+        .into_iter()
+        .fold(cmd, |cmd, id| cmd.mut_arg(id, |arg| arg.env(None)));
+    (cmd, resolved)
+}
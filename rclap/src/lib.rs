@@ -145,8 +145,85 @@
 //! # Feature Flags
 //!
 //! Enable the `secrecy` feature to access secure wrapper types for passwords, tokens, and API keys.
+//! It also enables `type = "secret_string"`, parsed into `secrecy::SecretString`.
+//!
+//! Enable the `timezone` feature to use `type = "timezone"`, parsed into `chrono_tz::Tz`.
+//!
+//! Enable the `lang` feature to use `type = "lang"`, a BCP-47 language tag parsed into
+//! `unic_langid::LanguageIdentifier`.
+//!
+//! Enable the `regex` feature to validate string fields with `pattern = "..."`.
+//!
+//! Enable the `cidr` feature to use `type = "cidr"` (and `[cidr]`), parsed into `ipnet::IpNet`.
+//!
+//! Enable the `url` feature to use `type = "url"`, parsed into `url::Url`. Defaults are
+//! validated when the macro expands.
+//!
+//! Enable the `uuid` feature to use `type = "uuid"`, parsed into `uuid::Uuid`. Defaults are
+//! validated when the macro expands.
+//!
+//! Enable the `datetime` feature to use `type = "datetime"`, parsed into
+//! `toml_datetime::Datetime` (RFC 3339 dates, times and date-times). Defaults can be written
+//! as native TOML datetime literals.
+//!
+//! Enable the `serde` feature to use `#[config(..., serde = true)]`: the generated structs
+//! derive `serde::Serialize` and get `to_toml_string()` / `to_json_string()`.
+//! `deserialize = true` derives `serde::Deserialize` and adds `from_toml_str()`.
+//!
+//! Enable the `schema` feature to use `#[config(..., schema = true)]`: the generated structs
+//! derive `schemars::JsonSchema`, describing the fields parsed from strings (timezones, urls,
+//! ...) as strings and secrets as write-only strings.
+//!
+//! Enable the `dotenv` feature to use `[app] dotenv = true`: the generated parse loads a
+//! `.env` file, when present, before reading the environment.
+//!
+//! Enable the `config_file` feature to use `[app] config_file = true`, a generated
+//! `--config <PATH>` flag whose TOML file fills the fields not given on the command line or
+//! in the environment.
+//!
+//! Enable the `xdg` feature to use `[app] config_file = { name = "...", lookup = "xdg" }`,
+//! reading the file from the platform config directory when `--config` is not given.
+//!
+//! Enable the `watch` feature to use `[app] watch = true`: the generated `watch(callback)`
+//! parses again each time the config file changes and passes the new config to `callback`.
 
-pub use rclap_derive::config;
+pub use rclap_derive::{Config, config, config_section, config_str};
+/// Paths used by the generated code, so a crate needs no clap dependency of its own.
+#[doc(hidden)]
+pub mod __private {
+    pub use clap;
+}
+pub mod cli;
+pub mod color;
+#[cfg(feature = "config_file")]
+pub mod config_file;
+pub mod layer;
+pub use color::Color;
+pub mod parsers;
+pub mod provenance;
+pub mod section;
+#[cfg(feature = "serde")]
+pub mod serialize;
+#[cfg(feature = "watch")]
+pub mod watch;
+#[cfg(feature = "timezone")]
+pub use chrono_tz;
+#[cfg(feature = "cidr")]
+pub use ipnet;
+#[cfg(feature = "regex")]
+pub use regex;
+#[cfg(feature = "schema")]
+pub use schemars;
+#[cfg(feature = "serde")]
+pub use serde;
+#[cfg(feature = "datetime")]
+pub use toml_datetime;
+#[cfg(feature = "lang")]
+pub use unic_langid;
+#[cfg(feature = "url")]
+pub use url;
+#[cfg(feature = "uuid")]
+pub use uuid;
 #[cfg(feature = "secrecy")]
 pub mod secrecy;
 #[cfg(feature = "secrecy")]
@@ -0,0 +1,13 @@
+//! `key=value` pairs for `type = "map"` fields.
+
+/// Splits `value` at its first `=` into a non-empty key and a (possibly empty) value.
+pub fn key_value(value: &str) -> Result<(String, String), String> {
+    let Some((key, val)) = value.split_once('=') else {
+        return Err(format!("'{value}' is not a KEY=VALUE pair"));
+    };
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(format!("'{value}' has an empty key"));
+    }
+    Ok((key.to_string(), val.to_string()))
+}
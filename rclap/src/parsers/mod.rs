@@ -0,0 +1,28 @@
+//! Value parsers used by the generated clap structures.
+//!
+//! The `#[config]` macro references these parsers through `value_parser = ...` for the
+//! spec types that need more than `FromStr` (validation, completion hints, unit parsing).
+
+pub use rclap_core::parsers::{address, byte_size, email, hostname};
+pub mod key_value;
+pub use key_value::key_value;
+pub mod port;
+pub use port::PortValueParser;
+
+#[cfg(feature = "timezone")]
+pub mod timezone;
+#[cfg(feature = "timezone")]
+pub use timezone::TimezoneValueParser;
+
+#[cfg(feature = "regex")]
+pub mod pattern;
+#[cfg(feature = "regex")]
+pub use pattern::matches_pattern;
+
+#[cfg(feature = "cidr")]
+pub use rclap_core::parsers::cidr;
+
+#[cfg(feature = "secrecy")]
+pub mod secret_string;
+#[cfg(feature = "secrecy")]
+pub use secret_string::secret_string;
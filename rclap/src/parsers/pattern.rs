@@ -0,0 +1,11 @@
+use regex::Regex;
+
+/// Checks `value` against the `pattern` of the field `id`. The generated code compiles the
+/// regex once and calls this for every occurrence of the argument.
+pub fn matches_pattern(re: &Regex, id: &str, value: &str) -> Result<String, String> {
+    if re.is_match(value) {
+        Ok(value.to_string())
+    } else {
+        Err(format!("{id} must match the pattern {}", re.as_str()))
+    }
+}
@@ -0,0 +1,109 @@
+use std::ffi::OsStr;
+use std::ops::RangeBounds;
+use std::sync::Mutex;
+
+use clap::builder::{RangedI64ValueParser, TypedValueParser};
+
+/// Ports below this number need elevated privileges to bind on most unix systems.
+const PRIVILEGED_PORT_END: u16 = 1024;
+
+/// The ports warned about, so a reload or a default parsed again warns only once.
+static WARNED: Mutex<Vec<u16>> = Mutex::new(Vec::new());
+
+/// Parses a TCP/UDP port number, optionally restricted to a range.
+///
+/// When the port is privileged and the process is known not to be allowed to bind it, a
+/// warning is printed on stderr once per port, or the value is rejected in strict mode. The
+/// check is best-effort: on Linux a port below `ip_unprivileged_port_start` needs
+/// `CAP_NET_BIND_SERVICE` in the effective capabilities of the process; elsewhere, or when
+/// `/proc` cannot be read, every port is accepted. Defaults go through the same check.
+#[derive(Clone, Debug)]
+pub struct PortValueParser {
+    inner: RangedI64ValueParser<u16>,
+    strict: bool,
+    can_bind: fn(u16) -> Option<bool>,
+}
+
+impl PortValueParser {
+    pub fn new() -> Self {
+        Self {
+            inner: RangedI64ValueParser::new(),
+            strict: false,
+            can_bind,
+        }
+    }
+
+    pub fn range<B: RangeBounds<i64>>(mut self, range: B) -> Self {
+        self.inner = self.inner.range(range);
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Replaces the check telling whether the process may bind a port, `None` when it cannot
+    /// tell, e.g. to test strict mode whatever the privileges of the test run.
+    pub fn can_bind(mut self, can_bind: fn(u16) -> Option<bool>) -> Self {
+        self.can_bind = can_bind;
+        self
+    }
+}
+
+impl Default for PortValueParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypedValueParser for PortValueParser {
+    type Value = u16;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &OsStr,
+    ) -> Result<Self::Value, clap::Error> {
+        let port = self.inner.parse_ref(cmd, arg, value)?;
+        if port == 0 || (self.can_bind)(port) != Some(false) {
+            return Ok(port);
+        }
+        let message = format!("port {port} is privileged and this process may not bind it");
+        if self.strict {
+            let reject = move |_: &str| Err::<u16, String>(message.clone());
+            return reject.parse_ref(cmd, arg, value);
+        }
+        let mut warned = WARNED.lock().unwrap_or_else(|e| e.into_inner());
+        if !warned.contains(&port) {
+            warned.push(port);
+            let arg_name = arg.map(|a| a.to_string()).unwrap_or_default();
+            eprintln!("warning: {arg_name}: {message}");
+        }
+        Ok(port)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn can_bind(port: u16) -> Option<bool> {
+    let start = std::fs::read_to_string("/proc/sys/net/ipv4/ip_unprivileged_port_start")
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(PRIVILEGED_PORT_END);
+    if port >= start {
+        return Some(true);
+    }
+    // Bit 10 of the effective capability set is CAP_NET_BIND_SERVICE, which root holds too.
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let effective = status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:"))?;
+    let effective = u64::from_str_radix(effective.trim(), 16).ok()?;
+    Some(effective & (1 << 10) != 0)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn can_bind(port: u16) -> Option<bool> {
+    (port >= PRIVILEGED_PORT_END).then_some(true)
+}
@@ -0,0 +1,6 @@
+use secrecy::SecretString;
+
+/// Wraps the value in a `secrecy::SecretString`, zeroized on drop and never printed by `Debug`.
+pub fn secret_string(s: &str) -> Result<SecretString, String> {
+    Ok(SecretString::from(s))
+}
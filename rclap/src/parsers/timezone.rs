@@ -0,0 +1,64 @@
+use std::ffi::OsStr;
+
+use chrono_tz::Tz;
+use clap::builder::{PossibleValue, TypedValueParser};
+
+/// Zone names offered to shell completion. Any IANA name is accepted when parsing.
+pub const COMMON_TIMEZONES: [&str; 24] = [
+    "UTC",
+    "Europe/London",
+    "Europe/Paris",
+    "Europe/Berlin",
+    "Europe/Madrid",
+    "Europe/Rome",
+    "Europe/Amsterdam",
+    "Europe/Moscow",
+    "Africa/Cairo",
+    "Africa/Johannesburg",
+    "Africa/Tunis",
+    "America/New_York",
+    "America/Chicago",
+    "America/Denver",
+    "America/Los_Angeles",
+    "America/Toronto",
+    "America/Sao_Paulo",
+    "Asia/Dubai",
+    "Asia/Kolkata",
+    "Asia/Singapore",
+    "Asia/Shanghai",
+    "Asia/Tokyo",
+    "Australia/Sydney",
+    "Pacific/Auckland",
+];
+
+/// Parses IANA time-zone names (`Europe/Paris`, `UTC`, ...) into [`chrono_tz::Tz`].
+#[derive(Clone, Debug, Default)]
+pub struct TimezoneValueParser;
+
+impl TimezoneValueParser {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+pub fn parse_timezone(s: &str) -> Result<Tz, String> {
+    s.parse::<Tz>()
+        .map_err(|_| format!("'{s}' is not a known IANA time zone"))
+}
+
+impl TypedValueParser for TimezoneValueParser {
+    type Value = Tz;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &OsStr,
+    ) -> Result<Self::Value, clap::Error> {
+        parse_timezone.parse_ref(cmd, arg, value)
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        Some(Box::new(COMMON_TIMEZONES.iter().map(PossibleValue::new)))
+    }
+}
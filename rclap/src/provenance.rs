@@ -0,0 +1,173 @@
+//! Where each configuration value came from, for audit logs written at startup.
+
+use std::fmt;
+
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Command};
+
+use crate::cli::json_string;
+use crate::layer::Resolved;
+
+/// Placeholder shown instead of the raw value of `secret = true` fields.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// The layer a value was taken from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    CommandLine,
+    Env,
+    /// The `--config` file.
+    File,
+    /// A custom [`Layer`](crate::layer::Layer), by name.
+    Custom(&'static str),
+    Default,
+    /// Neither given nor defaulted (an `optional` field left empty).
+    Unset,
+}
+
+impl Source {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Source::CommandLine => "cli",
+            Source::Env => "env",
+            Source::File => "file",
+            Source::Custom(name) => name,
+            Source::Default => "default",
+            Source::Unset => "unset",
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The provenance of a single field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Field path without the struct prefix, e.g. `database.url`.
+    pub field: String,
+    pub source: Source,
+    /// The flag, env var or spec file the value came from.
+    pub location: Option<String>,
+    /// The value as given, before clap parsed it; [`REDACTED`] for secrets.
+    pub raw: Option<String>,
+    pub redacted: bool,
+}
+
+/// Field → source report of a parse, as returned by the generated `try_parse_with_provenance()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Provenance {
+    pub entries: Vec<Entry>,
+}
+
+impl Provenance {
+    /// Builds the report for `fields`, given as `(id, secret)` pairs, from the matches of a
+    /// parse. `spec` is the spec file holding the defaults.
+    pub fn from_matches(
+        cmd: &Command,
+        matches: &ArgMatches,
+        fields: &[(&str, bool)],
+        spec: &str,
+    ) -> Self {
+        let entries = fields
+            .iter()
+            .map(|&(id, secret)| {
+                let arg = cmd.get_arguments().find(|arg| arg.get_id() == id);
+                let source = match matches.value_source(id) {
+                    Some(ValueSource::CommandLine) => Source::CommandLine,
+                    Some(ValueSource::EnvVariable) => Source::Env,
+                    Some(ValueSource::DefaultValue) => Source::Default,
+                    _ => Source::Unset,
+                };
+                let location = match source {
+                    Source::CommandLine => arg.and_then(|a| a.get_long()).map(|l| format!("--{l}")),
+                    Source::Env => arg
+                        .and_then(|a| a.get_env())
+                        .map(|env| env.to_string_lossy().into_owned()),
+                    Source::Default => Some(spec.to_string()),
+                    Source::File | Source::Custom(_) => None,
+                    Source::Unset => None,
+                };
+                let raw = matches.get_raw(id).map(|values| {
+                    values
+                        .map(|v| v.to_string_lossy().into_owned())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                });
+                let redacted = secret && raw.is_some();
+                Entry {
+                    field: id.split_once('.').map_or(id, |(_, path)| path).to_string(),
+                    source,
+                    location,
+                    raw: if redacted {
+                        Some(REDACTED.to_string())
+                    } else {
+                        raw
+                    },
+                    redacted,
+                }
+            })
+            .collect();
+        Provenance { entries }
+    }
+
+    /// Attributes the defaulted fields supplied by a layer to that layer.
+    pub fn with_resolved(mut self, resolved: &[Resolved]) -> Self {
+        for entry in &mut self.entries {
+            let layer = resolved.iter().find(|r| {
+                r.id.split_once('.')
+                    .is_some_and(|(_, path)| path == entry.field)
+            });
+            if let Some(layer) = layer
+                && entry.source == Source::Default
+            {
+                entry.source = layer.source;
+                entry.location = layer.location.clone();
+            }
+        }
+        self
+    }
+
+    pub fn get(&self, field: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.field == field)
+    }
+
+    /// One JSON object per field, one per line.
+    pub fn to_json_lines(&self) -> String {
+        self.entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{{\"field\":{},\"source\":{},\"location\":{},\"raw\":{},\"redacted\":{}}}",
+                    json_string(Some(&entry.field)),
+                    json_string(Some(entry.source.as_str())),
+                    json_string(entry.location.as_deref()),
+                    json_string(entry.raw.as_deref()),
+                    entry.redacted,
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            write!(f, "{} = ", entry.field)?;
+            match &entry.raw {
+                Some(raw) => write!(f, "{raw:?}")?,
+                None => write!(f, "<unset>")?,
+            }
+            write!(f, " ({}", entry.source)?;
+            if let Some(location) = &entry.location {
+                write!(f, ": {location}")?;
+            }
+            writeln!(f, ")")?;
+        }
+        Ok(())
+    }
+}
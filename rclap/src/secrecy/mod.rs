@@ -18,6 +18,11 @@
 //! - Similar behavior to `Secret<String>`
 //! - Useful for passwords, tokens, or any string-based secrets
 //!
+//! ## `SecretString`
+//!
+//! The `secrecy` crate's own string secret, used by `type = "secret_string"` fields. Read it
+//! with [`ExposeSecret::expose_secret`].
+//!
 //!
 //! # Feature Flag
 //!
@@ -28,3 +33,5 @@ pub mod secret;
 pub mod string_secret;
 pub use secret::Secret;
 pub use string_secret::StringSecret;
+
+pub use ::secrecy::{ExposeSecret, SecretString};
@@ -68,3 +68,18 @@ impl<'de, S: CloneableSecret + serde::Deserialize<'de>> serde::Deserialize<'de>
         Ok(Secret::new(s))
     }
 }
+
+#[cfg(feature = "schema")]
+impl<C: CloneableSecret> schemars::JsonSchema for Secret<C> {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Secret".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({ "type": "string", "writeOnly": true })
+    }
+
+    fn inline_schema() -> bool {
+        true
+    }
+}
@@ -35,7 +35,7 @@ impl std::str::FromStr for StringSecret {
 }
 impl From<&str> for StringSecret {
     fn from(s: &str) -> Self {
-    Self::new(s)
+        Self::new(s)
     }
 }
 
@@ -65,3 +65,18 @@ impl<'de> serde::Deserialize<'de> for StringSecret {
         Ok(StringSecret::new(&s))
     }
 }
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for StringSecret {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Secret".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({ "type": "string", "writeOnly": true })
+    }
+
+    fn inline_schema() -> bool {
+        true
+    }
+}
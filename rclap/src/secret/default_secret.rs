@@ -71,3 +71,18 @@ impl<'de, S: serde::Deserialize<'de>> serde::Deserialize<'de> for Secret<S> {
         Ok(Secret::new(s))
     }
 }
+
+#[cfg(feature = "schema")]
+impl<C> schemars::JsonSchema for Secret<C> {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Secret".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({ "type": "string", "writeOnly": true })
+    }
+
+    fn inline_schema() -> bool {
+        true
+    }
+}
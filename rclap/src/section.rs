@@ -0,0 +1,199 @@
+//! Spec-backed sections published by library crates with `#[config_section("redis.toml")]`
+//! and embedded in an application spec with `external = "redis_lib::RedisConfig"`.
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use clap::{ArgMatches, Args, Command, FromArgMatches};
+
+/// Implemented by every `#[config_section]` struct. The application's `#[config]` checks at
+/// compile time that an `external` type implements it and provides the fields listed in
+/// `requires`, with the declared types when `requires` is a table.
+pub trait ConfigSection: clap::Args + clap::FromArgMatches {
+    /// The spec file the section was generated from.
+    const SPEC: &'static str;
+    /// Field paths of the section, nested sections flattened (`pool.size`), with the Rust type
+    /// of each field written without spaces (`Option<u16>`).
+    const FIELDS: &'static [(&'static str, &'static str)];
+
+    #[doc(hidden)]
+    fn rclap_fill_maps(&mut self, _matches: &ArgMatches) {}
+}
+
+/// A section declaring the type `T` of an earlier section of the spec. It is parsed with the
+/// flags of its own args struct `A`, then converted into `T`, which it derefs to.
+pub struct Reused<T, A> {
+    value: T,
+    args: PhantomData<fn() -> A>,
+}
+
+impl<T, A> Reused<T, A> {
+    pub fn new(value: T) -> Self {
+        Reused {
+            value,
+            args: PhantomData,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T, A> From<T> for Reused<T, A> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T, A> Deref for Reused<T, A> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T, A> DerefMut for Reused<T, A> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T, A: Args + Into<T>> Args for Reused<T, A> {
+    fn group_id() -> Option<clap::Id> {
+        A::group_id()
+    }
+
+    fn augment_args(cmd: Command) -> Command {
+        A::augment_args(cmd)
+    }
+
+    fn augment_args_for_update(cmd: Command) -> Command {
+        A::augment_args_for_update(cmd)
+    }
+}
+
+impl<T, A: FromArgMatches + Into<T>> FromArgMatches for Reused<T, A> {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        A::from_arg_matches(matches).map(|args| Self::new(args.into()))
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+impl<T: fmt::Debug, A> fmt::Debug for Reused<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl<T: Clone, A> Clone for Reused<T, A> {
+    fn clone(&self) -> Self {
+        Self::new(self.value.clone())
+    }
+}
+
+impl<T: PartialEq, A> PartialEq for Reused<T, A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Eq, A> Eq for Reused<T, A> {}
+
+impl<T: PartialOrd, A> PartialOrd for Reused<T, A> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.value.partial_cmp(&other.value)
+    }
+}
+
+impl<T: Ord, A> Ord for Reused<T, A> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.value.cmp(&other.value)
+    }
+}
+
+impl<T: Hash, A> Hash for Reused<T, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize, A> serde::Serialize for Reused<T, A> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>, A> serde::Deserialize<'de> for Reused<T, A> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Self::new)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl<T: schemars::JsonSchema, A> schemars::JsonSchema for Reused<T, A> {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        T::schema_name()
+    }
+
+    fn schema_id() -> std::borrow::Cow<'static, str> {
+        T::schema_id()
+    }
+
+    fn json_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        T::json_schema(generator)
+    }
+
+    fn inline_schema() -> bool {
+        T::inline_schema()
+    }
+}
+
+/// Whether `fields` contains `field`; usable in const assertions.
+pub const fn has_field(fields: &[(&str, &str)], field: &str) -> bool {
+    field_type(fields, field).is_some()
+}
+
+/// The type of `field` in `fields`; usable in const assertions.
+pub const fn field_type<'a>(fields: &[(&str, &'a str)], field: &str) -> Option<&'a str> {
+    let mut i = 0;
+    while i < fields.len() {
+        if str_eq(fields[i].0, field) {
+            return Some(fields[i].1);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Whether `fields` contains `field` with the type `ty`; usable in const assertions.
+pub const fn has_field_of_type(fields: &[(&str, &str)], field: &str, ty: &str) -> bool {
+    match field_type(fields, field) {
+        Some(found) => str_eq(found, ty),
+        None => false,
+    }
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
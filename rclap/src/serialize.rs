@@ -0,0 +1,99 @@
+//! Dumps of a resolved configuration, generated as `to_toml_string()` / `to_json_string()` by
+//! `#[config(..., serde = true)]`. Secrets are written masked.
+//!
+//! `#[config(..., deserialize = true)]` generates `from_toml_str()` reading such a dump back,
+//! and `[app] dump_config = true` prints one for `--dump-config`.
+
+use clap::error::ErrorKind;
+use clap::{ArgMatches, Command};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::cli::DUMP_CONFIG_ID;
+use crate::provenance::REDACTED;
+
+pub type Error = toml::ser::Error;
+pub type JsonError = serde_json::Error;
+pub type DeError = toml::de::Error;
+
+/// The single field of the object a TOML datetime serializes as outside of TOML.
+const DATETIME_FIELD: &str = "$__toml_private_datetime";
+
+/// The configuration as a TOML document, sections as tables.
+pub fn to_toml_string<T: Serialize>(value: &T) -> Result<String, Error> {
+    toml::to_string(value)
+}
+
+/// The configuration as a single-line JSON object. Unset optional fields are left out.
+pub fn to_json_string<T: Serialize>(value: &T) -> Result<String, JsonError> {
+    Ok(plain(serde_json::to_value(value)?).to_string())
+}
+
+/// A configuration saved as TOML. Every field without `optional = true` must be present.
+pub fn from_toml_str<T: DeserializeOwned>(s: &str) -> Result<T, DeError> {
+    toml::from_str(s)
+}
+
+/// Handles `--dump-config`: returns `config` in the requested format, secrets masked and the
+/// `sensitive` fields, given by id, replaced by [`REDACTED`].
+pub fn dump_config<T: Serialize>(
+    cmd: &mut Command,
+    matches: &ArgMatches,
+    config: &T,
+    sensitive: &[&str],
+) -> Result<Option<String>, clap::Error> {
+    let Some(format) = matches.get_one::<String>(DUMP_CONFIG_ID) else {
+        return Ok(None);
+    };
+    let mut value =
+        toml::Value::try_from(config).map_err(|e| cmd.error(ErrorKind::Io, e.to_string()))?;
+    for id in sensitive {
+        let path = id.split_once('.').map_or(*id, |(_, path)| path);
+        redact(&mut value, path);
+    }
+    match format.as_str() {
+        "json" => serde_json::to_value(&value)
+            .map(|value| Some(format!("{}\n", plain(value))))
+            .map_err(|e| cmd.error(ErrorKind::Io, e.to_string())),
+        _ => toml::to_string(&value)
+            .map(Some)
+            .map_err(|e| cmd.error(ErrorKind::Io, e.to_string())),
+    }
+}
+
+/// Replaces the value at `path` (`database.password`) of `value`, when set.
+fn redact(value: &mut toml::Value, path: &str) {
+    let (name, rest) = match path.split_once('.') {
+        Some((name, rest)) => (name, Some(rest)),
+        None => (path, None),
+    };
+    let Some(field) = value.as_table_mut().and_then(|table| table.get_mut(name)) else {
+        return;
+    };
+    match rest {
+        Some(rest) => redact(field, rest),
+        None => *field = toml::Value::String(REDACTED.to_string()),
+    }
+}
+
+/// Leaves out the unset optional fields, as TOML does, and writes datetimes, which serialize as
+/// a marker object outside of TOML, as strings.
+fn plain(value: serde_json::Value) -> serde_json::Value {
+    use serde_json::Value;
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::String(datetime)) = object.get(DATETIME_FIELD)
+                && object.len() == 1
+            {
+                return Value::String(datetime.clone());
+            }
+            object
+                .into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key, plain(value)))
+                .collect()
+        }
+        Value::Array(items) => items.into_iter().map(plain).collect(),
+        value => value,
+    }
+}
@@ -0,0 +1,52 @@
+//! Hot reload of the `--config` file, generated as `watch()` by `[app] watch = true`.
+
+use std::path::{Path, PathBuf};
+
+use clap::error::ErrorKind;
+use notify::Watcher as _;
+
+/// Watches a config file until dropped.
+pub struct Watcher {
+    _watcher: notify::RecommendedWatcher,
+}
+
+/// Calls `reload` each time the content of the file at `path` changes, until the returned
+/// watcher is dropped. The directory is watched, so editors replacing the file are seen too.
+pub fn watch<F>(path: &Path, mut reload: F) -> Result<Watcher, clap::Error>
+where
+    F: FnMut() + Send + 'static,
+{
+    let error = |e: notify::Error| {
+        clap::Error::raw(
+            ErrorKind::Io,
+            format!("cannot watch config file '{}': {e}\n", path.display()),
+        )
+    };
+    let file = path
+        .canonicalize()
+        .map_err(|e| error(notify::Error::io(e)))?;
+    let dir = file
+        .parent()
+        .map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+    let mut last = std::fs::read(&file).ok();
+    let watched = file.clone();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+        if !event.paths.iter().any(|path| path == &watched) {
+            return;
+        }
+        // One save often raises several events; reload once per new content.
+        let content = std::fs::read(&watched).ok();
+        if content.is_some() && content != last {
+            last = content;
+            reload();
+        }
+    })
+    .map_err(error)?;
+    watcher
+        .watch(&dir, notify::RecursiveMode::NonRecursive)
+        .map_err(error)?;
+    Ok(Watcher { _watcher: watcher })
+}
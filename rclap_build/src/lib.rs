@@ -0,0 +1,85 @@
+//! Generates rclap configs from build scripts instead of the `#[config]` macro. The code is
+//! written to `OUT_DIR`, where IDEs can see it, and included with `include!`:
+//!
+//! ```no_run
+//! // build.rs
+//! rclap_build::generate("config.toml", "config_gen.rs");
+//! ```
+//!
+//! ```ignore
+//! // src/main.rs
+//! include!(concat!(env!("OUT_DIR"), "/config_gen.rs"));
+//! ```
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Generates the config of the spec `spec`, relative to the crate root, into `out`, relative
+/// to `OUT_DIR`. The struct is named after the spec file: `app_config.toml` gives `AppConfig`.
+///
+/// Panics on an invalid spec, failing the build with the error `#[config]` would report.
+pub fn generate(spec: impl AsRef<Path>, out: impl AsRef<Path>) {
+    Builder::new(spec).generate(out)
+}
+
+/// The settings of a generated config, as given to `#[config]`.
+pub struct Builder {
+    paths: Vec<String>,
+    struct_name: Option<String>,
+    settings: String,
+}
+
+impl Builder {
+    pub fn new(spec: impl AsRef<Path>) -> Self {
+        Builder {
+            paths: vec![spec.as_ref().to_string_lossy().into_owned()],
+            struct_name: None,
+            settings: String::new(),
+        }
+    }
+
+    /// A further spec file, overriding the fields of the previous ones.
+    pub fn spec(mut self, spec: impl AsRef<Path>) -> Self {
+        self.paths
+            .push(spec.as_ref().to_string_lossy().into_owned());
+        self
+    }
+
+    pub fn struct_name(mut self, struct_name: impl Into<String>) -> Self {
+        self.struct_name = Some(struct_name.into());
+        self
+    }
+
+    /// Settings of `#[config]` other than the paths, e.g. `serde = true, profile = "prod"`.
+    pub fn settings(mut self, settings: impl Into<String>) -> Self {
+        self.settings = settings.into();
+        self
+    }
+
+    /// Writes the config into `out`, relative to `OUT_DIR`, formatted by `rustfmt` when it is
+    /// installed, and tells cargo to rerun the build script when the spec changes.
+    pub fn generate(self, out: impl AsRef<Path>) {
+        let struct_name = self.struct_name.unwrap_or_else(|| {
+            let stem = Path::new(&self.paths[0])
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            stem.split(['_', '-'])
+                .map(rclap_core::to_pascal_case)
+                .collect()
+        });
+        let generated = rclap_codegen::generate(&struct_name, &self.paths, &self.settings)
+            .unwrap_or_else(|e| panic!("Invalid rclap settings: {e}"));
+        let out_dir = env::var("OUT_DIR").expect("OUT_DIR not set - call generate from build.rs");
+        let out = PathBuf::from(out_dir).join(out);
+        rclap_codegen::write_formatted(&out, &generated.code)
+            .unwrap_or_else(|e| panic!("Failed to write {}: {e}", out.display()));
+
+        for file in &generated.files {
+            println!("cargo:rerun-if-changed={}", file.display());
+        }
+        for var in &generated.env {
+            println!("cargo:rerun-if-env-changed={var}");
+        }
+    }
+}
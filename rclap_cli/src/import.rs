@@ -0,0 +1,525 @@
+//! `rclap import`: a spec equivalent to a `#[derive(Parser)]` struct, the first step of moving
+//! a CLI to rclap.
+
+use quote::ToTokens;
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Expr, Fields, GenericArgument, Item, ItemStruct, Lit, PathArguments, Type};
+
+/// The fields and sections of a struct, in the order of the struct.
+#[derive(Default)]
+struct Table {
+    app: Vec<(String, toml::Value)>,
+    fields: Vec<(String, Vec<(String, toml::Value)>)>,
+    sections: Vec<(String, Table)>,
+}
+
+/// The spec of the struct `name` of `source`, by default the first one deriving `Parser`,
+/// and the warnings about what the spec cannot express.
+pub fn import(source: &str, name: Option<&str>) -> Result<(String, Vec<String>), String> {
+    let file = syn::parse_file(source).map_err(|e| format!("cannot parse the source: {e}"))?;
+    let structs: Vec<&ItemStruct> = file
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Struct(item) => Some(item),
+            _ => None,
+        })
+        .collect();
+    let root = match name {
+        Some(name) => structs.iter().find(|item| item.ident == name),
+        None => structs.iter().find(|item| derives(item, "Parser")),
+    }
+    .ok_or_else(|| match name {
+        Some(name) => format!("no struct {name} in the source"),
+        None => "no struct derives clap::Parser in the source".to_string(),
+    })?;
+    let mut warnings = Vec::new();
+    let mut table = read_struct(root, &structs, &mut warnings);
+    table.app = app_settings(root);
+    let mut out = String::new();
+    write_table(&mut out, &table, &[]);
+    Ok((out, warnings))
+}
+
+fn derives(item: &ItemStruct, name: &str) -> bool {
+    item.attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("derive"))
+        .any(|attr| {
+            attr.parse_args_with(Punctuated::<syn::Path, syn::Token![,]>::parse_terminated)
+                .is_ok_and(|paths| {
+                    paths
+                        .iter()
+                        .any(|path| path.segments.last().is_some_and(|s| s.ident == name))
+                })
+        })
+}
+
+/// The settings of an `arg`, `clap` or `command` attribute, `None` for bare ones.
+fn settings(attrs: &[Attribute], names: &[&str]) -> Vec<(String, Option<Expr>)> {
+    let mut settings = Vec::new();
+    for attr in attrs.iter().filter(|attr| {
+        names
+            .iter()
+            .any(|name| attr.path().is_ident(name) && !matches!(attr.meta, syn::Meta::Path(_)))
+    }) {
+        let _ = attr.parse_nested_meta(|meta| {
+            let key = meta.path.to_token_stream().to_string();
+            if meta.input.peek(syn::Token![=]) {
+                settings.push((key, Some(meta.value()?.parse::<Expr>()?)));
+            } else if meta.input.peek(syn::token::Paren) {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                settings.push((key, content.parse::<Expr>().ok()));
+            } else {
+                settings.push((key, None));
+            }
+            Ok(())
+        });
+    }
+    settings
+}
+
+/// The text of a string or char literal, the tokens of any other expression.
+fn text(expr: &Expr) -> String {
+    match expr {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Str(s) => s.value(),
+            Lit::Char(c) => c.value().to_string(),
+            lit => lit.to_token_stream().to_string(),
+        },
+        expr => expr.to_token_stream().to_string().replace(' ', ""),
+    }
+}
+
+/// The doc comment: its first paragraph, and the whole text when it has more.
+fn doc(attrs: &[Attribute]) -> (Option<String>, Option<String>) {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(nv) if nv.path.is_ident("doc") => Some(text(&nv.value)),
+            _ => None,
+        })
+        .map(|line| {
+            line.strip_prefix(' ')
+                .unwrap_or(&line)
+                .trim_end()
+                .to_string()
+        })
+        .collect();
+    let text = lines.join("\n").trim().to_string();
+    if text.is_empty() {
+        return (None, None);
+    }
+    let mut paragraphs = text.split("\n\n");
+    let first = paragraphs.next().unwrap_or_default().replace('\n', " ");
+    let long = paragraphs.next().is_some().then_some(text.clone());
+    (Some(first), long)
+}
+
+/// `[app]` from the `command` attribute of the parser.
+fn app_settings(item: &ItemStruct) -> Vec<(String, toml::Value)> {
+    let mut app = Vec::new();
+    for (key, value) in settings(&item.attrs, &["command", "clap"]) {
+        let value = match (key.as_str(), value) {
+            ("name" | "about" | "long_about" | "author" | "after_help", Some(value)) => {
+                text(&value)
+            }
+            ("version", None) => "cargo".to_string(),
+            ("version", Some(value)) => text(&value),
+            ("about", None) => match doc(&item.attrs).0 {
+                Some(about) => about,
+                None => continue,
+            },
+            _ => continue,
+        };
+        app.push((key, toml::Value::String(value)));
+    }
+    app
+}
+
+fn read_struct(item: &ItemStruct, structs: &[&ItemStruct], warnings: &mut Vec<String>) -> Table {
+    let mut table = Table::default();
+    let Fields::Named(fields) = &item.fields else {
+        warnings.push(format!("{} has no named fields", item.ident));
+        return table;
+    };
+    for field in &fields.named {
+        let Some(ident) = &field.ident else { continue };
+        let name = syn::ext::IdentExt::unraw(ident).to_string();
+        let command = settings(&field.attrs, &["command", "clap"]);
+        if command.iter().any(|(key, _)| key == "subcommand") {
+            warnings.push(format!(
+                "{}.{name}: subcommands stay hand-written, next to the spec fields",
+                item.ident
+            ));
+            continue;
+        }
+        if command.iter().any(|(key, _)| key == "flatten") {
+            let ty = type_name(&field.ty);
+            match structs
+                .iter()
+                .find(|item| ty.rsplit("::").next() == Some(&item.ident.to_string()))
+            {
+                Some(section) => {
+                    table
+                        .sections
+                        .push((name, read_struct(section, structs, warnings)));
+                }
+                None => table.fields.push((
+                    name,
+                    vec![
+                        ("type".to_string(), toml::Value::String(ty)),
+                        ("external".to_string(), toml::Value::Boolean(true)),
+                    ],
+                )),
+            }
+            continue;
+        }
+        let location = format!("{}.{name}", item.ident);
+        table
+            .fields
+            .push((name.clone(), read_field(&name, field, &location, warnings)));
+    }
+    table
+}
+
+fn read_field(
+    name: &str,
+    field: &syn::Field,
+    location: &str,
+    warnings: &mut Vec<String>,
+) -> Vec<(String, toml::Value)> {
+    let mut spec = Vec::new();
+    let string = |value: String| toml::Value::String(value);
+    let (optional, ty) = match inner(&field.ty, "Option") {
+        Some(ty) => (true, ty),
+        None => (false, &field.ty),
+    };
+    let args = settings(&field.attrs, &["arg", "clap"]);
+    let has = |key: &str| args.iter().any(|(k, _)| k == key);
+    let value_enum = has("value_enum");
+    let list = inner(ty, "Vec");
+    let rust_type = type_name(ty);
+    if value_enum {
+        spec.push(("enum".to_string(), string(rust_type.clone())));
+    } else if let Some(element) = list {
+        let element = match spec_type(element) {
+            Some(element) => element,
+            None => {
+                warnings.push(format!(
+                    "{location}: lists of {} are not supported, read as strings",
+                    type_name(element)
+                ));
+                "string".to_string()
+            }
+        };
+        spec.push(("type".to_string(), string(format!("[{element}]"))));
+    } else if rust_type.ends_with("HashMap<String,String>") {
+        spec.push(("type".to_string(), string("map".to_string())));
+    } else {
+        match spec_type(ty) {
+            Some(ty) if ty == "string" => {}
+            Some(ty) => spec.push(("type".to_string(), string(ty))),
+            // Parsed with its `FromStr`, named as the config module sees it.
+            None => spec.push(("type".to_string(), string(rust_type.clone()))),
+        }
+    }
+    let (doc, long_doc) = doc(&field.attrs);
+    let mut doc = doc;
+    let mut long = None;
+    let mut short = None;
+    for (key, value) in &args {
+        match (key.as_str(), value) {
+            ("long", None) => long = Some(name.replace('_', "-")),
+            ("long", Some(value)) => long = Some(text(value)),
+            ("short", None) => short = name.chars().next().map(String::from),
+            ("short", Some(value)) => short = Some(text(value)),
+            ("help", Some(value)) => doc = Some(text(value)),
+            ("env", None) => spec.push(("env".to_string(), string(name.to_uppercase()))),
+            ("env", Some(value)) => spec.push(("env".to_string(), string(text(value)))),
+            ("default_value" | "default_value_t", Some(value)) => {
+                let default = text(value);
+                let default = match value_enum {
+                    true => variant_name(&default),
+                    false => default,
+                };
+                spec.push(("default".to_string(), string(default)));
+            }
+            ("value_delimiter", Some(value)) => {
+                spec.push(("delimiter".to_string(), string(text(value))))
+            }
+            ("alias" | "visible_alias", Some(value)) => spec.push((
+                "aliases".to_string(),
+                toml::Value::Array(vec![string(text(value))]),
+            )),
+            ("global", None) => spec.push(("global".to_string(), toml::Value::Boolean(true))),
+            ("last", None) => spec.push(("raw".to_string(), toml::Value::Boolean(true))),
+            ("value_enum" | "required" | "trailing_var_arg" | "allow_hyphen_values", _) => {}
+            (key, _) => warnings.push(format!("{location}: `{key}` is not carried over")),
+        }
+    }
+    if long.is_none() && short.is_none() {
+        if list.is_some() {
+            spec.push(("trailing".to_string(), toml::Value::Boolean(true)));
+        } else {
+            warnings.push(format!(
+                "{location}: positional arguments become flags, --{}",
+                name.replace('_', "-")
+            ));
+            long = Some(name.replace('_', "-"));
+        }
+    }
+    if let Some(long) = long {
+        spec.push(("long".to_string(), string(long)));
+    }
+    if let Some(short) = short {
+        spec.push(("short".to_string(), string(short)));
+    }
+    if optional {
+        spec.push(("optional".to_string(), toml::Value::Boolean(true)));
+    }
+    if let Some(doc) = doc {
+        spec.push(("doc".to_string(), string(doc)));
+    }
+    if let Some(long_doc) = long_doc {
+        spec.push(("long_doc".to_string(), string(long_doc)));
+    }
+    // `raw` needs `trailing`, given first.
+    if let Some(raw) = spec.iter().position(|(key, _)| key == "raw") {
+        let raw = spec.remove(raw);
+        spec.push(raw);
+    }
+    spec
+}
+
+/// The element type of `Option<T>` or `Vec<T>`, for the `wrapper` named.
+fn inner<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+    let Type::Path(path) = ty else { return None };
+    let segment = path.path.segments.last()?;
+    if segment.ident != wrapper {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => match args.args.first()? {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn type_name(ty: &Type) -> String {
+    ty.to_token_stream().to_string().replace(' ', "")
+}
+
+/// The spec `type` of a Rust type the spec has a name for.
+fn spec_type(ty: &Type) -> Option<String> {
+    let name = type_name(ty);
+    let last = name.rsplit("::").next().unwrap_or(&name);
+    match last {
+        "String" => Some("string".to_string()),
+        "PathBuf" => Some("path".to_string()),
+        "bool" | "char" | "f32" | "f64" => Some(last.to_string()),
+        _ if rclap_core::INTEGER_TYPES.contains(&last) => Some(last.to_string()),
+        _ => None,
+    }
+}
+
+/// The variant of a `ValueEnum` default: `Level::Info` or `info` as `Info`.
+fn variant_name(default: &str) -> String {
+    default
+        .rsplit("::")
+        .next()
+        .unwrap_or(default)
+        .split(['-', '_'])
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|c| c.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+fn write_table(out: &mut String, table: &Table, path: &[&str]) {
+    if !path.is_empty() && !table.fields.is_empty() {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&format!("[{}]\n", path.join(".")));
+    }
+    for (name, settings) in &table.fields {
+        let settings: Vec<String> = settings
+            .iter()
+            .map(|(key, value)| format!("{key} = {value}"))
+            .collect();
+        out.push_str(&match settings.is_empty() {
+            true => format!("{name} = {{}}\n"),
+            false => format!("{name} = {{ {} }}\n", settings.join(", ")),
+        });
+    }
+    if !table.app.is_empty() {
+        out.push_str("\n[app]\n");
+        for (key, value) in &table.app {
+            out.push_str(&format!("{key} = {value}\n"));
+        }
+    }
+    for (name, section) in &table.sections {
+        let mut path = path.to_vec();
+        path.push(name);
+        write_table(out, section, &path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(source: &str) -> (String, Vec<String>) {
+        let (spec, warnings) = import(source, None).unwrap();
+        // The spec must read back as the build reads it.
+        let table: toml::Table = toml::from_str(&spec).unwrap();
+        assert!(!table.is_empty());
+        (spec, warnings)
+    }
+
+    #[test]
+    fn test_import_fields() {
+        let (spec, warnings) = spec(
+            r#"
+            #[derive(Parser)]
+            struct Cli {
+                /// Listen port
+                #[arg(long, short, env = "PORT", default_value_t = 8080)]
+                port: u16,
+                #[arg(long)]
+                name: Option<String>,
+                #[arg(long, value_delimiter = ',')]
+                tags: Vec<String>,
+                files: Vec<PathBuf>,
+            }
+            "#,
+        );
+        assert_eq!(
+            spec,
+            r#"port = { type = "u16", env = "PORT", default = "8080", long = "port", short = "p", doc = "Listen port" }
+name = { long = "name", optional = true }
+tags = { type = "[string]", delimiter = ",", long = "tags" }
+files = { type = "[path]", trailing = true }
+"#
+        );
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_import_value_enum() {
+        let (spec, _) = spec(
+            r#"
+            #[derive(Parser)]
+            struct Cli {
+                #[arg(long, value_enum, default_value_t = Level::Info)]
+                level: Level,
+                #[arg(long, value_enum, default_value = "very-verbose")]
+                mode: Mode,
+            }
+            "#,
+        );
+        assert_eq!(
+            spec,
+            r#"level = { enum = "Level", default = "Info", long = "level" }
+mode = { enum = "Mode", default = "VeryVerbose", long = "mode" }
+"#
+        );
+    }
+
+    #[test]
+    fn test_import_flatten() {
+        let (spec, _) = spec(
+            r#"
+            #[derive(Parser)]
+            struct Cli {
+                #[command(flatten)]
+                database: Database,
+                #[command(flatten)]
+                logging: other::Logging,
+            }
+            #[derive(Args)]
+            struct Database {
+                #[arg(long)]
+                url: String,
+            }
+            "#,
+        );
+        assert_eq!(
+            spec,
+            r#"logging = { type = "other::Logging", external = true }
+
+[database]
+url = { long = "url" }
+"#
+        );
+    }
+
+    #[test]
+    fn test_import_positional() {
+        let (spec, warnings) = spec(
+            r#"
+            #[derive(Parser)]
+            struct Cli {
+                input_file: String,
+                #[arg(long, num_args = 2)]
+                pair: Vec<String>,
+            }
+            "#,
+        );
+        assert!(spec.starts_with("input_file = { long = \"input-file\" }\n"));
+        assert_eq!(
+            warnings,
+            [
+                "Cli.input_file: positional arguments become flags, --input-file",
+                "Cli.pair: `num_args` is not carried over",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_app() {
+        let (spec, _) = spec(
+            r#"
+            /// Serves the "files"
+            ///
+            /// Second paragraph.
+            #[derive(clap::Parser)]
+            #[command(name = "serve", version, about)]
+            struct Cli {
+                /// First line
+                ///
+                /// More "details"\there.
+                #[arg(long)]
+                root: PathBuf,
+            }
+            "#,
+        );
+        let table: toml::Table = toml::from_str(&spec).unwrap();
+        assert_eq!(
+            table["root"]["long_doc"].as_str(),
+            Some("First line\n\nMore \"details\"\\there.")
+        );
+        assert_eq!(table["app"]["name"].as_str(), Some("serve"));
+        assert_eq!(table["app"]["version"].as_str(), Some("cargo"));
+        assert_eq!(table["app"]["about"].as_str(), Some("Serves the \"files\""));
+    }
+
+    #[test]
+    fn test_import_named_struct() {
+        let source = "struct A { x: u8 }\n#[derive(Parser)] struct B { y: u8 }";
+        assert!(import(source, None).unwrap().0.starts_with("y = "));
+        assert!(import(source, Some("A")).unwrap().0.starts_with("x = "));
+        assert_eq!(
+            import(source, Some("C")).unwrap_err(),
+            "no struct C in the source"
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! The `rclap` command: checks specs and renders their documentation without compiling the
+//! crates using them.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use rclap_core::ConfigSpec;
+
+mod import;
+
+#[derive(Parser)]
+#[command(name = "rclap", version, about = "Works with rclap spec files")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Reports every problem of a spec, with its line: unknown keys, invalid shorts, duplicate
+    /// flags, unparsable defaults...
+    Validate {
+        spec: PathBuf,
+        /// The struct the spec is read for, lowercased in the field ids.
+        #[arg(long, default_value = "config")]
+        struct_name: String,
+    },
+    /// Prints the reference documentation of every option of a spec.
+    Docs {
+        spec: PathBuf,
+        #[arg(long, value_enum, default_value_t = Format::Md)]
+        format: Format,
+        #[arg(long, default_value = "config")]
+        struct_name: String,
+    },
+    /// Prints a `--config` file listing every field, commented out with its default.
+    Template {
+        spec: PathBuf,
+        #[arg(long, default_value = "config")]
+        struct_name: String,
+    },
+    /// Prints a `.env.example` listing the env var of every field, commented out with its
+    /// default.
+    EnvExample {
+        spec: PathBuf,
+        #[arg(long, default_value = "config")]
+        struct_name: String,
+    },
+    /// Prints a spec equivalent to a `#[derive(Parser)]` struct of a Rust source file.
+    Import {
+        source: PathBuf,
+        /// The struct to import, by default the first one deriving `Parser`.
+        #[arg(long = "struct")]
+        struct_name: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// Markdown tables, one per section.
+    Md,
+    /// A roff man page.
+    Man,
+    /// A JSON array of the options.
+    Json,
+}
+
+/// Reads `spec` as the build would, reporting its problems as `rclap validate` does.
+fn load(spec: &Path, struct_name: &str) -> Result<ConfigSpec, ExitCode> {
+    let problems = rclap_core::validate::validate(spec, struct_name);
+    if !problems.is_empty() {
+        for problem in &problems {
+            eprintln!("error: {problem}");
+        }
+        return Err(ExitCode::FAILURE);
+    }
+    ConfigSpec::from_file(spec, struct_name).map_err(|e| {
+        eprintln!("error: {e}");
+        ExitCode::FAILURE
+    })
+}
+
+fn main() -> ExitCode {
+    match Cli::parse().command {
+        Command::Docs {
+            spec,
+            format,
+            struct_name,
+        } => match load(&spec, &struct_name.to_lowercase()) {
+            Ok(config) => {
+                let name = spec.file_stem().unwrap_or_default().to_string_lossy();
+                print!(
+                    "{}",
+                    match format {
+                        Format::Md => config.markdown_help(),
+                        Format::Man => config.man_page(&name),
+                        Format::Json => config.json_docs(),
+                    }
+                );
+                ExitCode::SUCCESS
+            }
+            Err(code) => code,
+        },
+        Command::Template { spec, struct_name } => match load(&spec, &struct_name.to_lowercase()) {
+            Ok(config) => {
+                print!("{}", config.config_template());
+                ExitCode::SUCCESS
+            }
+            Err(code) => code,
+        },
+        Command::EnvExample { spec, struct_name } => {
+            match load(&spec, &struct_name.to_lowercase()) {
+                Ok(config) => {
+                    print!("{}", config.env_example());
+                    ExitCode::SUCCESS
+                }
+                Err(code) => code,
+            }
+        }
+        Command::Import {
+            source,
+            struct_name,
+        } => {
+            let imported = std::fs::read_to_string(&source)
+                .map_err(|e| format!("cannot read {}: {e}", source.display()))
+                .and_then(|source| import::import(&source, struct_name.as_deref()));
+            match imported {
+                Ok((spec, warnings)) => {
+                    for warning in warnings {
+                        eprintln!("warning: {warning}");
+                    }
+                    print!("{spec}");
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Command::Validate { spec, struct_name } => {
+            let problems = rclap_core::validate::validate(&spec, &struct_name.to_lowercase());
+            for problem in &problems {
+                eprintln!("error: {problem}");
+            }
+            if problems.is_empty() {
+                println!("{}: ok", spec.display());
+                ExitCode::SUCCESS
+            } else {
+                eprintln!(
+                    "{}: {} problem{}",
+                    spec.display(),
+                    problems.len(),
+                    if problems.len() == 1 { "" } else { "s" }
+                );
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
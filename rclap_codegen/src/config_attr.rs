@@ -0,0 +1,368 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use syn::{Token, ext::IdentExt, parse::Parse, parse::ParseStream};
+
+pub(crate) struct ConfigAttr {
+    /// The spec files, each overriding the fields of the previous ones.
+    paths: Vec<String>,
+    pub export: bool,
+    pub extra_derives: Vec<syn::Path>,
+    /// Generate `impl Default` from the spec defaults.
+    pub default: bool,
+    /// The `[profiles.<name>]` of the spec to apply.
+    profile: Option<String>,
+    /// `relative = "source"`: the paths are relative to the file of the macro call rather
+    /// than to the crate root.
+    source_relative: bool,
+    /// `env_path = "MYAPP_SPEC"`: the compile-time env var naming the spec, over `paths`.
+    env_path: Option<String>,
+    /// `module = "my_cfg"`: the generated module, by default the lowercased struct name.
+    module: Option<syn::Ident>,
+    /// `override(port.default = "9090")`: spec settings replaced for this struct only.
+    pub overrides: Vec<(String, toml::Value)>,
+    /// `debug_expand = true`: also write the generated code under `target/`.
+    debug_expand: bool,
+    /// The file of the macro call, when the compiler reports it.
+    source_file: Option<PathBuf>,
+}
+/// The `Serialize` derive added by `serde = true`, through rclap's re-export of serde.
+pub(crate) const SERDE_SERIALIZE: &str = "rclap::serde::Serialize";
+/// The `Deserialize` derive added by `deserialize = true`.
+pub(crate) const SERDE_DESERIALIZE: &str = "rclap::serde::Deserialize";
+/// The `JsonSchema` derive added by `schema = true`, through rclap's re-export of schemars.
+pub(crate) const SCHEMARS_JSON_SCHEMA: &str = "rclap::schemars::JsonSchema";
+/// The spec location reported for `config_str!`.
+const INLINE_SPEC: &str = "<inline spec>";
+/// Compile-time env var selecting the spec profile when the attribute sets none.
+pub(crate) const PROFILE_ENV: &str = "RCLAP_PROFILE";
+/// Compile-time env var turning `debug_expand` on for every config of the build.
+pub(crate) const DEBUG_EXPAND_ENV: &str = "RCLAP_DEBUG_EXPAND";
+impl ConfigAttr {
+    /// The spec files as written, joined with `, `.
+    pub(crate) fn path(&self) -> String {
+        self.paths.join(", ")
+    }
+
+    /// Marks the spec as given inline, which provenance then reports as its location.
+    pub(crate) fn inline(self) -> Self {
+        ConfigAttr {
+            paths: vec![INLINE_SPEC.to_string()],
+            ..self
+        }
+    }
+
+    pub(crate) fn with_source_file(self, source_file: Option<PathBuf>) -> Self {
+        ConfigAttr {
+            source_file,
+            ..self
+        }
+    }
+
+    /// The spec files given apart from the settings, by a build script.
+    pub(crate) fn with_paths(self, paths: Vec<String>) -> Self {
+        ConfigAttr { paths, ..self }
+    }
+
+    pub(crate) fn full_paths(&self) -> Vec<PathBuf> {
+        let base_dir = self.base_dir();
+        self.paths.iter().map(|path| base_dir.join(path)).collect()
+    }
+
+    fn base_dir(&self) -> PathBuf {
+        if self.source_relative {
+            let source = self.source_file.as_ref().expect(
+                "relative = \"source\" needs the source file of the macro call, which this compiler does not report",
+            );
+            return source.parent().map(Path::to_path_buf).unwrap_or_default();
+        }
+        let manifest_dir = env::var("CARGO_MANIFEST_DIR")
+            .expect("CARGO_MANIFEST_DIR not set - this should be available during compilation");
+        PathBuf::from(manifest_dir)
+    }
+
+    /// `profile = "..."`, or the `RCLAP_PROFILE` env var of the build.
+    pub(crate) fn profile(&self) -> Option<String> {
+        self.profile
+            .clone()
+            .or_else(|| env::var(PROFILE_ENV).ok().filter(|p| !p.is_empty()))
+    }
+
+    /// The module holding the generated types: `module`, or the lowercased struct name.
+    pub(crate) fn module(&self, struct_name: &syn::Ident) -> syn::Ident {
+        self.module.clone().unwrap_or_else(|| {
+            syn::Ident::new(
+                &struct_name.to_string().to_lowercase(),
+                proc_macro2::Span::call_site(),
+            )
+        })
+    }
+
+    /// The compile-time env vars the spec depends on, which the build must then track.
+    pub(crate) fn tracked_env(&self) -> Vec<&str> {
+        let profile = self.profile.is_none().then_some(PROFILE_ENV);
+        self.env_path
+            .as_deref()
+            .into_iter()
+            .chain(profile)
+            .chain([DEBUG_EXPAND_ENV])
+            .collect()
+    }
+
+    /// `debug_expand = true`, or `RCLAP_DEBUG_EXPAND=1` in the build env.
+    pub(crate) fn debug_expand(&self) -> bool {
+        self.debug_expand || env::var(DEBUG_EXPAND_ENV).is_ok_and(|v| v == "1" || v == "true")
+    }
+
+    /// The attribute derives followed by the `[app] derives` of the spec, without repeats.
+    pub(crate) fn derives(&self, spec_derives: &[String]) -> Vec<syn::Path> {
+        let mut derives = self.extra_derives.clone();
+        for derive in spec_derives {
+            let path: syn::Path = syn::parse_str(derive)
+                .unwrap_or_else(|_| panic!("Invalid derive '{derive}' in [app] derives"));
+            if !derives.iter().any(|d| derive_name(d) == derive_name(&path)) {
+                derives.push(path);
+            }
+        }
+        derives
+    }
+}
+impl Default for ConfigAttr {
+    fn default() -> Self {
+        Self {
+            paths: vec!["config.toml".to_string()],
+            export: true,
+            extra_derives: Vec::new(),
+            default: false,
+            profile: None,
+            source_relative: false,
+            env_path: None,
+            module: None,
+            overrides: Vec::new(),
+            debug_expand: false,
+            source_file: None,
+        }
+    }
+}
+
+impl Parse for ConfigAttr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut config = ConfigAttr::default();
+        let mut has_path = false;
+        let mut env_path = None;
+        let mut serde = false;
+        let mut schema = false;
+        let mut deserialize = false;
+
+        if input.peek(syn::LitStr) {
+            let path_lit: syn::LitStr = input.parse()?;
+            config.paths = vec![path_lit.value()];
+            has_path = true;
+            if input.peek(Token![,]) {
+                let _comma: Token![,] = input.parse()?;
+            }
+        }
+
+        while !input.is_empty() {
+            let ident = syn::Ident::parse_any(input)?;
+
+            match ident.to_string().as_str() {
+                "path" => {
+                    let _eq: Token![=] = input.parse()?;
+                    let path_lit: syn::LitStr = input.parse()?;
+                    config.paths = vec![path_lit.value()];
+                    has_path = true;
+                }
+                "module" => {
+                    let _eq: Token![=] = input.parse()?;
+                    let module_lit: syn::LitStr = input.parse()?;
+                    let module = module_lit.parse::<syn::Ident>().map_err(|_| {
+                        syn::Error::new(
+                            module_lit.span(),
+                            format!("module '{}' is not a valid identifier", module_lit.value()),
+                        )
+                    })?;
+                    config.module = Some(syn::Ident::new(
+                        &module.to_string(),
+                        proc_macro2::Span::call_site(),
+                    ));
+                }
+                "relative" => {
+                    let _eq: Token![=] = input.parse()?;
+                    let relative_lit: syn::LitStr = input.parse()?;
+                    config.source_relative = match relative_lit.value().as_str() {
+                        "source" => true,
+                        "manifest" => false,
+                        _ => {
+                            return Err(syn::Error::new(
+                                relative_lit.span(),
+                                "expected relative = \"source\" or \"manifest\"",
+                            ));
+                        }
+                    };
+                }
+                "env_path" => {
+                    let _eq: Token![=] = input.parse()?;
+                    let env_lit: syn::LitStr = input.parse()?;
+                    env_path = Some(env_lit);
+                }
+                "paths" => {
+                    let _eq: Token![=] = input.parse()?;
+                    let content;
+                    let bracket = syn::bracketed!(content in input);
+                    config.paths = content
+                        .parse_terminated(|input| input.parse::<syn::LitStr>(), Token![,])?
+                        .into_iter()
+                        .map(|path| path.value())
+                        .collect();
+                    if config.paths.is_empty() {
+                        return Err(syn::Error::new(
+                            bracket.span.join(),
+                            "paths needs at least one spec file",
+                        ));
+                    }
+                    has_path = true;
+                }
+                "export" => {
+                    let _eq: Token![=] = input.parse()?;
+                    let export_lit: syn::LitBool = input.parse()?;
+                    config.export = export_lit.value();
+                }
+                "default" => {
+                    let _eq: Token![=] = input.parse()?;
+                    let default_lit: syn::LitBool = input.parse()?;
+                    config.default = default_lit.value();
+                }
+                "debug_expand" => {
+                    let _eq: Token![=] = input.parse()?;
+                    let debug_lit: syn::LitBool = input.parse()?;
+                    config.debug_expand = debug_lit.value();
+                }
+                "profile" => {
+                    let _eq: Token![=] = input.parse()?;
+                    let profile_lit: syn::LitStr = input.parse()?;
+                    config.profile = Some(profile_lit.value());
+                }
+                "override" => {
+                    let content;
+                    syn::parenthesized!(content in input);
+                    config.overrides = content
+                        .parse_terminated(parse_override, Token![,])?
+                        .into_iter()
+                        .collect();
+                }
+                "serde" => {
+                    let _eq: Token![=] = input.parse()?;
+                    let serde_lit: syn::LitBool = input.parse()?;
+                    serde = serde_lit.value();
+                }
+                "deserialize" => {
+                    let _eq: Token![=] = input.parse()?;
+                    let deserialize_lit: syn::LitBool = input.parse()?;
+                    deserialize = deserialize_lit.value();
+                }
+                "schema" => {
+                    let _eq: Token![=] = input.parse()?;
+                    let schema_lit: syn::LitBool = input.parse()?;
+                    schema = schema_lit.value();
+                }
+                "derives" => {
+                    let _eq: Token![=] = input.parse()?;
+
+                    if input.peek(syn::token::Bracket) {
+                        let content;
+                        syn::bracketed!(content in input);
+                        config.extra_derives = content
+                            .parse_terminated(parse_derive, Token![,])?
+                            .into_iter()
+                            .collect();
+                    }
+                }
+                _ => {
+                    return Err(syn::Error::new(ident.span(), "unknown parameter"));
+                }
+            }
+
+            if input.peek(Token![,]) {
+                let _comma: Token![,] = input.parse()?;
+            }
+        }
+        if let Some(env_lit) = env_path {
+            let var = env_lit.value();
+            match env::var(&var) {
+                Ok(path) if !path.is_empty() => config.paths = vec![path],
+                _ if has_path => {}
+                _ => {
+                    return Err(syn::Error::new(
+                        env_lit.span(),
+                        format!("{var} is not set and no path is given to fall back to"),
+                    ));
+                }
+            }
+            config.env_path = Some(var);
+        }
+        if serde {
+            config.extra_derives.push(syn::parse_str(SERDE_SERIALIZE)?);
+        }
+        if deserialize {
+            config
+                .extra_derives
+                .push(syn::parse_str(SERDE_DESERIALIZE)?);
+        }
+        if schema {
+            config
+                .extra_derives
+                .push(syn::parse_str(SCHEMARS_JSON_SCHEMA)?);
+        }
+        Ok(config)
+    }
+}
+
+/// The path of a derive as written, e.g. `rclap::serde::Serialize`.
+pub(crate) fn derive_name(path: &syn::Path) -> String {
+    quote::quote! { #path }.to_string().replace(' ', "")
+}
+
+/// A derive given as a path (`serde::Serialize`) or a string (`"Hash"`).
+fn parse_derive(input: ParseStream) -> syn::Result<syn::Path> {
+    if input.peek(syn::LitStr) {
+        input.parse::<syn::LitStr>()?.parse()
+    } else {
+        input.parse()
+    }
+}
+
+/// A `field.path.setting = value` override, the value a string, integer, float or bool.
+fn parse_override(input: ParseStream) -> syn::Result<(String, toml::Value)> {
+    let mut path = vec![syn::Ident::parse_any(input)?.to_string()];
+    while input.peek(Token![.]) {
+        let _dot: Token![.] = input.parse()?;
+        path.push(syn::Ident::parse_any(input)?.to_string());
+    }
+    let _eq: Token![=] = input.parse()?;
+    let negative = input.peek(Token![-]);
+    if negative {
+        let _minus: Token![-] = input.parse()?;
+    }
+    let value = match input.parse::<syn::Lit>()? {
+        syn::Lit::Str(s) if !negative => toml::Value::String(s.value()),
+        syn::Lit::Bool(b) if !negative => toml::Value::Boolean(b.value),
+        syn::Lit::Int(i) => {
+            let value: i64 = i.base10_parse()?;
+            toml::Value::Integer(if negative { -value } else { value })
+        }
+        syn::Lit::Float(f) => {
+            let value: f64 = f.base10_parse()?;
+            toml::Value::Float(if negative { -value } else { value })
+        }
+        lit => {
+            return Err(syn::Error::new(
+                lit.span(),
+                "expected a string, integer, float or bool",
+            ));
+        }
+    };
+    Ok((path.join("."), value))
+}
@@ -0,0 +1,310 @@
+//! `#[derive(Config)]`: clap impls for a hand-written struct whose fields take their flag, env
+//! var, default and help from the spec field of the same id.
+
+use std::path::PathBuf;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use rclap_core::{GenericSpec, Spec};
+use syn::ext::IdentExt;
+
+use crate::config_attr::ConfigAttr;
+
+pub(crate) fn derive_config(
+    input: &syn::DeriveInput,
+    source_file: Option<PathBuf>,
+) -> syn::Result<TokenStream> {
+    let config_attr = match input
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("config"))
+    {
+        Some(attr) => attr.parse_args::<ConfigAttr>()?,
+        None => ConfigAttr::default(),
+    }
+    .with_source_file(source_file);
+    let syn::Data::Struct(syn::DataStruct {
+        fields: syn::Fields::Named(fields),
+        ..
+    }) = &input.data
+    else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "#[derive(Config)] needs a struct with named fields",
+        ));
+    };
+    let struct_name = &input.ident;
+    let config_spec = crate::load_spec(&config_attr, struct_name);
+    let prefix = struct_name.to_string().to_lowercase();
+
+    let mut args = vec![];
+    let mut values = vec![];
+    for field in &fields.named {
+        let name = field.ident.as_ref().expect("named field");
+        let (path, span) = match field_id(field)? {
+            Some(id) => (id.value(), id.span()),
+            None => (name.unraw().to_string(), name.span()),
+        };
+        let id = format!("{prefix}.{path}");
+        let spec = find_field(&config_spec.fields, &id)
+            .ok_or_else(|| syn::Error::new(span, format!("the spec declares no field '{path}'")))?;
+        let shape = FieldShape::of(&field.ty);
+        args.push(field_arg(spec, &id, &shape).map_err(|e| syn::Error::new(span, e))?);
+        values.push(field_value(name, &id, &shape));
+    }
+
+    let track_inputs = crate::track_inputs(&config_attr, &config_spec);
+    let generated = quote! {
+        const _: () = {
+            use rclap::__private::clap;
+            #track_inputs
+
+            impl clap::FromArgMatches for #struct_name {
+                fn from_arg_matches(matches: &clap::ArgMatches) -> Result<Self, clap::Error> {
+                    Self::from_arg_matches_mut(&mut matches.clone())
+                }
+
+                fn from_arg_matches_mut(
+                    matches: &mut clap::ArgMatches,
+                ) -> Result<Self, clap::Error> {
+                    Ok(Self {
+                        #(#values)*
+                    })
+                }
+
+                fn update_from_arg_matches(
+                    &mut self,
+                    matches: &clap::ArgMatches,
+                ) -> Result<(), clap::Error> {
+                    *self = Self::from_arg_matches(matches)?;
+                    Ok(())
+                }
+            }
+
+            impl clap::Args for #struct_name {
+                fn augment_args(cmd: clap::Command) -> clap::Command {
+                    cmd #(.arg(#args))*
+                }
+
+                fn augment_args_for_update(cmd: clap::Command) -> clap::Command {
+                    Self::augment_args(cmd)
+                }
+            }
+
+            impl clap::CommandFactory for #struct_name {
+                fn command() -> clap::Command {
+                    <Self as clap::Args>::augment_args(clap::Command::new(env!("CARGO_PKG_NAME")))
+                }
+
+                fn command_for_update() -> clap::Command {
+                    Self::command()
+                }
+            }
+
+            impl clap::Parser for #struct_name {}
+        };
+    };
+    Ok(crate::debug_expand(&config_attr, struct_name, generated))
+}
+
+/// The `#[config(id = "database.url")]` of a field.
+fn field_id(field: &syn::Field) -> syn::Result<Option<syn::LitStr>> {
+    let mut id = None;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("config"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("id") {
+                id = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `id = \"...\"`"))
+            }
+        })?;
+    }
+    Ok(id)
+}
+
+fn find_field<'a>(fields: &'a [Spec], id: &str) -> Option<&'a Spec> {
+    fields.iter().find_map(|field| match &field.variant {
+        _ if field.id == id => Some(field),
+        GenericSpec::SubtypeSpec(sub) => find_field(sub, id),
+        _ => None,
+    })
+}
+
+/// How the Rust type of a field is filled from the matches.
+enum FieldShape<'a> {
+    Flag,
+    One(&'a syn::Type),
+    Optional(&'a syn::Type),
+    Many(&'a syn::Type),
+}
+
+impl<'a> FieldShape<'a> {
+    fn of(ty: &'a syn::Type) -> Self {
+        if let syn::Type::Path(path) = ty
+            && let Some(segment) = path.path.segments.last()
+        {
+            if segment.ident == "bool" && segment.arguments.is_empty() {
+                return FieldShape::Flag;
+            }
+            if let syn::PathArguments::AngleBracketed(args) = &segment.arguments
+                && args.args.len() == 1
+                && let Some(syn::GenericArgument::Type(inner)) = args.args.first()
+            {
+                if segment.ident == "Option" {
+                    return FieldShape::Optional(inner);
+                }
+                if segment.ident == "Vec" {
+                    return FieldShape::Many(inner);
+                }
+            }
+        }
+        FieldShape::One(ty)
+    }
+}
+
+/// The `clap::Arg` of a field, from the settings of its spec field.
+fn field_arg(spec: &Spec, id: &str, shape: &FieldShape) -> Result<TokenStream, String> {
+    let (long, short, env, defaults) = match &spec.variant {
+        GenericSpec::FieldSpec(f) => (
+            &f.long_arg,
+            f.short_arg,
+            &f.env,
+            f.default.iter().cloned().collect(),
+        ),
+        GenericSpec::EnumSpec(e) => (
+            &e.long_arg,
+            e.short_arg,
+            &e.env,
+            e.default.iter().cloned().collect(),
+        ),
+        GenericSpec::VecSpec(v) => {
+            let defaults: Vec<String> = v
+                .default
+                .as_ref()
+                .and_then(|d| d.as_array())
+                .into_iter()
+                .flatten()
+                .map(|value| match value {
+                    toml::Value::String(s) => s.clone(),
+                    value => value.to_string(),
+                })
+                .collect();
+            (&v.long_arg, v.short_arg, &v.env, defaults)
+        }
+        _ => {
+            return Err(format!(
+                "'{}' is not a value field of the spec; #[derive(Config)] maps value fields only",
+                spec.id
+            ));
+        }
+    };
+    let long = long.as_deref().unwrap_or(id);
+    let mut arg = match &spec.variant {
+        GenericSpec::VecSpec(v) if v.raw => quote! {
+            clap::Arg::new(#id).last(true).allow_hyphen_values(true)
+        },
+        GenericSpec::VecSpec(v) if v.trailing => quote! {
+            clap::Arg::new(#id).trailing_var_arg(true).allow_hyphen_values(true)
+        },
+        _ => quote! { clap::Arg::new(#id).long(#long) },
+    };
+    if let Some(short) = short {
+        arg.extend(quote! { .short(#short) });
+    }
+    if let Some(env) = env {
+        arg.extend(quote! { .env(#env) });
+    }
+    if let Some(doc) = &spec.doc {
+        arg.extend(quote! { .help(#doc) });
+    }
+    if let Some(long_doc) = &spec.long_doc {
+        arg.extend(quote! { .long_help(#long_doc) });
+    }
+    if !spec.aliases.is_empty() {
+        let aliases = &spec.aliases;
+        arg.extend(quote! { .visible_aliases([#(#aliases),*]) });
+    }
+    if spec.deprecated.is_some() {
+        arg.extend(quote! { .hide(true) });
+    }
+    if spec.global {
+        arg.extend(quote! { .global(true) });
+    }
+    if let Some(hint) = crate::value_hint(spec) {
+        arg.extend(quote! { .value_hint(#hint) });
+    }
+    for (method, value) in crate::arg_conditions(spec) {
+        arg.extend(quote! { .#method(#value) });
+    }
+    if let Some(num_args) = crate::num_args(spec) {
+        arg.extend(quote! { .num_args(#num_args) });
+    }
+    if let Some(missing) = &spec.default_missing_value {
+        arg.extend(quote! { .default_missing_value(#missing) });
+    }
+    if !defaults.is_empty() {
+        arg.extend(quote! { .default_values([#(#defaults),*]) });
+    }
+    arg.extend(match shape {
+        FieldShape::Flag => quote! { .action(clap::ArgAction::SetTrue) },
+        FieldShape::One(ty) => {
+            let required = defaults.is_empty();
+            quote! {
+                .value_parser(clap::value_parser!(#ty))
+                .action(clap::ArgAction::Set)
+                .required(#required)
+            }
+        }
+        FieldShape::Optional(ty) => quote! {
+            .value_parser(clap::value_parser!(#ty))
+            .action(clap::ArgAction::Set)
+        },
+        FieldShape::Many(ty) => {
+            let mut many = quote! {
+                .value_parser(clap::value_parser!(#ty))
+                .action(clap::ArgAction::Append)
+            };
+            if let GenericSpec::VecSpec(v) = &spec.variant {
+                if v.split_paths {
+                    many.extend(quote! { .value_delimiter(rclap::cli::PATH_LIST_SEPARATOR) });
+                } else if let Some(delimiter) = v.delimiter {
+                    many.extend(quote! { .value_delimiter(#delimiter) });
+                }
+            }
+            many
+        }
+    });
+    if let GenericSpec::EnumSpec(e) = &spec.variant
+        && e.ignore_case
+    {
+        arg.extend(quote! { .ignore_case(true) });
+    }
+    Ok(arg)
+}
+
+fn field_value(name: &syn::Ident, id: &str, shape: &FieldShape) -> TokenStream {
+    let value = match shape {
+        FieldShape::Flag => quote! { matches.get_flag(#id) },
+        FieldShape::One(ty) => quote! {
+            matches.remove_one::<#ty>(#id).ok_or_else(|| {
+                clap::Error::raw(
+                    clap::error::ErrorKind::MissingRequiredArgument,
+                    format!("the field '{}' was not given\n", #id),
+                )
+            })?
+        },
+        FieldShape::Optional(ty) => quote! { matches.remove_one::<#ty>(#id) },
+        FieldShape::Many(ty) => quote! {
+            matches
+                .remove_many::<#ty>(#id)
+                .map(|values| values.collect())
+                .unwrap_or_default()
+        },
+    };
+    quote! { #name: #value, }
+}
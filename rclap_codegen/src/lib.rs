@@ -0,0 +1,2469 @@
+//! The code generation behind rclap's macros, shared by `rclap_derive` and by `rclap_build`
+//! for build scripts. Each entry point returns the generated items, or the compile error
+//! reporting why they could not be generated.
+
+mod config_attr;
+mod config_derive;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use proc_macro2::{Literal, TokenStream};
+use quote::quote;
+use rclap_core::*;
+use syn::ext::IdentExt;
+
+use crate::config_attr::{
+    ConfigAttr, SCHEMARS_JSON_SCHEMA, SERDE_DESERIALIZE, SERDE_SERIALIZE, derive_name,
+};
+
+/// `#[config]` on a struct or an inline module. `source_file` is the file of the macro call,
+/// against which `relative = "source"` paths resolve.
+pub fn config(args: TokenStream, input: TokenStream, source_file: Option<PathBuf>) -> TokenStream {
+    let config_attr = match syn::parse2::<ConfigAttr>(args) {
+        Ok(attr) => attr.with_source_file(source_file),
+        Err(e) => return e.to_compile_error(),
+    };
+    let input_parsed = match syn::parse2::<syn::Item>(input) {
+        Ok(syn::Item::Struct(item)) => item,
+        Ok(syn::Item::Mod(item)) => return generate_module(item, &config_attr),
+        Ok(item) => {
+            return syn::Error::new_spanned(item, "#[config] applies to a struct or a module")
+                .to_compile_error();
+        }
+        Err(e) => return e.to_compile_error(),
+    };
+    let struct_name = &input_parsed.ident;
+    let config_spec = load_spec(&config_attr, struct_name);
+    let user_fields = match user_fields(&input_parsed, &config_spec) {
+        Ok(fields) => fields,
+        Err(e) => return e.to_compile_error(),
+    };
+
+    let generated = generate_struct(config_spec, struct_name, &config_attr, &user_fields);
+    debug_expand(&config_attr, struct_name, generated)
+}
+
+/// `#[config] mod settings { ... }`: the config generated inside the module, as a `Settings`
+/// umbrella struct holding a struct per top-level section (`DatabaseConfig`, ...), so each
+/// subsystem can take only its own part.
+fn generate_module(item: syn::ItemMod, config_attr: &ConfigAttr) -> TokenStream {
+    let Some((_, items)) = &item.content else {
+        return syn::Error::new_spanned(
+            &item,
+            "#[config] needs an inline module, e.g. `mod settings { }`",
+        )
+        .to_compile_error();
+    };
+    let struct_name = syn::Ident::new(
+        &to_pascal_case(&item.ident.to_string()),
+        proc_macro2::Span::call_site(),
+    );
+    let config_spec = load_spec(config_attr, &struct_name);
+    let generated = debug_expand(
+        config_attr,
+        &struct_name,
+        generate_struct(config_spec, &struct_name, config_attr, &[]),
+    );
+    let syn::ItemMod {
+        attrs,
+        vis,
+        mod_token,
+        ident,
+        ..
+    } = &item;
+    quote! {
+        #(#attrs)*
+        #vis #mod_token #ident {
+            #(#items)*
+            #generated
+        }
+    }
+}
+
+/// `#[config_section]`: a reusable section struct, for library crates.
+pub fn config_section(
+    args: TokenStream,
+    input: TokenStream,
+    source_file: Option<PathBuf>,
+) -> TokenStream {
+    let config_attr = match syn::parse2::<ConfigAttr>(args) {
+        Ok(attr) => attr.with_source_file(source_file),
+        Err(e) => return e.to_compile_error(),
+    };
+    let input_parsed = match syn::parse2::<syn::ItemStruct>(input) {
+        Ok(item) => item,
+        Err(e) => return e.to_compile_error(),
+    };
+    let struct_name = &input_parsed.ident;
+    let config_spec = load_spec(&config_attr, struct_name);
+    let user_fields = match user_fields(&input_parsed, &config_spec) {
+        Ok(fields) => fields,
+        Err(e) => return e.to_compile_error(),
+    };
+
+    let generated = generate_section(config_spec, struct_name, &config_attr, &user_fields);
+    debug_expand(&config_attr, struct_name, generated)
+}
+
+/// `#[derive(Config)]` on a hand-written struct.
+pub fn derive_config(input: TokenStream, source_file: Option<PathBuf>) -> TokenStream {
+    syn::parse2::<syn::DeriveInput>(input)
+        .and_then(|input| config_derive::derive_config(&input, source_file))
+        .unwrap_or_else(syn::Error::into_compile_error)
+}
+
+/// `config_str!(MyConfig, "...")`: a config from a spec given inline.
+pub fn config_str(input: TokenStream) -> TokenStream {
+    let InlineSpec {
+        struct_name,
+        spec,
+        config_attr,
+    } = match syn::parse2::<InlineSpec>(input) {
+        Ok(inline) => inline,
+        Err(e) => return e.to_compile_error(),
+    };
+    let generic = GenericConfigSpec::from_toml_str(&spec.value())
+        .unwrap_or_else(|e| panic!("Failed to parse Toml config: {}", e));
+    let config_spec = resolve_spec(generic, &config_attr, &struct_name);
+
+    let generated = generate_struct(config_spec, &struct_name, &config_attr, &[]);
+    debug_expand(&config_attr, &struct_name, generated)
+}
+
+/// A config generated outside of a macro call, by a build script.
+pub struct Generated {
+    pub code: TokenStream,
+    /// The spec files read, which the build must watch.
+    pub files: Vec<PathBuf>,
+    /// The build env vars the spec depends on.
+    pub env: Vec<String>,
+}
+
+/// Generates `struct_name` from the spec files `paths`, relative to the crate root, as
+/// `#[config]` would with the attribute `settings` (e.g. `serde = true`).
+pub fn generate(struct_name: &str, paths: &[String], settings: &str) -> syn::Result<Generated> {
+    let config_attr = syn::parse_str::<ConfigAttr>(settings)?.with_paths(paths.to_vec());
+    let struct_name: syn::Ident = syn::parse_str(struct_name)?;
+    let config_spec = load_spec(&config_attr, &struct_name);
+    let files = config_spec.files.clone();
+    let env = config_attr
+        .tracked_env()
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    let mut code: syn::File = syn::parse2(generate_struct(
+        config_spec,
+        &struct_name,
+        &config_attr,
+        &[],
+    ))?;
+    // Unlike macro output, included code is linted as if written by hand.
+    let allow: syn::Attribute =
+        syn::parse_quote! { #[allow(dead_code, unused_imports, clippy::all)] };
+    for item in &mut code.items {
+        match item {
+            syn::Item::Mod(item) => item.attrs.push(allow.clone()),
+            syn::Item::Use(item) => item.attrs.push(allow.clone()),
+            _ => {}
+        }
+    }
+    Ok(Generated {
+        code: quote! { #code },
+        files,
+        env,
+    })
+}
+
+struct InlineSpec {
+    struct_name: syn::Ident,
+    spec: syn::LitStr,
+    config_attr: ConfigAttr,
+}
+
+impl syn::parse::Parse for InlineSpec {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let struct_name = input.parse()?;
+        let _comma: syn::Token![,] = input.parse()?;
+        let spec = input.parse()?;
+        if input.peek(syn::Token![,]) {
+            let _comma: syn::Token![,] = input.parse()?;
+        }
+        let config_attr: ConfigAttr = input.parse()?;
+        Ok(InlineSpec {
+            struct_name,
+            spec,
+            config_attr: config_attr.inline(),
+        })
+    }
+}
+
+/// Writes `code` to a file when the attribute asks for `debug_expand`, so the generated code
+/// can be read and diffed without cargo-expand.
+pub(crate) fn debug_expand(
+    config_attr: &ConfigAttr,
+    struct_name: &syn::Ident,
+    code: TokenStream,
+) -> TokenStream {
+    if config_attr.debug_expand() {
+        let file = expand_dir().join(format!("{}.rs", config_attr.module(struct_name)));
+        fs::create_dir_all(expand_dir())
+            .and_then(|_| write_formatted(&file, &code))
+            .unwrap_or_else(|e| panic!("Failed to write {}: {e}", file.display()));
+    }
+    code
+}
+
+/// `rclap-expand/` in `OUT_DIR` when the crate has a build script, or else in the `target`
+/// directory, under the package name.
+fn expand_dir() -> PathBuf {
+    if let Ok(out_dir) = env::var("OUT_DIR") {
+        return PathBuf::from(out_dir).join("rclap-expand");
+    }
+    let manifest_dir = PathBuf::from(
+        env::var("CARGO_MANIFEST_DIR")
+            .expect("CARGO_MANIFEST_DIR not set - this should be available during compilation"),
+    );
+    let target_dir = env::var("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .ok()
+        .or_else(|| {
+            manifest_dir
+                .ancestors()
+                .map(|dir| dir.join("target"))
+                .find(|dir| dir.is_dir())
+        })
+        .unwrap_or_else(|| manifest_dir.join("target"));
+    let package = env::var("CARGO_PKG_NAME").unwrap_or_default();
+    target_dir.join("rclap-expand").join(package)
+}
+
+/// Writes `code` to `file`, formatted by `rustfmt` when it is installed.
+pub fn write_formatted(file: &Path, code: &TokenStream) -> std::io::Result<()> {
+    fs::write(file, code.to_string())?;
+    // Unformatted code is still valid, so a missing rustfmt is not an error.
+    let _ = Command::new("rustfmt")
+        .args(["--edition", "2024"])
+        .arg(file)
+        .status();
+    Ok(())
+}
+
+/// The spec of the attribute, with its profile and overrides applied.
+fn load_spec(config_attr: &ConfigAttr, struct_name: &proc_macro2::Ident) -> ConfigSpec {
+    let generic = GenericConfigSpec::from_files(&config_attr.full_paths())
+        .unwrap_or_else(|e| panic!("Failed to parse Toml config: {}", e));
+    resolve_spec(generic, config_attr, struct_name)
+}
+
+fn resolve_spec(
+    generic: GenericConfigSpec,
+    config_attr: &ConfigAttr,
+    struct_name: &proc_macro2::Ident,
+) -> ConfigSpec {
+    let struct_def: String = quote! {#struct_name}.to_string().to_lowercase();
+    generic
+        .with_struct_name(struct_def)
+        .with_profile(config_attr.profile())
+        .with_overrides(config_attr.overrides.clone())
+        .try_into()
+        .unwrap_or_else(|problems: Vec<_>| panic!("{}", validate::messages(&problems)))
+}
+
+/// The hand-written fields of the annotated struct, kept next to the spec fields. Fields
+/// without a visibility stay visible where the struct was written.
+fn user_fields(item: &syn::ItemStruct, config_spec: &ConfigSpec) -> syn::Result<Vec<syn::Field>> {
+    let fields = match &item.fields {
+        syn::Fields::Unit => return Ok(vec![]),
+        syn::Fields::Unnamed(fields) => {
+            return Err(syn::Error::new_spanned(
+                fields,
+                "a config struct takes named fields only",
+            ));
+        }
+        syn::Fields::Named(fields) => &fields.named,
+    };
+    fields
+        .iter()
+        .map(|field| {
+            let ident = field.ident.as_ref().expect("named field");
+            if config_spec
+                .fields
+                .iter()
+                .any(|spec| ident.unraw() == spec.name)
+            {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    format!("field `{ident}` is already declared by the spec"),
+                ));
+            }
+            let mut field = field.clone();
+            if matches!(field.vis, syn::Visibility::Inherited) {
+                field.vis = syn::parse_quote! { pub(super) };
+            }
+            Ok(field)
+        })
+        .collect()
+}
+
+fn generate_section(
+    config_spec: ConfigSpec,
+    struct_name: &proc_macro2::Ident,
+    config_attr: &ConfigAttr,
+    user_fields: &[syn::Field],
+) -> TokenStream {
+    let extra_derives = config_attr.derives(&config_spec.app.derives);
+    let mut all_structs = vec![generate_single_struct(
+        struct_name,
+        &config_spec.fields,
+        user_fields,
+        quote! {},
+        &quote! {},
+        extra_derives.clone(),
+        false,
+    )];
+    let mut all_iter_map_impls = vec![generate_iter_map_impl(struct_name, &config_spec.fields)];
+    collect_subtypes(
+        &config_spec.fields,
+        &mut all_structs,
+        extra_derives.clone(),
+        &mut all_iter_map_impls,
+        false,
+    );
+    let field_paths: Vec<TokenStream> = section_fields(&config_spec.fields)
+        .into_iter()
+        .map(|(cfg, id, ty)| {
+            let path = id.split_once('.').map_or(id.as_str(), |(_, path)| path);
+            quote! { #cfg (#path, #ty) }
+        })
+        .collect();
+    let spec_path = config_attr.path();
+    let default_impls = if config_attr.default {
+        generate_default_impls(struct_name, &config_spec.fields)
+    } else {
+        vec![]
+    };
+    let private_mod_name = config_attr.module(struct_name);
+    let export = if config_attr.export {
+        quote! {
+           pub use #private_mod_name::*;
+        }
+    } else {
+        quote! {}
+    };
+    let track_inputs = track_inputs(config_attr, &config_spec);
+    quote! {
+        pub mod #private_mod_name {
+            use rclap::__private::clap;
+            use rclap::Secret;
+            use rclap::StringSecret;
+            #track_inputs
+            #(#all_structs)*
+            #(#all_iter_map_impls)*
+            #(#default_impls)*
+
+            impl rclap::section::ConfigSection for #struct_name {
+                const SPEC: &'static str = #spec_path;
+                const FIELDS: &'static [(&'static str, &'static str)] = &[#(#field_paths),*];
+
+                fn rclap_fill_maps(&mut self, matches: &clap::ArgMatches) {
+                    Self::rclap_fill_maps(self, matches)
+                }
+            }
+        }
+
+        pub use #private_mod_name::#struct_name;
+        #export
+    }
+}
+
+/// Reading the spec files through `include_bytes!`, and `RCLAP_PROFILE` and `env_path`
+/// through `option_env!`, makes cargo rebuild when they change.
+fn track_inputs(config_attr: &ConfigAttr, config_spec: &ConfigSpec) -> TokenStream {
+    let files = config_spec
+        .files
+        .iter()
+        .map(|file| file.to_string_lossy().into_owned());
+    let vars = config_attr.tracked_env();
+    quote! {
+        #(const _: &[u8] = include_bytes!(#files);)*
+        #(const _: Option<&str> = option_env!(#vars);)*
+    }
+}
+
+fn generate_struct(
+    config_spec: ConfigSpec,
+    struct_name: &proc_macro2::Ident,
+    config_attr: &ConfigAttr,
+    user_fields: &[syn::Field],
+) -> proc_macro2::TokenStream {
+    let mut extra_derives = config_attr.derives(&config_spec.app.derives);
+    // `--dump-config` prints the config through its `Serialize`.
+    if config_spec.app.dump_config && !derives_rclap(&extra_derives, SERDE_SERIALIZE) {
+        extra_derives.push(syn::parse_str(SERDE_SERIALIZE).expect("valid derive path"));
+    }
+    let mut all_structs = Vec::new();
+    let mut all_iter_map_impls = Vec::new();
+    let mut user_fields = user_fields.to_vec();
+    if config_spec.app.external_subcommands {
+        user_fields.push(syn::parse_quote! {
+            #[command(subcommand)]
+            pub command: Option<Commands>
+        });
+        all_structs.push(generate_commands_enum(extra_derives.clone()));
+    }
+
+    let main_struct = generate_single_struct(
+        struct_name,
+        &config_spec.fields,
+        &user_fields,
+        app_command(&config_spec.app),
+        &quote! { .help_heading(None::<&str>) },
+        extra_derives.clone(),
+        false,
+    );
+    all_structs.push(main_struct);
+
+    let main_iter_map = generate_iter_map_impl(struct_name, &config_spec.fields);
+    all_iter_map_impls.push(main_iter_map);
+
+    collect_subtypes(
+        &config_spec.fields,
+        &mut all_structs,
+        extra_derives.clone(),
+        &mut all_iter_map_impls,
+        false,
+    );
+    let locked_ids = field_ids(&config_spec.fields, &|field| field.locked);
+    let sensitive_ids = field_ids(&config_spec.fields, &|field| field.sensitive);
+    let deprecated_fields = deprecated_fields(&config_spec.fields);
+    let provenance_fields: Vec<TokenStream> = provenance_fields(&config_spec.fields)
+        .into_iter()
+        .map(|(cfg, id, secret)| quote! { #cfg (#id, #secret) })
+        .collect();
+    let spec_path = config_attr.path();
+    let default_impls = if config_attr.default {
+        generate_default_impls(struct_name, &config_spec.fields)
+    } else {
+        vec![]
+    };
+    let private_mod_name = config_attr.module(struct_name);
+    let on_error = if config_spec.app.error_json {
+        quote! {
+            if e.use_stderr() {
+                let mut cmd = <Self as clap::CommandFactory>::command();
+                eprintln!("{}", rclap::cli::error_json(&mut cmd, &e));
+                std::process::exit(e.exit_code());
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let with_config_file = if config_spec.app.config_file.enabled {
+        quote! { let cmd = rclap::config_file::with_config_file(cmd); }
+    } else {
+        quote! {}
+    };
+    // An injected environment replaces the process one, `.env` file included.
+    let load_dotenv = match (&config_spec.app.dotenv_path, config_spec.app.dotenv) {
+        (Some(path), _) => quote! {
+            if env.is_none() {
+                rclap::cli::load_dotenv(#path)?;
+            }
+        },
+        (None, true) => quote! {
+            if env.is_none() {
+                rclap::cli::load_dotenv(".env")?;
+            }
+        },
+        (None, false) => quote! {},
+    };
+    let with_config_template = if config_spec.app.config_template {
+        quote! { let cmd = rclap::cli::with_config_template(cmd); }
+    } else {
+        quote! {}
+    };
+    let print_config_template = if config_spec.app.config_template {
+        quote! {
+            if matches.get_flag(rclap::cli::CONFIG_TEMPLATE_ID) {
+                return Err(rclap::cli::display(Self::config_template().to_string()));
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let with_dump_config = if config_spec.app.dump_config {
+        quote! { let cmd = rclap::cli::with_dump_config(cmd); }
+    } else {
+        quote! {}
+    };
+    let dump_config = if config_spec.app.dump_config {
+        quote! {
+            if let Some(dump) =
+                rclap::serialize::dump_config(&mut cmd, &matches, &config, &[#(#sensitive_ids),*])?
+            {
+                return Err(rclap::cli::display(dump));
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let lookup_config_file = match &config_spec.app.config_file.name {
+        Some(name) => quote! {
+            let config_file = match config_file {
+                Some(file) => Some(file),
+                None => rclap::config_file::ConfigFile::from_config_dir(&mut cmd, #name)?,
+            };
+        },
+        None => quote! {},
+    };
+    let watch = if config_spec.app.watch {
+        quote! {
+            /// Parses the process arguments again each time the config file changes, passing
+            /// the new config to `callback`, until the returned watcher is dropped.
+            pub fn watch<F>(callback: F) -> Result<rclap::watch::Watcher, clap::Error>
+            where
+                F: FnMut(Result<Self, clap::Error>) + Send + 'static,
+            {
+                Self::watch_from(std::env::args_os(), callback)
+            }
+
+            /// `watch` with the arguments `itr`: the config file it names (or looks up) is
+            /// watched, and a change parses `itr` again.
+            pub fn watch_from<I, T, F>(
+                itr: I,
+                mut callback: F,
+            ) -> Result<rclap::watch::Watcher, clap::Error>
+            where
+                I: IntoIterator<Item = T>,
+                T: Into<std::ffi::OsString> + Clone,
+                F: FnMut(Result<Self, clap::Error>) + Send + 'static,
+            {
+                let mut cmd = Self::command();
+                let itr: Vec<std::ffi::OsString> = itr.into_iter().map(Into::into).collect();
+                let config_file = rclap::config_file::ConfigFile::from_args(&mut cmd, &itr)?;
+                #lookup_config_file
+                let Some(file) = config_file else {
+                    return Err(cmd.error(
+                        clap::error::ErrorKind::MissingRequiredArgument,
+                        "no config file to watch: give --config",
+                    ));
+                };
+                rclap::watch::watch(&file.path, move || {
+                    callback(Self::try_parse_with_layers(itr.clone(), &[]))
+                })
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let global = if config_spec.app.global {
+        quote! {
+            /// Parses the process arguments, exiting on error, and stores the config for
+            /// `global()`. Later calls return the config stored by the first one.
+            pub fn init() -> &'static Self {
+                RCLAP_GLOBAL.get_or_init(Self::parse)
+            }
+
+            /// Stores `config` for `global()`, e.g. one parsed from other arguments with
+            /// `parse_from`, handing it back when a config is already stored.
+            pub fn init_with(config: Self) -> Result<&'static Self, Self> {
+                RCLAP_GLOBAL.set(config)?;
+                Ok(Self::global())
+            }
+
+            /// The config stored by `init()`, readable from anywhere in the process.
+            ///
+            /// # Panics
+            ///
+            /// When `init()` was not called yet.
+            pub fn global() -> &'static Self {
+                RCLAP_GLOBAL.get().unwrap_or_else(|| {
+                    panic!(
+                        "{}::global() called before {}::init()",
+                        stringify!(#struct_name),
+                        stringify!(#struct_name)
+                    )
+                })
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let global_static = if config_spec.app.global {
+        quote! {
+            static RCLAP_GLOBAL: std::sync::OnceLock<#struct_name> = std::sync::OnceLock::new();
+        }
+    } else {
+        quote! {}
+    };
+    // The `--config` file is read before the real parse: its values become arg defaults.
+    let load_config_file = if config_spec.app.config_file.enabled {
+        quote! {
+            let itr: Vec<std::ffi::OsString> = itr.into_iter().map(Into::into).collect();
+            let config_file = rclap::config_file::ConfigFile::from_args(&mut cmd, &itr)?;
+            #lookup_config_file
+        }
+    } else {
+        quote! {}
+    };
+    let env_files = env_files(&config_spec.fields);
+    // Read before the parse, so an unreadable file fails it.
+    let read_env_files = if env_files.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            let env_files = match env {
+                Some(vars) => rclap::layer::EnvFileLayer::read_from(&[#(#env_files),*], vars)?,
+                None => rclap::layer::EnvFileLayer::read(&[#(#env_files),*])?,
+            };
+        }
+    };
+    let stack = config_spec
+        .app
+        .sources()
+        .into_iter()
+        .map(|source| match source {
+            "cli" => quote! { stack.push(&rclap::layer::CliLayer); },
+            "env" if env_files.is_empty() => quote! { stack.push(env_layer); },
+            "env" => quote! {
+                stack.push(env_layer);
+                stack.push(&env_files);
+            },
+            "file" => quote! {
+                if let Some(file) = &config_file {
+                    stack.push(file);
+                }
+            },
+            _ => quote! {
+                stack.extend_from_slice(layers);
+                stack.push(&rclap::layer::DefaultsLayer);
+            },
+        });
+    let export = if config_attr.export {
+        quote! {
+           pub use #private_mod_name::*;
+        }
+    } else {
+        quote! {}
+    };
+    let markdown_help = config_spec.markdown_help();
+    let config_template = config_spec.config_template();
+    let track_inputs = track_inputs(config_attr, &config_spec);
+    quote! {
+
+      pub mod #private_mod_name {
+            use rclap::__private::clap;
+            use rclap::Secret;
+            use rclap::StringSecret;
+            #track_inputs
+            #(#all_structs)*
+            #(#all_iter_map_impls)*
+            #(#default_impls)*
+
+        #global_static
+
+        impl #struct_name {
+            /// The clap command the parse runs, `--config` included, to extend or embed in a
+            /// larger CLI, or to feed completion and man page generators.
+            pub fn command() -> clap::Command {
+                let cmd = <Self as clap::CommandFactory>::command();
+                #with_config_file
+                #with_dump_config
+                #with_config_template
+                cmd
+            }
+
+            /// The reference of every option as Markdown tables, one per section.
+            pub fn markdown_help() -> &'static str {
+                #markdown_help
+            }
+
+            /// A `--config` file listing every field with its description and env var,
+            /// commented out and set to its default.
+            pub fn config_template() -> &'static str {
+                #config_template
+            }
+
+            pub fn parse() -> Self {
+                Self::parse_from(std::env::args_os())
+            }
+
+            #watch
+
+            #global
+
+            pub fn try_parse() -> Result<Self, clap::Error> {
+                Self::try_parse_from(std::env::args_os())
+            }
+
+            pub fn parse_from<I, T>(itr: I) -> Self
+            where
+                I: IntoIterator<Item = T>,
+                T: Into<std::ffi::OsString> + Clone,
+            {
+                Self::try_parse_from(itr).unwrap_or_else(|e| {
+                    #on_error
+                    e.exit()
+                })
+            }
+
+            /// Parses only the flags declared by `E` (e.g. `--config` or `--profile`) out of
+            /// the process arguments, ignoring the rest, before the full parse runs.
+            pub fn parse_early<E: clap::Parser>() -> E {
+                Self::parse_early_from(std::env::args_os())
+            }
+
+            pub fn parse_early_from<E, I, T>(itr: I) -> E
+            where
+                E: clap::Parser,
+                I: IntoIterator<Item = T>,
+                T: Into<std::ffi::OsString> + Clone,
+            {
+                rclap::cli::try_parse_early_from(itr).unwrap_or_else(|e| e.exit())
+            }
+
+            /// `parse_from` returning the error instead of exiting, e.g. to assert on it in
+            /// tests. Unlike `clap::Parser::try_parse_from`, it reads the config file and the
+            /// other layers. `--list-values`, `--dump-config` and `--print-config-template`
+            /// return their output as an error of kind `DisplayHelp`, as `--help` does.
+            pub fn try_parse_from<I, T>(itr: I) -> Result<Self, clap::Error>
+            where
+                I: IntoIterator<Item = T>,
+                T: Into<std::ffi::OsString> + Clone,
+            {
+                Self::try_parse_with_layers(itr, &[])
+            }
+
+            /// Parses `itr` with extra value sources, ranked below the environment and the
+            /// config file and above the spec defaults, highest first.
+            pub fn try_parse_with_layers<I, T>(
+                itr: I,
+                layers: &[&dyn rclap::layer::Layer],
+            ) -> Result<Self, clap::Error>
+            where
+                I: IntoIterator<Item = T>,
+                T: Into<std::ffi::OsString> + Clone,
+            {
+                Self::try_parse_with_provenance(itr, layers).map(|(config, _)| config)
+            }
+
+            /// `try_parse_with_layers` also returning where each field came from.
+            pub fn try_parse_with_provenance<I, T>(
+                itr: I,
+                layers: &[&dyn rclap::layer::Layer],
+            ) -> Result<(Self, rclap::provenance::Provenance), clap::Error>
+            where
+                I: IntoIterator<Item = T>,
+                T: Into<std::ffi::OsString> + Clone,
+            {
+                Self::rclap_try_parse_in(itr, layers, None)
+            }
+
+            /// Parses `itr` reading the env vars from `env` instead of the process
+            /// environment, which is left untouched and unread, so tests need no `set_var`.
+            pub fn try_parse_from_with_env<I, T>(
+                itr: I,
+                env: &std::collections::HashMap<String, String>,
+            ) -> Result<Self, clap::Error>
+            where
+                I: IntoIterator<Item = T>,
+                T: Into<std::ffi::OsString> + Clone,
+            {
+                Self::rclap_try_parse_in(itr, &[], Some(env)).map(|(config, _)| config)
+            }
+
+            fn rclap_try_parse_in<I, T>(
+                itr: I,
+                layers: &[&dyn rclap::layer::Layer],
+                env: Option<&std::collections::HashMap<String, String>>,
+            ) -> Result<(Self, rclap::provenance::Provenance), clap::Error>
+            where
+                I: IntoIterator<Item = T>,
+                T: Into<std::ffi::OsString> + Clone,
+            {
+                #load_dotenv
+                let mut cmd = rclap::cli::with_list_values(Self::command());
+                #load_config_file
+                #read_env_files
+                let env_map = env.map(rclap::layer::EnvMap);
+                let env_layer: &dyn rclap::layer::Layer = match &env_map {
+                    Some(env_map) => env_map,
+                    None => &rclap::layer::EnvLayer,
+                };
+                let mut stack: Vec<&dyn rclap::layer::Layer> = Vec::new();
+                #(#stack)*
+                let (layered, resolved) = if env.is_some() {
+                    rclap::layer::resolve_with_env_map(cmd, &stack)
+                } else {
+                    rclap::layer::resolve(cmd, &stack)
+                };
+                cmd = layered;
+                let matches = cmd
+                    .try_get_matches_from_mut(itr)
+                    .map_err(|e| rclap::cli::redact_error(&cmd, e, &[#(#sensitive_ids),*]))?;
+                if let Some(listing) = rclap::cli::list_values(&mut cmd, &matches)? {
+                    return Err(rclap::cli::display(listing));
+                }
+                #print_config_template
+                rclap::cli::check_locked(&mut cmd, &matches, &[#(#locked_ids),*])?;
+                rclap::cli::warn_deprecated(&cmd, &matches, &[#(#deprecated_fields),*]);
+                let provenance = rclap::provenance::Provenance::from_matches(
+                    &cmd,
+                    &matches,
+                    &[#(#provenance_fields),*],
+                    #spec_path,
+                );
+                let provenance = provenance.with_resolved(&resolved);
+                let mut config = <Self as clap::FromArgMatches>::from_arg_matches(&matches)
+                    .map_err(|e| e.format(&mut cmd))?;
+                config.rclap_fill_maps(&matches);
+                #dump_config
+                Ok((config, provenance))
+            }}
+        }
+
+       pub use #private_mod_name::#struct_name;
+       #export
+    }
+}
+
+/// The `#[command(...)]` of the top-level struct, from the `[app]` metadata.
+fn app_command(app: &AppSpec) -> TokenStream {
+    let settings = [
+        ("name", &app.name),
+        ("about", &app.about),
+        ("long_about", &app.long_about),
+        ("author", &app.author),
+        ("before_help", &app.before_help),
+        ("after_help", &app.after_help),
+        ("help_template", &app.help_template),
+    ];
+    let mut params: Vec<TokenStream> = settings
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let key = syn::Ident::new(key, proc_macro2::Span::call_site());
+            value.as_ref().map(|value| quote! { #key = #value })
+        })
+        .collect();
+    let version = match app.version.as_deref() {
+        Some("cargo") => Some(quote! { env!("CARGO_PKG_VERSION") }),
+        Some(version) => Some(quote! { #version }),
+        None => None,
+    };
+    match (version, &app.git_sha_env) {
+        (Some(version), Some(sha_env)) => params.push(quote! {
+            version = rclap::cli::version_with_sha(#version, option_env!(#sha_env))
+        }),
+        (Some(version), None) => params.push(quote! { version = #version }),
+        (None, _) => {}
+    }
+    if app.next_line_help {
+        params.push(quote! { next_line_help = true });
+    }
+    if app.disable_help_flag {
+        params.push(quote! { disable_help_flag = true });
+    }
+    if app.external_subcommands {
+        params.push(quote! { allow_external_subcommands = true });
+    }
+    if let Some(width) = app.term_width {
+        params.push(quote! { term_width = #width });
+    }
+    let styles = app.style.styles();
+    if !styles.is_empty() {
+        let styles = styles.into_iter().map(|(name, style)| {
+            let method = syn::Ident::new(name, proc_macro2::Span::call_site());
+            let style = style_tokens(style);
+            quote! { .#method(#style) }
+        });
+        params.push(quote! { styles = clap::builder::Styles::styled() #(#styles)* });
+    }
+    if params.is_empty() {
+        quote! {}
+    } else {
+        quote! { #[command(#(#params),*)] }
+    }
+}
+
+/// An `[app.style]` entry, effects (`bold`, `dimmed`, `italic`, `underline`) and a color, as
+/// a clap style.
+fn style_tokens(style: &str) -> TokenStream {
+    let mut tokens = quote! { clap::builder::styling::Style::new() };
+    for word in style.split_whitespace() {
+        tokens.extend(match word {
+            "bold" => quote! { .bold() },
+            "dimmed" => quote! { .dimmed() },
+            "italic" => quote! { .italic() },
+            "underline" => quote! { .underline() },
+            color => {
+                let color = color_tokens(color).expect("style checked when the spec was read");
+                quote! { .fg_color(Some(#color)) }
+            }
+        });
+    }
+    tokens
+}
+
+/// An ANSI color name (`green`, `bright-blue`) or a `#rrggbb` color.
+fn color_tokens(color: &str) -> Option<TokenStream> {
+    if let Some(hex) = color.strip_prefix('#') {
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        if hex.len() != 6 {
+            return None;
+        }
+        let (r, g, b) = (channel(0)?, channel(2)?, channel(4)?);
+        return Some(quote! {
+            clap::builder::styling::Color::Rgb(clap::builder::styling::RgbColor(#r, #g, #b))
+        });
+    }
+    let (bright, base) = match color.strip_prefix("bright-") {
+        Some(base) => ("Bright", base),
+        None => ("", color),
+    };
+    if !StyleSpec::COLORS.contains(&base) {
+        return None;
+    }
+    let variant = syn::Ident::new(
+        &format!("{bright}{}", to_pascal_case(base)),
+        proc_macro2::Span::call_site(),
+    );
+    Some(quote! {
+        clap::builder::styling::Color::Ansi(clap::builder::styling::AnsiColor::#variant)
+    })
+}
+
+/// `in_optional_section` relaxes the implicit `required` of the fields: clap only skips an
+/// absent `Option` flatten once none of its args are required, and a missing field of a given
+/// section is still reported when the struct is built from the matches.
+///
+/// clap adds the map args after every field, under the heading of the last nested section:
+/// `map_heading` sets theirs back.
+fn generate_single_struct(
+    struct_ident: &proc_macro2::Ident,
+    fields: &[Spec],
+    user_fields: &[syn::Field],
+    command: TokenStream,
+    map_heading: &TokenStream,
+    extra_derives: Vec<syn::Path>,
+    in_optional_section: bool,
+) -> TokenStream {
+    // clap derive only collects repeated occurrences into `Vec`s, so map fields are skipped by
+    // the derive, added as command-level args and filled from the matches after parsing.
+    let mut map_args = vec![];
+    let mut map_fills = vec![];
+    let mut section_checks = vec![];
+    let schema = derives_rclap(&extra_derives, SCHEMARS_JSON_SCHEMA);
+    let field_definitions: Vec<TokenStream> = fields
+        .iter()
+        .map(|field| {
+            let field_name = field_ident(field);
+            let cfg = cfg_attr(field);
+            let (fills, checks) = (map_fills.len(), section_checks.len());
+
+            let mut attributes = vec![];
+
+            let mut arg_params = vec![];
+            if let Some(doc) = &field.doc {
+                attributes.push(quote! { #[doc = #doc] });
+                arg_params.push(quote! { help = #doc });
+            }
+            if let Some(long_doc) = &field.long_doc {
+                attributes.push(quote! { #[doc = ""] #[doc = #long_doc] });
+                arg_params.push(quote! { long_help = #long_doc });
+            }
+            if !field.aliases.is_empty() {
+                let aliases = &field.aliases;
+                arg_params.push(quote! { visible_aliases = [#(#aliases),*] });
+            }
+            if field.deprecated.is_some() {
+                arg_params.push(quote! { hide = true });
+            }
+            if field.global {
+                arg_params.push(quote! { global = true });
+            }
+            if let Some(hint) = value_hint(field) {
+                arg_params.push(quote! { value_hint = #hint });
+            }
+            for (method, value) in arg_conditions(field) {
+                arg_params.push(quote! { #method = #value });
+            }
+            if let Some(num_args) = num_args(field) {
+                arg_params.push(quote! { num_args = #num_args });
+            }
+            if let Some(missing) = &field.default_missing_value {
+                arg_params.push(quote! { default_missing_value = #missing });
+            }
+
+            let id = &field.id;
+            let is_optional = field.optional;
+            arg_params.push(quote! { id = #id });
+            if in_optional_section {
+                arg_params.push(quote! { required = false });
+            }
+            if field.sensitive {
+                arg_params.push(quote! { hide_env_values = true });
+                arg_params.push(quote! { hide_default_value = true });
+            }
+            match &field.variant {
+                GenericSpec::VecSpec(f) => {
+                    if let Some(default) = &f.default {
+                        let default = default.as_array().unwrap_or_else(|| {
+                            panic!("Default of array field '{id}' must be an array")
+                        });
+                        let inner_type = field
+                            .field_type
+                            .strip_prefix("Vec<")
+                            .and_then(|t| t.strip_suffix('>'))
+                            .unwrap_or(&field.field_type);
+                        arg_params.push(vec_default(
+                            id,
+                            inner_type,
+                            default,
+                            is_optional || f.value_parser.is_some(),
+                        ));
+                    }
+                    if let Some(pattern) = &f.pattern {
+                        arg_params.push(pattern_value_parser(field, pattern));
+                    } else if let Some(parser) = &f.value_parser {
+                        let parser: TokenStream = parser.parse().expect("Invalid value parser");
+                        arg_params.push(quote! { value_parser = #parser });
+                    }
+                    if field.field_type == format!("Vec<{PATH_BUF}>") && field.hint.is_none() {
+                        arg_params.push(quote! { value_hint = clap::ValueHint::AnyPath });
+                    }
+                    // An optional `--flag` alone yields `Some(vec![])`, distinct from not given.
+                    let min_items = f.min_items.unwrap_or(if is_optional { 0 } else { 1 });
+                    match f.max_items {
+                        _ if f.trailing => {
+                            let min_items = f.min_items.unwrap_or(0);
+                            arg_params.push(quote! { num_args = #min_items.. });
+                        }
+                        Some(max) => arg_params.push(quote! { num_args = #min_items..=#max }),
+                        None if is_optional || f.min_items.is_some() => {
+                            arg_params.push(quote! { num_args = #min_items.. })
+                        }
+                        None => {}
+                    }
+                    if let Some(env) = &f.env {
+                        arg_params.push(quote! { env = #env });
+                    }
+                    if f.split_paths {
+                        arg_params.push(quote! { value_delimiter = rclap::cli::PATH_LIST_SEPARATOR });
+                    } else if let Some(delimiter) = f.delimiter {
+                        arg_params.push(quote! { value_delimiter = #delimiter });
+                    }
+                    if f.raw {
+                        arg_params.push(quote! { last = true, allow_hyphen_values = true });
+                    } else if f.trailing {
+                        arg_params.push(quote! { trailing_var_arg = true, allow_hyphen_values = true });
+                    } else if let Some(l) = &f.long_arg {
+                        arg_params.push(quote! { long = #l });
+                    } else {
+                        arg_params.push(quote! { long = #id })
+                    }
+                    if let Some(s) = &f.short_arg {
+                        arg_params.push(quote! { short = #s });
+                    }
+
+                    attributes.push(quote! { #[arg(#(#arg_params),*)] });
+                }
+                GenericSpec::FieldSpec(f) => {
+                    if let Some(default) = &f.default {
+                        if is_optional
+                            || field.secret
+                            || f.value_parser.is_some()
+                            || !has_literal_default(&field.field_type)
+                        {
+                            arg_params.push(quote! { default_value = #default });
+                        } else if field.field_type == "char" {
+                            let c = default.chars().next().unwrap();
+                            arg_params.push(quote! { default_value_t = #c });
+                        } else {
+                            let default_lit: TokenStream =
+                                default.parse().expect("Invalid default value");
+                            arg_params.push(quote! { default_value_t = #default_lit });
+                        }
+                    }
+                    if !f.values.is_empty() {
+                        let values = &f.values;
+                        arg_params.push(quote! { value_parser = [#(#values),*] });
+                    } else if let Some(pattern) = &f.pattern {
+                        arg_params.push(pattern_value_parser(field, pattern));
+                    } else if let Some(parser) = &f.value_parser {
+                        let parser: TokenStream = parser.parse().expect("Invalid value parser");
+                        arg_params.push(quote! { value_parser = #parser });
+                    } else if f.min.is_some() || f.max.is_some() {
+                        arg_params.push(range_value_parser(field, f.min, f.max));
+                    }
+                    if field.field_type == TIMEZONE {
+                        arg_params.push(quote! { hide_possible_values = true });
+                    }
+                    if let Some(env) = &f.env {
+                        arg_params.push(quote! { env = #env });
+                    }
+                    if let Some(l) = &f.long_arg {
+                        arg_params.push(quote! { long = #l });
+                    } else {
+                        arg_params.push(quote! { long = #id })
+                    }
+                    if let Some(s) = &f.short_arg {
+                        arg_params.push(quote! { short = #s });
+                    }
+
+                    attributes.push(quote! { #[arg(#(#arg_params),*)] });
+                }
+                GenericSpec::EnumSpec(e) => {
+                    arg_params.push(quote! { value_enum });
+                    if e.ignore_case {
+                        arg_params.push(quote! { ignore_case = true });
+                    }
+                    if let Some(default) = &e.default {
+                        let enum_path: syn::Path =
+                            syn::parse_str(&field.field_type).expect("Invalid enum path");
+                        let default_variant =
+                            syn::Ident::new(default, proc_macro2::Span::call_site());
+                        arg_params.push(quote! { default_value_t = #enum_path::#default_variant });
+                    }
+                    if let Some(env) = &e.env {
+                        arg_params.push(quote! { env = #env });
+                    }
+                    if let Some(l) = &e.long_arg {
+                        arg_params.push(quote! { long = #l });
+                    } else {
+                        arg_params.push(quote! { long = #id })
+                    }
+                    if let Some(s) = &e.short_arg {
+                        arg_params.push(quote! { short = #s });
+                    }
+
+                    attributes.push(quote! { #[arg(#(#arg_params),*)] });
+                }
+                GenericSpec::MapSpec(m) => {
+                    let long = m.long_arg.as_deref().unwrap_or(id);
+                    let mut arg = quote! {
+                        clap::Arg::new(#id)
+                            .long(#long)
+                            .value_name("KEY=VALUE")
+                            .value_parser(rclap::parsers::key_value)
+                            .action(clap::ArgAction::Append)
+                    };
+                    if let Some(delimiter) = m.delimiter {
+                        arg.extend(quote! { .value_delimiter(#delimiter) });
+                    }
+                    if let Some(doc) = &field.doc {
+                        arg.extend(quote! { .help(#doc) });
+                    }
+                    if let Some(long_doc) = &field.long_doc {
+                        arg.extend(quote! { .long_help(#long_doc) });
+                    }
+                    if !field.aliases.is_empty() {
+                        let aliases = &field.aliases;
+                        arg.extend(quote! { .visible_aliases([#(#aliases),*]) });
+                    }
+                    if field.deprecated.is_some() {
+                        arg.extend(quote! { .hide(true) });
+                    }
+                    if field.global {
+                        arg.extend(quote! { .global(true) });
+                    }
+                    if let Some(hint) = value_hint(field) {
+                        arg.extend(quote! { .value_hint(#hint) });
+                    }
+                    for (method, value) in arg_conditions(field) {
+                        arg.extend(quote! { .#method(#value) });
+                    }
+                    if let Some(s) = &m.short_arg {
+                        arg.extend(quote! { .short(#s) });
+                    }
+                    if let Some(env) = &m.env {
+                        arg.extend(quote! { .env(#env) });
+                    }
+                    if field.sensitive {
+                        arg.extend(quote! { .hide_env_values(true).hide_default_value(true) });
+                    }
+                    if !m.default.is_empty() {
+                        let pairs = m.default.iter().map(|(k, v)| format!("{k}={v}"));
+                        arg.extend(quote! { .default_values([#(#pairs),*]) });
+                    }
+                    arg.extend(map_heading.clone());
+                    map_args.push(command_arg(field, &arg));
+                    map_fills.push(quote! {
+                        self.#field_name = matches
+                            .get_many::<(String, String)>(#id)
+                            .map(|pairs| pairs.cloned().collect())
+                            .unwrap_or_default();
+                    });
+                    attributes.push(quote! { #[arg(skip)] });
+                }
+                GenericSpec::SubtypeMapSpec(m) => {
+                    let long = m.long_arg.as_deref().unwrap_or(id);
+                    let element: TokenStream = m.element.parse().expect("Invalid map_of type");
+                    let mut arg = quote! {
+                        clap::Arg::new(#id)
+                            .long(#long)
+                            .value_name("NAME:KEY=VALUE,...")
+                            .value_parser(rclap::cli::named_element::<#element>)
+                            .action(clap::ArgAction::Append)
+                            .value_delimiter(';')
+                    };
+                    if let Some(doc) = &field.doc {
+                        arg.extend(quote! { .help(#doc) });
+                    }
+                    if let Some(long_doc) = &field.long_doc {
+                        arg.extend(quote! { .long_help(#long_doc) });
+                    }
+                    if !field.aliases.is_empty() {
+                        let aliases = &field.aliases;
+                        arg.extend(quote! { .visible_aliases([#(#aliases),*]) });
+                    }
+                    if field.deprecated.is_some() {
+                        arg.extend(quote! { .hide(true) });
+                    }
+                    if field.global {
+                        arg.extend(quote! { .global(true) });
+                    }
+                    if let Some(hint) = value_hint(field) {
+                        arg.extend(quote! { .value_hint(#hint) });
+                    }
+                    for (method, value) in arg_conditions(field) {
+                        arg.extend(quote! { .#method(#value) });
+                    }
+                    if let Some(s) = &m.short_arg {
+                        arg.extend(quote! { .short(#s) });
+                    }
+                    if let Some(env) = &m.env {
+                        arg.extend(quote! { .env(#env) });
+                    }
+                    if field.sensitive {
+                        arg.extend(quote! { .hide_env_values(true).hide_default_value(true) });
+                    }
+                    arg.extend(map_heading.clone());
+                    map_args.push(command_arg(field, &arg));
+                    map_fills.push(quote! {
+                        self.#field_name = matches
+                            .get_many::<(String, #element)>(#id)
+                            .map(|entries| entries.cloned().collect())
+                            .unwrap_or_default();
+                    });
+                    attributes.push(quote! { #[arg(skip)] });
+                }
+                GenericSpec::SubtypeVecSpec(v) => {
+                    arg_params.push(quote! { value_name = "KEY=VALUE,..." });
+                    arg_params.push(quote! { value_delimiter = ';' });
+                    if let Some(env) = &v.env {
+                        arg_params.push(quote! { env = #env });
+                    }
+                    if let Some(l) = &v.long_arg {
+                        arg_params.push(quote! { long = #l });
+                    } else {
+                        arg_params.push(quote! { long = #id })
+                    }
+                    if let Some(s) = &v.short_arg {
+                        arg_params.push(quote! { short = #s });
+                    }
+                    attributes.push(quote! { #[arg(#(#arg_params),*)] });
+                }
+                GenericSpec::SubtypeSpec(_) if field.reuses.is_some() => {
+                    // The reused type fills maps with the ids of the section that declared it:
+                    // rebuild it from this section's own args instead.
+                    let args: TokenStream =
+                        field.field_type.parse().expect("Invalid section type");
+                    let rebuild = quote! {
+                        let mut args = <#args as clap::FromArgMatches>::from_arg_matches(matches)
+                            .expect("section already parsed");
+                        args.rclap_fill_maps(matches);
+                        *section = rclap::section::Reused::new(args.into());
+                    };
+                    map_fills.push(if is_optional {
+                        quote! {
+                            if let Some(section) = &mut self.#field_name {
+                                #rebuild
+                            }
+                        }
+                    } else {
+                        quote! {
+                            let section = &mut self.#field_name;
+                            #rebuild
+                        }
+                    });
+                    attributes.push(section_flatten(field));
+                }
+                GenericSpec::SubtypeSpec(_) => {
+                    map_fills.push(if is_optional {
+                        quote! {
+                            if let Some(section) = &mut self.#field_name {
+                                section.rclap_fill_maps(matches);
+                            }
+                        }
+                    } else {
+                        quote! { self.#field_name.rclap_fill_maps(matches); }
+                    });
+                    attributes.push(section_flatten(field));
+                }
+                GenericSpec::ExternalSpec(e) if e.section => {
+                    let section: TokenStream =
+                        field.field_type.parse().expect("Invalid external section path");
+                    let section_name = &field.field_type;
+                    let requires = e.requires.iter().map(|(required, ty)| match ty {
+                        None => {
+                            let message = format!(
+                                "section {section_name} has no field '{required}' required by the spec of `{id}`"
+                            );
+                            quote! {
+                                assert!(
+                                    rclap::section::has_field(
+                                        <#section as rclap::section::ConfigSection>::FIELDS,
+                                        #required,
+                                    ),
+                                    #message
+                                );
+                            }
+                        }
+                        Some(ty) => {
+                            let message = format!(
+                                "section {section_name} has no field '{required}' of type `{ty}` required by the spec of `{id}`"
+                            );
+                            quote! {
+                                assert!(
+                                    rclap::section::has_field_of_type(
+                                        <#section as rclap::section::ConfigSection>::FIELDS,
+                                        #required,
+                                        #ty,
+                                    ),
+                                    #message
+                                );
+                            }
+                        }
+                    });
+                    section_checks.push(quote! {
+                        const _: () = {
+                            let _ = <#section as rclap::section::ConfigSection>::FIELDS;
+                            #(#requires)*
+                        };
+                    });
+                    map_fills.push(quote! {
+                        rclap::section::ConfigSection::rclap_fill_maps(&mut self.#field_name, matches);
+                    });
+                    attributes.push(section_flatten(field));
+                }
+                GenericSpec::ExternalSpec(_) => {
+                    attributes.push(quote! { #[command(flatten)] });
+                }
+            }
+
+            for fill in &mut map_fills[fills..] {
+                *fill = quote! { #cfg { #fill } };
+            }
+            for check in &mut section_checks[checks..] {
+                *check = quote! { #cfg #check };
+            }
+
+            if schema && let Some(with) = schemars_with(field, is_optional) {
+                attributes.push(quote! { #[schemars(with = #with)] });
+            }
+
+            let field_type = struct_field_type(field);
+
+            if is_optional {
+                quote! {
+                    #cfg
+                    #(#attributes)*
+                    pub #field_name: Option<#field_type>,
+                }
+            } else {
+                quote! {
+                    #cfg
+                    #(#attributes)*
+                    pub #field_name: #field_type,
+                }
+            }
+        })
+        .collect();
+    // Sections go last: the heading a flattened section sets applies to every arg added after it.
+    let (section_definitions, field_definitions): (Vec<_>, Vec<_>) = fields
+        .iter()
+        .zip(field_definitions)
+        .partition(|(field, _)| field.heading.is_some());
+    let section_definitions = section_definitions.into_iter().map(|(_, tokens)| tokens);
+    let field_definitions = field_definitions.into_iter().map(|(_, tokens)| tokens);
+
+    // `secrecy::SecretString` deliberately has no `PartialEq`.
+    let derives = if holds_secret_string(fields) {
+        quote! { #[derive(Debug, Clone, clap::Parser)] }
+    } else {
+        quote! { #[derive(Debug, Clone, PartialEq, clap::Parser)] }
+    };
+    let serialize = derives_rclap(&extra_derives, SERDE_SERIALIZE);
+    let deserialize = derives_rclap(&extra_derives, SERDE_DESERIALIZE);
+    let extra_derives = without_derives(extra_derives, &["Debug", "Clone", "PartialEq", "Parser"]);
+    let extra_derives = if extra_derives.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            #[derive(#(#extra_derives),*)]
+        }
+    };
+    let serde_attrs = if serialize || deserialize {
+        quote! { #[serde(crate = "rclap::serde")] }
+    } else {
+        quote! {}
+    };
+    let schemars_attrs = if schema {
+        quote! { #[schemars(crate = "rclap::schemars")] }
+    } else {
+        quote! {}
+    };
+    let serialize_methods = if serialize {
+        quote! {
+            impl #struct_ident {
+                /// The resolved configuration as TOML, secrets masked.
+                pub fn to_toml_string(&self) -> Result<String, rclap::serialize::Error> {
+                    rclap::serialize::to_toml_string(self)
+                }
+
+                /// The resolved configuration as JSON, secrets masked.
+                pub fn to_json_string(&self) -> Result<String, rclap::serialize::JsonError> {
+                    rclap::serialize::to_json_string(self)
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let deserialize_methods = if deserialize {
+        quote! {
+            impl #struct_ident {
+                /// Reads back a configuration saved as TOML, sections as tables or dotted keys.
+                pub fn from_toml_str(s: &str) -> Result<Self, rclap::serialize::DeError> {
+                    rclap::serialize::from_toml_str(s)
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let builder = generate_builder(struct_ident, fields, user_fields);
+    quote! {
+        #derives
+        #extra_derives
+        #serde_attrs
+        #schemars_attrs
+        #command
+        #(#map_args)*
+        pub struct #struct_ident {
+            #(#field_definitions)*
+            #(#user_fields,)*
+            #(#section_definitions)*
+        }
+
+        impl #struct_ident {
+            #[allow(unused_variables)]
+            fn rclap_fill_maps(&mut self, matches: &clap::ArgMatches) {
+                #(#map_fills)*
+            }
+        }
+
+        #(#section_checks)*
+
+        #serialize_methods
+
+        #deserialize_methods
+
+        #builder
+    }
+}
+
+/// Whether `serde = true`, `deserialize = true` or `schema = true` added rclap's `derive`.
+fn derives_rclap(extra_derives: &[syn::Path], derive: &str) -> bool {
+    extra_derives.iter().any(|path| derive_name(path) == derive)
+}
+
+/// The extra derives not already in `builtin`, compared by trait name.
+fn without_derives(extra_derives: Vec<syn::Path>, builtin: &[&str]) -> Vec<syn::Path> {
+    extra_derives
+        .into_iter()
+        .filter(|path| {
+            path.segments
+                .last()
+                .is_none_or(|segment| !builtin.contains(&segment.ident.to_string().as_str()))
+        })
+        .collect()
+}
+
+/// Whether the derives go through rclap's re-export of serde.
+fn uses_rclap_serde(extra_derives: &[syn::Path]) -> bool {
+    derives_rclap(extra_derives, SERDE_SERIALIZE) || derives_rclap(extra_derives, SERDE_DESERIALIZE)
+}
+
+/// The Rust name of a field: its spec name, raw (`r#type`) when that is a keyword.
+fn field_ident(field: &Spec) -> syn::Ident {
+    let name = field.name.as_str();
+    let span = proc_macro2::Span::call_site();
+    match name {
+        // `gen` is reserved from the 2024 edition on, which syn still parses as an identifier.
+        "gen" => syn::Ident::new_raw(name, span),
+        _ if syn::parse_str::<syn::Ident>(name).is_err() => syn::Ident::new_raw(name, span),
+        _ => syn::Ident::new(name, span),
+    }
+}
+
+/// `#[cfg(...)]` of a field declared with `cfg`, nothing otherwise.
+fn cfg_attr(field: &Spec) -> TokenStream {
+    match cfg_predicate(field) {
+        Some(predicate) => quote! { #[cfg(#predicate)] },
+        None => quote! {},
+    }
+}
+
+fn cfg_predicate(field: &Spec) -> Option<TokenStream> {
+    field
+        .cfg
+        .as_ref()
+        .map(|cfg| cfg.parse().expect("cfg checked when the spec was read"))
+}
+
+/// The `#[command(arg = ...)]` of a map field, applied only when its `cfg` holds.
+fn command_arg(field: &Spec, arg: &TokenStream) -> TokenStream {
+    match cfg_predicate(field) {
+        Some(predicate) => quote! { #[cfg_attr(#predicate, command(arg = #arg))] },
+        None => quote! { #[command(arg = #arg)] },
+    }
+}
+
+/// `#[command(flatten)]` of a section, grouping its args under its heading in `--help`.
+fn section_flatten(field: &Spec) -> TokenStream {
+    match &field.heading {
+        Some(heading) => quote! { #[command(flatten, next_help_heading = #heading)] },
+        None => quote! { #[command(flatten)] },
+    }
+}
+
+/// The `with` type describing a field of a type parsed from a string with no `JsonSchema` of
+/// its own, e.g. `Option<String>` for an optional timezone. A `SecretString` is described as
+/// rclap's own secret.
+fn schemars_with(field: &Spec, is_optional: bool) -> Option<String> {
+    if field.secret || field.reuses.is_some() {
+        return None;
+    }
+    let with = [TIMEZONE, LANGUAGE_ID, IP_NET, URL, UUID, DATETIME]
+        .iter()
+        .fold(field.field_type.clone(), |ty, parsed| {
+            ty.replace(parsed, "String")
+        })
+        .replace(SECRET_STRING, "rclap::StringSecret");
+    if with == field.field_type {
+        None
+    } else if is_optional {
+        Some(format!("Option<{with}>"))
+    } else {
+        Some(with)
+    }
+}
+
+/// The Rust type of a field, without the `Option` of optional fields.
+fn struct_field_type(field: &Spec) -> TokenStream {
+    let field_type: TokenStream = field.field_type.parse().expect("Invalid type in config");
+    if let Some(reused) = &field.reuses {
+        let reused: TokenStream = reused.parse().expect("Invalid section type");
+        quote! { rclap::section::Reused<#reused, #field_type> }
+    } else if field.secret && field.field_type == "String" {
+        quote! { StringSecret }
+    } else if field.secret {
+        quote! { Secret<#field_type> }
+    } else {
+        field_type
+    }
+}
+
+/// `{Struct}Builder`: a setter per field and a `build()` taking the spec default of every
+/// field left unset, nested sections through their own builders.
+fn generate_builder(
+    struct_ident: &proc_macro2::Ident,
+    fields: &[Spec],
+    user_fields: &[syn::Field],
+) -> TokenStream {
+    let builder_ident = quote::format_ident!("{}Builder", struct_ident);
+    let mut slots = vec![];
+    let mut setters = vec![];
+    let mut values = vec![];
+    for field in fields {
+        let name = field_ident(field);
+        let id = &field.id;
+        let ty = struct_field_type(field);
+        let element = |ty: &str| -> TokenStream {
+            ty.trim_start_matches("Vec<")
+                .trim_end_matches('>')
+                .parse()
+                .expect("Invalid type in config")
+        };
+        let value = match &field.variant {
+            GenericSpec::SubtypeSpec(_) if field.optional => quote! { self.#name },
+            GenericSpec::SubtypeSpec(_) if field.reuses.is_some() => {
+                let sub_builder = quote::format_ident!("{}Builder", field.field_type);
+                quote! {
+                    match self.#name {
+                        Some(value) => value,
+                        None => rclap::section::Reused::new(#sub_builder::default().build()?.into()),
+                    }
+                }
+            }
+            GenericSpec::SubtypeSpec(_) => {
+                let sub_builder = quote::format_ident!("{}Builder", field.field_type);
+                quote! {
+                    match self.#name {
+                        Some(value) => value,
+                        None => #sub_builder::default().build()?,
+                    }
+                }
+            }
+            GenericSpec::ExternalSpec(_) => quote! {
+                match self.#name {
+                    Some(value) => value,
+                    None => rclap::cli::from_defaults::<#ty>()?.0,
+                }
+            },
+            GenericSpec::MapSpec(_) => quote! {
+                match self.#name {
+                    Some(value) => value,
+                    None => rclap::cli::default_many::<(String, String)>(&cmd, #id)?
+                        .unwrap_or_default()
+                        .into_iter()
+                        .collect(),
+                }
+            },
+            GenericSpec::SubtypeMapSpec(m) => {
+                let element: TokenStream = m.element.parse().expect("Invalid map_of type");
+                quote! {
+                    match self.#name {
+                        Some(value) => value,
+                        None => rclap::cli::default_many::<(String, #element)>(&cmd, #id)?
+                            .unwrap_or_default()
+                            .into_iter()
+                            .collect(),
+                    }
+                }
+            }
+            GenericSpec::VecSpec(_) | GenericSpec::SubtypeVecSpec(_) => {
+                let element = element(&field.field_type);
+                let default = quote! { rclap::cli::default_many::<#element>(&cmd, #id)? };
+                if field.optional {
+                    quote! { match self.#name { Some(value) => Some(value), None => #default } }
+                } else {
+                    quote! {
+                        match self.#name {
+                            Some(value) => value,
+                            None => #default.unwrap_or_default(),
+                        }
+                    }
+                }
+            }
+            _ if field.optional => quote! {
+                match self.#name {
+                    Some(value) => Some(value),
+                    None => rclap::cli::default_one::<#ty>(&cmd, #id)?,
+                }
+            },
+            _ => quote! {
+                match self.#name {
+                    Some(value) => value,
+                    None => rclap::cli::required_default::<#ty>(&cmd, #id)?,
+                }
+            },
+        };
+        let cfg = cfg_attr(field);
+        slots.push(quote! { #cfg #name: Option<#ty>, });
+        // `impl Into` lets strings and paths take `&str`; other types stay concrete so integer
+        // literals infer.
+        let param = if matches!(field.field_type.as_str(), "String" | PATH_BUF) && !field.secret {
+            quote! { impl Into<#ty> }
+        } else {
+            quote! { #ty }
+        };
+        setters.push(quote! {
+            #cfg
+            pub fn #name(mut self, value: #param) -> Self {
+                self.#name = Some(value.into());
+                self
+            }
+        });
+        values.push(quote! { #cfg #name: #value, });
+    }
+    // Hand-written fields have no spec default: unset, they take their `Default`.
+    for field in user_fields {
+        let name = &field.ident;
+        let ty = &field.ty;
+        slots.push(quote! { #name: Option<#ty>, });
+        setters.push(quote! {
+            pub fn #name(mut self, value: #ty) -> Self {
+                self.#name = Some(value);
+                self
+            }
+        });
+        values.push(quote! { #name: self.#name.unwrap_or_default(), });
+    }
+    quote! {
+        /// Builds the configuration in code; unset fields take their spec default.
+        #[derive(Debug, Clone, Default)]
+        pub struct #builder_ident {
+            #(#slots)*
+        }
+
+        impl #builder_ident {
+            #(#setters)*
+
+            /// Fails when a field without a default was not set.
+            pub fn build(self) -> Result<#struct_ident, clap::Error> {
+                #[allow(unused_variables)]
+                let cmd = <#struct_ident as clap::CommandFactory>::command();
+                Ok(#struct_ident {
+                    #(#values)*
+                })
+            }
+        }
+
+        impl #struct_ident {
+            pub fn builder() -> #builder_ident {
+                #builder_ident::default()
+            }
+        }
+    }
+}
+
+/// Emits the default of an array field. Literal types (`String`, `char`, integers, floats,
+/// `bool`) become typed `default_values_t`, paths `default_values_os_t`; every other type, or
+/// any field parsed by a custom parser, takes its defaults as strings parsed by clap.
+fn vec_default(
+    id: &str,
+    inner_type: &str,
+    default: &[toml::Value],
+    as_strings: bool,
+) -> TokenStream {
+    let literals: Option<Vec<TokenStream>> = (!as_strings)
+        .then(|| {
+            default
+                .iter()
+                .map(|value| scalar_literal(inner_type, value))
+                .collect()
+        })
+        .flatten();
+    if let Some(literals) = literals {
+        return quote! { default_values_t = vec![#(#literals),*] };
+    }
+    if inner_type == PATH_BUF && !as_strings {
+        let paths = default.iter().map(|v| {
+            let path = v
+                .as_str()
+                .unwrap_or_else(|| panic!("Path defaults of field '{id}' must be strings"));
+            quote! { std::path::PathBuf::from(#path) }
+        });
+        return quote! { default_values_os_t = vec![#(#paths),*] };
+    }
+    let strings = default.iter().map(|v| match v {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Array(_) | toml::Value::Table(_) => {
+            panic!("Defaults of field '{id}' must be scalars")
+        }
+        other => other.to_string(),
+    });
+    quote! { default_values = [#(#strings),*] }
+}
+
+/// A typed Rust literal for `value` when `ty` has one, e.g. `8080`, `'x'` or `"a".to_string()`.
+fn scalar_literal(ty: &str, value: &toml::Value) -> Option<TokenStream> {
+    match (ty, value) {
+        ("String", toml::Value::String(s)) => Some(quote! { #s.to_string() }),
+        ("char", toml::Value::String(s)) if s.chars().count() == 1 => {
+            let c = s.chars().next()?;
+            Some(quote! { #c })
+        }
+        ("bool", toml::Value::Boolean(b)) => Some(quote! { #b }),
+        ("f32" | "f64", toml::Value::Float(f)) => {
+            let lit = Literal::f64_unsuffixed(*f);
+            Some(quote! { #lit })
+        }
+        ("f32" | "f64", toml::Value::Integer(i)) => {
+            let lit = Literal::f64_unsuffixed(*i as f64);
+            Some(quote! { #lit })
+        }
+        (ty, toml::Value::Integer(i)) if INTEGER_TYPES.contains(&ty) => {
+            let lit = Literal::i64_unsuffixed(*i);
+            Some(quote! { #lit })
+        }
+        (ty, toml::Value::String(s)) if has_literal_default(ty) && ty != "char" => {
+            let lit: TokenStream = s.parse().ok()?;
+            syn::parse2::<syn::Lit>(lit.clone()).ok()?;
+            Some(lit)
+        }
+        _ => None,
+    }
+}
+
+fn pattern_value_parser(field: &Spec, pattern: &str) -> TokenStream {
+    let id = &field.id;
+    quote! {
+        value_parser = |s: &str| -> Result<String, String> {
+            static PATTERN: std::sync::LazyLock<rclap::regex::Regex> =
+                std::sync::LazyLock::new(|| rclap::regex::Regex::new(#pattern).unwrap());
+            rclap::parsers::matches_pattern(&PATTERN, #id, s)
+        }
+    }
+}
+
+fn range_value_parser(field: &Spec, min: Option<i128>, max: Option<i128>) -> TokenStream {
+    let ty = field.field_type.as_str();
+    let min = min.map(Literal::i128_unsuffixed);
+    let max = max.map(Literal::i128_unsuffixed);
+    let range = match (min, max) {
+        (Some(min), Some(max)) => quote! { #min..=#max },
+        (Some(min), None) => quote! { #min.. },
+        (None, Some(max)) => quote! { ..=#max },
+        (None, None) => quote! { .. },
+    };
+    let ty_tokens: TokenStream = ty.parse().expect("Invalid type in config");
+    match ty {
+        "usize" => quote! {
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(#range)
+        },
+        "isize" => quote! {
+            value_parser = clap::builder::RangedI64ValueParser::<isize>::new().range(#range)
+        },
+        _ => quote! { value_parser = clap::value_parser!(#ty_tokens).range(#range) },
+    }
+}
+
+/// Ids of the fields matching `select` (e.g. `locked = true`), nested sections included,
+/// each behind the `cfg` of its field and sections.
+fn field_ids(fields: &[Spec], select: &dyn Fn(&Spec) -> bool) -> Vec<TokenStream> {
+    fields
+        .iter()
+        .flat_map(|field| {
+            let cfg = cfg_attr(field);
+            let ids = match &field.variant {
+                GenericSpec::SubtypeSpec(sub) => field_ids(sub, select),
+                _ if select(field) => {
+                    let id = &field.id;
+                    vec![quote! { #id }]
+                }
+                _ => vec![],
+            };
+            ids.into_iter().map(move |id| quote! { #cfg #id })
+        })
+        .collect()
+}
+
+/// The `(id, env var)` pairs of the fields read from an `env_file`, sections included.
+fn env_files(fields: &[Spec]) -> Vec<TokenStream> {
+    fields
+        .iter()
+        .flat_map(|field| {
+            let cfg = cfg_attr(field);
+            let files = match (&field.variant, &field.env_file) {
+                (GenericSpec::SubtypeSpec(sub), _) => env_files(sub),
+                (_, Some(var)) => {
+                    let id = &field.id;
+                    vec![quote! { (#id, #var) }]
+                }
+                _ => vec![],
+            };
+            files.into_iter().map(move |file| quote! { #cfg #file })
+        })
+        .collect()
+}
+
+/// `impl Default` for the struct and its nested sections, built from the spec defaults.
+/// Panics when a required field has no default, so the spec cannot silently fail at runtime.
+fn generate_default_impls(struct_ident: &proc_macro2::Ident, fields: &[Spec]) -> Vec<TokenStream> {
+    let mut impls = vec![quote! {
+        impl Default for #struct_ident {
+            fn default() -> Self {
+                let (mut config, matches) = rclap::cli::from_defaults::<Self>()
+                    .unwrap_or_else(|e| panic!("{e}"));
+                config.rclap_fill_maps(&matches);
+                config
+            }
+        }
+    }];
+    for field in fields.iter().filter(|field| !field.optional) {
+        let has_default = match &field.variant {
+            GenericSpec::FieldSpec(f) => f.default.is_some() || field.field_type == "bool",
+            GenericSpec::EnumSpec(e) => e.default.is_some(),
+            GenericSpec::SubtypeSpec(sub) => {
+                let ident = syn::Ident::new(&field.field_type, proc_macro2::Span::call_site());
+                impls.extend(generate_default_impls(&ident, sub));
+                true
+            }
+            _ => true,
+        };
+        if !has_default {
+            panic!(
+                "`default = true` requires a default for field '{}', or mark it optional",
+                field.id
+            );
+        }
+    }
+    impls
+}
+
+fn holds_secret_string(fields: &[Spec]) -> bool {
+    fields.iter().any(|field| match &field.variant {
+        GenericSpec::SubtypeSpec(sub) => holds_secret_string(sub),
+        GenericSpec::SubtypeVecSpec(element) => holds_secret_string(&element.fields),
+        GenericSpec::SubtypeMapSpec(element) => holds_secret_string(&element.fields),
+        _ => field.field_type.contains(SECRET_STRING),
+    })
+}
+
+/// `(cfg, id, secret)` of every value field, nested sections flattened; `cfg` gathers the
+/// `#[cfg]` of the field and of its sections.
+fn provenance_fields(fields: &[Spec]) -> Vec<(TokenStream, String, bool)> {
+    fields
+        .iter()
+        .flat_map(|field| {
+            let cfg = cfg_attr(field);
+            let entries = match &field.variant {
+                GenericSpec::SubtypeSpec(sub) => provenance_fields(sub),
+                GenericSpec::ExternalSpec(_) => vec![],
+                _ => vec![(quote! {}, field.id.clone(), field.secret || field.sensitive)],
+            };
+            entries
+                .into_iter()
+                .map(move |(inner, id, secret)| (quote! { #cfg #inner }, id, secret))
+        })
+        .collect()
+}
+
+/// The `cfg`, id and Rust type of every leaf field of a section, the type written as in the
+/// generated struct and without spaces.
+fn section_fields(fields: &[Spec]) -> Vec<(TokenStream, String, String)> {
+    fields
+        .iter()
+        .flat_map(|field| {
+            let cfg = cfg_attr(field);
+            let entries = match &field.variant {
+                GenericSpec::SubtypeSpec(sub) => section_fields(sub),
+                GenericSpec::ExternalSpec(_) => vec![],
+                _ => {
+                    let ty = struct_field_type(field).to_string().replace(' ', "");
+                    let ty = if field.optional {
+                        format!("Option<{ty}>")
+                    } else {
+                        ty
+                    };
+                    vec![(quote! {}, field.id.clone(), ty)]
+                }
+            };
+            entries
+                .into_iter()
+                .map(move |(inner, id, ty)| (quote! { #cfg #inner }, id, ty))
+        })
+        .collect()
+}
+
+/// The clap settings of the `default_if`, `required_if`, `required_unless` and `overrides_with`
+/// of a field, as `(method, argument)` pairs.
+pub(crate) fn arg_conditions(field: &Spec) -> Vec<(syn::Ident, TokenStream)> {
+    let method = |name: &str| syn::Ident::new(name, proc_macro2::Span::call_site());
+    let mut conditions = vec![];
+    if !field.default_if.is_empty() {
+        let entries = field.default_if.iter().map(|c| {
+            let (id, equals, value) = (&c.field, &c.equals, &c.value);
+            quote! { (#id, #equals, Some(#value)) }
+        });
+        conditions.push((method("default_value_ifs"), quote! { [#(#entries),*] }));
+    }
+    if !field.required_if.is_empty() {
+        let entries = field.required_if.iter().map(|c| {
+            let (id, equals) = (&c.field, &c.equals);
+            quote! { (#id, #equals) }
+        });
+        conditions.push((method("required_if_eq_any"), quote! { [#(#entries),*] }));
+    }
+    if !field.required_unless.is_empty() {
+        let ids = &field.required_unless;
+        conditions.push((
+            method("required_unless_present_any"),
+            quote! { [#(#ids),*] },
+        ));
+    }
+    if !field.overrides_with.is_empty() {
+        let ids = &field.overrides_with;
+        conditions.push((method("overrides_with_all"), quote! { [#(#ids),*] }));
+    }
+    conditions
+}
+
+/// The `clap::ValueHint` of a field declaring `hint`, e.g. `file_path` as `ValueHint::FilePath`.
+pub(crate) fn value_hint(field: &Spec) -> Option<TokenStream> {
+    field.hint.as_ref().map(|hint| {
+        let variant: String = hint.split('_').map(to_pascal_case).collect();
+        let variant = syn::Ident::new(&variant, proc_macro2::Span::call_site());
+        quote! { clap::ValueHint::#variant }
+    })
+}
+
+/// The `num_args` range of a field, checked when the spec was read.
+pub(crate) fn num_args(field: &Spec) -> Option<TokenStream> {
+    field.num_args.as_ref().map(|range| {
+        range
+            .parse()
+            .expect("num_args checked when the spec was read")
+    })
+}
+
+/// `(id, note)` of the `deprecated` fields, nested sections included.
+fn deprecated_fields(fields: &[Spec]) -> Vec<TokenStream> {
+    fields
+        .iter()
+        .flat_map(|field| {
+            let cfg = cfg_attr(field);
+            let entries = match (&field.variant, &field.deprecated) {
+                (GenericSpec::SubtypeSpec(sub), _) => deprecated_fields(sub),
+                (_, Some(note)) => {
+                    let id = &field.id;
+                    vec![quote! { (#id, #note) }]
+                }
+                _ => vec![],
+            };
+            entries.into_iter().map(move |entry| quote! { #cfg #entry })
+        })
+        .collect()
+}
+
+fn collect_subtypes(
+    fields: &[Spec],
+    items: &mut Vec<TokenStream>,
+    extra_derives: Vec<syn::Path>,
+    iter_map_impls: &mut Vec<TokenStream>,
+    in_optional_section: bool,
+) {
+    for field in fields {
+        match &field.variant {
+            GenericSpec::SubtypeSpec(subtype_spec) => {
+                let struct_name = &field.field_type;
+                let struct_ident = syn::Ident::new(struct_name, proc_macro2::Span::call_site());
+                let optional = in_optional_section || field.optional;
+                let heading = &field.heading;
+                let subtype_struct = generate_single_struct(
+                    &struct_ident,
+                    subtype_spec,
+                    &[],
+                    quote! {},
+                    &quote! { .help_heading(#heading) },
+                    extra_derives.clone(),
+                    optional,
+                );
+                items.push(subtype_struct);
+                if let Some(reused) = &field.reuses {
+                    items.push(generate_reused_from(&struct_ident, reused, subtype_spec));
+                } else {
+                    let iter_map = generate_iter_map_impl(&struct_ident, subtype_spec);
+                    iter_map_impls.push(iter_map);
+                }
+                collect_subtypes(
+                    subtype_spec,
+                    items,
+                    extra_derives.clone(),
+                    iter_map_impls,
+                    optional,
+                );
+            }
+            GenericSpec::SubtypeVecSpec(element) => {
+                let struct_name = field
+                    .field_type
+                    .trim_start_matches("Vec<")
+                    .trim_end_matches('>');
+                let struct_ident = syn::Ident::new(struct_name, proc_macro2::Span::call_site());
+                items.push(generate_single_struct(
+                    &struct_ident,
+                    &element.fields,
+                    &[],
+                    quote! {},
+                    &quote! {},
+                    extra_derives.clone(),
+                    false,
+                ));
+                items.push(generate_element_impls(&struct_ident, &field.id));
+                iter_map_impls.push(generate_iter_map_impl(&struct_ident, &element.fields));
+                collect_subtypes(
+                    &element.fields,
+                    items,
+                    extra_derives.clone(),
+                    iter_map_impls,
+                    false,
+                );
+            }
+            GenericSpec::SubtypeMapSpec(element) => {
+                let struct_ident =
+                    syn::Ident::new(&element.element, proc_macro2::Span::call_site());
+                items.push(generate_single_struct(
+                    &struct_ident,
+                    &element.fields,
+                    &[],
+                    quote! {},
+                    &quote! {},
+                    extra_derives.clone(),
+                    false,
+                ));
+                items.push(generate_element_impls(&struct_ident, &field.id));
+                iter_map_impls.push(generate_iter_map_impl(&struct_ident, &element.fields));
+                collect_subtypes(
+                    &element.fields,
+                    items,
+                    extra_derives.clone(),
+                    iter_map_impls,
+                    false,
+                );
+            }
+            GenericSpec::EnumSpec(enum_spec) if enum_spec.variants.is_empty() => {}
+            GenericSpec::EnumSpec(enum_spec) => {
+                let enum_name = &field.field_type;
+
+                let enum_ident = syn::Ident::new(enum_name, proc_macro2::Span::call_site());
+                let enum_item = generate_enum(&enum_ident, enum_spec, extra_derives.clone());
+                items.push(enum_item);
+            }
+            _ => {}
+        }
+    }
+}
+/// Converts the args struct of a reused section into the type it reuses.
+fn generate_reused_from(
+    args_ident: &proc_macro2::Ident,
+    reused: &str,
+    fields: &[Spec],
+) -> TokenStream {
+    let reused: TokenStream = reused.parse().expect("Invalid section type");
+    let conversions = fields.iter().map(|field| {
+        let name = field_ident(field);
+        let cfg = cfg_attr(field);
+        match (&field.reuses, field.optional) {
+            (Some(_), true) => quote! { #cfg #name: args.#name.map(|s| s.into_inner().into()) },
+            (Some(_), false) => quote! { #cfg #name: args.#name.into_inner().into() },
+            (None, _) => quote! { #cfg #name: args.#name },
+        }
+    });
+    quote! {
+        impl From<#args_ident> for #reused {
+            fn from(args: #args_ident) -> Self {
+                #reused {
+                    #(#conversions),*
+                }
+            }
+        }
+    }
+}
+
+/// `FromStr`/`Display` of an array-of-tables element, as `key=value` pairs joined with `,`.
+fn generate_element_impls(struct_ident: &proc_macro2::Ident, prefix: &str) -> TokenStream {
+    quote! {
+        impl std::str::FromStr for #struct_ident {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                let (mut element, matches) = rclap::cli::parse_element::<Self>(#prefix, s)?;
+                element.rclap_fill_maps(&matches);
+                Ok(element)
+            }
+        }
+
+        impl std::fmt::Display for #struct_ident {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                let mut pairs: Vec<String> = self
+                    .iter_map()
+                    .into_iter()
+                    .map(|(k, v)| format!("{k}={v}"))
+                    .collect();
+                pairs.sort();
+                f.write_str(&pairs.join(","))
+            }
+        }
+    }
+}
+
+/// The `#[derive(...)]` of the extra derives of a generated enum.
+fn extra_derives_attr(extra_derives: &[syn::Path]) -> TokenStream {
+    if extra_derives.is_empty() {
+        quote! {}
+    } else {
+        let serde_attrs = if uses_rclap_serde(extra_derives) {
+            quote! { #[serde(crate = "rclap::serde")] }
+        } else {
+            quote! {}
+        };
+        let schemars_attrs = if derives_rclap(extra_derives, SCHEMARS_JSON_SCHEMA) {
+            quote! { #[schemars(crate = "rclap::schemars")] }
+        } else {
+            quote! {}
+        };
+        quote! {
+            #[derive(#(#extra_derives),*)]
+            #serde_attrs
+            #schemars_attrs
+        }
+    }
+}
+
+/// The subcommands of `external_subcommands = true`: any unknown one, with its arguments.
+fn generate_commands_enum(extra_derives: Vec<syn::Path>) -> TokenStream {
+    let extra_derives = without_derives(
+        extra_derives,
+        &["Debug", "Clone", "PartialEq", "Eq", "Subcommand"],
+    );
+    let extra_derives_attr = extra_derives_attr(&extra_derives);
+    // clap only recognizes the element type by its name.
+    quote! {
+        use std::ffi::OsString;
+
+        #[derive(Debug, Clone, PartialEq, Eq, clap::Subcommand)]
+        #extra_derives_attr
+        pub enum Commands {
+            #[command(external_subcommand)]
+            External(Vec<OsString>),
+        }
+    }
+}
+
+fn generate_enum(
+    enum_ident: &proc_macro2::Ident,
+    enum_spec: &EnumField,
+    extra_derives: Vec<syn::Path>,
+) -> TokenStream {
+    let variants: Vec<TokenStream> = enum_spec
+        .variants
+        .iter()
+        .map(|variant_name| {
+            let variant_ident = syn::Ident::new(variant_name, proc_macro2::Span::call_site());
+
+            quote! {
+                #variant_ident,
+            }
+        })
+        .collect();
+
+    let display_arms: Vec<TokenStream> = enum_spec
+        .variants
+        .iter()
+        .map(|variant_name| {
+            let variant_ident = syn::Ident::new(variant_name, proc_macro2::Span::call_site());
+            quote! {
+                #enum_ident::#variant_ident => write!(f, #variant_name),
+            }
+        })
+        .collect();
+
+    let derives = quote! {
+        #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+    };
+    let extra_derives = without_derives(
+        extra_derives,
+        &[
+            "Debug",
+            "Clone",
+            "PartialEq",
+            "Eq",
+            "PartialOrd",
+            "Ord",
+            "ValueEnum",
+        ],
+    );
+    let extra_derives_attr = extra_derives_attr(&extra_derives);
+    //TODO: make rename_all configurable
+    let enum_attributes = quote! {
+        #[clap(rename_all = "verbatim")]
+    };
+
+    quote! {
+           #derives
+           #extra_derives_attr
+           #enum_attributes
+           pub enum #enum_ident {
+               #(#variants)*
+           }
+        impl std::fmt::Display for #enum_ident {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                   match self {
+                       #(#display_arms)*
+                   }
+               }
+           }
+       }
+}
+fn generate_iter_map_impl(struct_ident: &proc_macro2::Ident, fields: &[Spec]) -> TokenStream {
+    let entries: Vec<TokenStream> = fields
+        .iter()
+        .map(|field| {
+            let field_name = field_ident(field);
+            let key = &field.name;
+            let cfg = cfg_attr(field);
+
+            let entry = match &field.variant {
+                // Flatten subtypes recursively
+                // Absent optional sections contribute no keys
+                GenericSpec::SubtypeSpec(_) if field.optional => {
+                    quote! {
+                        for (k, v) in self.#field_name.iter().flat_map(|s| s.iter_map()) {
+                            map.insert(format!("{}.{}", #key, k), v);
+                        }
+                    }
+                }
+                // Hand-written clap args have no `iter_map()`: their Debug form stands in
+                GenericSpec::ExternalSpec(e) if e.args => {
+                    quote! {
+                        map.insert(#key.to_string(), format!("{:?}", self.#field_name));
+                    }
+                }
+                GenericSpec::SubtypeSpec(_) | GenericSpec::ExternalSpec(_) => {
+                    quote! {
+                        for (k, v) in self.#field_name.iter_map() {
+                            map.insert(format!("{}.{}", #key, k), v);
+                        }
+                    }
+                }
+                // Map fields: sorted key=value pairs joined with comma
+                GenericSpec::MapSpec(_) => {
+                    quote! {
+                        let mut pairs: Vec<String> = self.#field_name
+                            .iter()
+                            .map(|(k, v)| format!("{k}={v}"))
+                            .collect();
+                        pairs.sort();
+                        map.insert(#key.to_string(), pairs.join(","));
+                    }
+                }
+                // map_of fields: sorted `name:key=value,...` entries joined with `;`
+                GenericSpec::SubtypeMapSpec(_) => {
+                    quote! {
+                        let mut entries: Vec<String> = self.#field_name
+                            .iter()
+                            .map(|(name, v)| format!("{name}:{v}"))
+                            .collect();
+                        entries.sort();
+                        map.insert(#key.to_string(), entries.join(";"));
+                    }
+                }
+                // Array-of-tables fields: elements as `key=value,...`, joined with `;`
+                GenericSpec::SubtypeVecSpec(_) => {
+                    quote! {
+                        map.insert(
+                            #key.to_string(),
+                            self.#field_name
+                                .iter()
+                                .map(|v| v.to_string())
+                                .collect::<Vec<_>>()
+                                .join(";"),
+                        );
+                    }
+                }
+                // Vec fields: join with comma (paths displayed, absent optional lists empty)
+                GenericSpec::VecSpec(_) => {
+                    let items = if field.optional {
+                        quote! { self.#field_name.iter().flatten() }
+                    } else {
+                        quote! { self.#field_name.iter() }
+                    };
+                    let item = if field.field_type == format!("Vec<{PATH_BUF}>") {
+                        quote! { v.display().to_string() }
+                    } else {
+                        quote! { v.to_string() }
+                    };
+                    quote! {
+                        map.insert(
+                            #key.to_string(),
+                            #items
+                                .map(|v| #item)
+                                .collect::<Vec<_>>()
+                                .join(","),
+                        );
+                    }
+                }
+                GenericSpec::EnumSpec(e) => {
+                    if e.optional {
+                        quote! {
+                            map.insert(
+                                #key.to_string(),
+                                self.#field_name
+                                    .as_ref()
+                                    .and_then(|e| clap::ValueEnum::to_possible_value(e))
+                                    .map(|pv| pv.get_name().to_string())
+                                    .expect("no skipped variants"),
+                            );
+                        }
+                    } else {
+                        quote! {
+                            map.insert(
+                                #key.to_string(),
+                                clap::ValueEnum::to_possible_value(&self.#field_name)
+                                    .expect("no skipped variants")
+                                    .get_name()
+                                    .to_string(),
+                            );
+                        }
+                    }
+                }
+                _ if field.field_type == SECRET_STRING => {
+                    let value = if field.optional {
+                        quote! {
+                            self.#field_name
+                                .as_ref()
+                                .map(|v| rclap::secrecy::ExposeSecret::expose_secret(v).to_string())
+                                .unwrap_or_default()
+                        }
+                    } else {
+                        quote! {
+                            rclap::secrecy::ExposeSecret::expose_secret(&self.#field_name).to_string()
+                        }
+                    };
+                    quote! {
+                        map.insert(#key.to_string(), #value);
+                    }
+                }
+                // Optional fields
+                _ if field.optional => {
+                    quote! {
+                        map.insert(
+                            #key.to_string(),
+                            self.#field_name
+                                .as_ref()
+                                .map(|v| v.to_string())
+                                .unwrap_or_default(),
+                        );
+                    }
+                }
+                _ if field.field_type == PATH_BUF => {
+                    quote! {
+                        map.insert(
+                            #key.to_string(),
+                            self.#field_name.display().to_string(),
+                        );
+                    }
+                }
+                // Secret fields: use expose_secret()
+                _ if field.secret => {
+                    quote! {
+                        map.insert(#key.to_string(), self.#field_name.expose_secret().to_string());
+                    }
+                }
+
+                // All other scalar / enum fields
+                _ => {
+                    quote! {
+                        map.insert(#key.to_string(), self.#field_name.to_string());
+                    }
+                }
+            };
+            quote! { #cfg { #entry } }
+        })
+        .collect();
+
+    quote! {
+        impl #struct_ident {
+            pub fn iter_map(&self) -> std::collections::HashMap<String, String> {
+                let mut map = std::collections::HashMap::new();
+                #(#entries)*
+                map
+            }
+        }
+    }
+}
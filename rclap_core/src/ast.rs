@@ -8,10 +8,60 @@ pub struct Spec {
     pub id: String,
     pub field_type: String,
     pub doc: Option<String>,
+    /// The help of `--help`, where `-h` shows `doc`.
+    pub long_doc: Option<String>,
+    /// Other long flags of the field, listed in `--help`.
+    pub aliases: Vec<String>,
     pub variant: GenericSpec,
     pub name: String,
     pub optional: bool,
     pub secret: bool,
+    pub locked: bool,
+    /// Accepted after any subcommand of the command too, as clap's `global` args.
+    pub global: bool,
+    /// Why the field is deprecated, e.g. `use --endpoint instead`. Its flag still parses,
+    /// hidden from `--help`, and warns when used.
+    pub deprecated: Option<String>,
+    /// Hides the env value and the default from `--help` and the value from parse errors.
+    pub sensitive: bool,
+    /// An env var naming a file whose trimmed content is the value, e.g. `DB_PASSWORD_FILE`
+    /// for a mounted secret. Ranked below the field's own env var.
+    pub env_file: Option<String>,
+    /// Set on a section whose type an earlier section already declared: `field_type` is then
+    /// the section's own args struct, converted into this type.
+    pub reuses: Option<String>,
+    /// A `#[cfg(...)]` predicate, e.g. `feature = "metrics"`, gating the generated field.
+    pub cfg: Option<String>,
+    /// The `--help` heading grouping the args of a section.
+    pub heading: Option<String>,
+    /// Defaults taken when another field has a given value, first match first.
+    pub default_if: Vec<DefaultIf>,
+    /// Conditions making the field required: another field (an arg id) given a value.
+    pub required_if: Vec<RequiredIf>,
+    /// Arg ids of the fields that, when given, make this one optional.
+    pub required_unless: Vec<String>,
+    /// Arg ids of the fields this one overrides, the last given winning.
+    pub overrides_with: Vec<String>,
+    /// The `ValueHint` of shell completion, e.g. `file_path` or `hostname`.
+    pub hint: Option<String>,
+    /// clap's `num_args` range of a single-value field, e.g. `0..=1`.
+    pub num_args: Option<String>,
+    /// The value of a flag given without one, e.g. `--color` meaning `always`.
+    pub default_missing_value: Option<String>,
+}
+/// A `default_if` entry: the field defaults to `value` when the field `field` (an arg id) is
+/// given `equals`.
+#[derive(serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DefaultIf {
+    pub field: String,
+    pub equals: String,
+    pub value: String,
+}
+/// A `required_if` entry: the field is required when the field `field` is given `equals`.
+#[derive(serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RequiredIf {
+    pub field: String,
+    pub equals: String,
 }
 #[derive(serde::Deserialize, Clone, Debug)]
 pub enum GenericSpec {
@@ -20,6 +70,9 @@ pub enum GenericSpec {
     ExternalSpec(ExternalStruct),
     EnumSpec(EnumField),
     VecSpec(VecField),
+    MapSpec(MapField),
+    SubtypeVecSpec(SubtypeVecField),
+    SubtypeMapSpec(SubtypeMapField),
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -30,6 +83,12 @@ pub struct Field {
     pub short_arg: Option<char>,
     pub optional: bool,
     pub is_secret: bool,
+    /// Inclusive bounds, wide enough for every `u64` and `i64` value.
+    pub min: Option<i128>,
+    pub max: Option<i128>,
+    pub values: Vec<String>,
+    pub value_parser: Option<String>,
+    pub pattern: Option<String>,
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -44,6 +103,13 @@ impl Deref for SubField {
 pub struct ExternalStruct {
     pub long_arg: Option<String>,
     pub short_arg: Option<char>,
+    /// Declared with `external = "..."`: a `#[config_section]` struct from another crate.
+    pub section: bool,
+    /// Declared with `type = "...", external = true`: a hand-written `clap::Args` struct.
+    pub args: bool,
+    /// Field paths the section must provide, with their Rust type when `requires` is a table,
+    /// checked at compile time.
+    pub requires: Vec<(String, Option<String>)>,
 }
 #[derive(serde::Deserialize, Clone, Debug)]
 pub struct EnumField {
@@ -54,6 +120,8 @@ pub struct EnumField {
     pub enum_name: String,
     pub variants: Vec<String>,
     pub default: Option<String>,
+    /// Match the variants ignoring ASCII case.
+    pub ignore_case: bool,
 }
 #[derive(serde::Deserialize, Clone, Debug)]
 pub struct VecField {
@@ -62,6 +130,380 @@ pub struct VecField {
     pub long_arg: Option<String>,
     pub short_arg: Option<char>,
     pub optional: bool,
+    pub value_parser: Option<String>,
+    pub pattern: Option<String>,
+    /// Split values on the platform path-list separator (`:` or `;`) instead of `,`.
+    pub split_paths: bool,
+    /// Character splitting CLI and env values, `None` to keep each value whole.
+    pub delimiter: Option<char>,
+    /// Bounds on the number of values per occurrence (`min_items` / `max_items`).
+    pub min_items: Option<usize>,
+    pub max_items: Option<usize>,
+    /// Positional, taking every remaining argument, hyphenated ones included.
+    pub trailing: bool,
+    /// With `trailing`: only the arguments after `--`, passed through untouched.
+    pub raw: bool,
+}
+/// A `[[listeners]]` array of tables: a `Vec` of a nested struct whose fields are described by
+/// the single table. Each element is given as `key=value,...`, elements separated by `;`.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct SubtypeVecField {
+    pub fields: SubField,
+    pub env: Option<String>,
+    pub long_arg: Option<String>,
+    pub short_arg: Option<char>,
+}
+/// A section with `map_of = "UpstreamConfig"`: a `HashMap` of named nested structs whose fields
+/// are the section's. Each entry is given as `name:key=value,...`, entries separated by `;`.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct SubtypeMapField {
+    pub element: String,
+    pub fields: SubField,
+    pub env: Option<String>,
+    pub long_arg: Option<String>,
+    pub short_arg: Option<char>,
+}
+/// A `type = "map"` field: repeatable `key=value` pairs collected into a `HashMap`.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct MapField {
+    pub default: Vec<(String, String)>,
+    pub env: Option<String>,
+    pub long_arg: Option<String>,
+    pub short_arg: Option<char>,
+    pub delimiter: Option<char>,
+}
+/// Command-level settings read from the top-level `[app]` (or `[rclap]`) table.
+#[derive(serde::Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppSpec {
+    pub error_json: bool,
+    /// Prepended to every `env` name of the spec.
+    pub env_prefix: Option<String>,
+    /// Derive the env var of every field without an `env` key from its id.
+    pub auto_env: bool,
+    /// Generate a `--config <PATH>` flag reading field values from a TOML file.
+    pub config_file: ConfigFileSpec,
+    /// Generate a hidden `--dump-config[=FORMAT]` flag printing the resolved configuration as
+    /// TOML or JSON, secrets redacted, and exiting.
+    pub dump_config: bool,
+    /// Generate a `--print-config-template` flag printing a commented config file of every
+    /// field and exiting.
+    pub config_template: bool,
+    /// Load a `.env` file, when present, before reading the environment.
+    pub dotenv: bool,
+    /// The `.env` file to load, by default `.env` in the working directory. Implies `dotenv`.
+    pub dotenv_path: Option<String>,
+    /// Generate `watch(callback)`, parsing again each time the config file changes.
+    pub watch: bool,
+    /// Generate `init()` and `global()`, storing the parsed config for the whole process.
+    pub global: bool,
+    /// Order of the value sources, highest first, e.g. `["cli", "file", "env", "default"]`.
+    pub precedence: Vec<String>,
+    /// Extra derives of the generated types, e.g. `["Eq", "Hash"]`.
+    pub derives: Vec<String>,
+    /// The command name shown in help and errors, by default the package name.
+    pub name: Option<String>,
+    /// Adds `--version`, printing this version, or the package version for `"cargo"`.
+    pub version: Option<String>,
+    /// Build-time env var holding a commit hash, appended to the version when set.
+    pub git_sha_env: Option<String>,
+    pub about: Option<String>,
+    /// The help shown by `--help`, over `about`.
+    pub long_about: Option<String>,
+    pub author: Option<String>,
+    /// Text shown above the usage in `--help`.
+    pub before_help: Option<String>,
+    /// Text shown below the options in `--help`, e.g. examples or links.
+    pub after_help: Option<String>,
+    /// Put the help of every arg on the line below its flag.
+    pub next_line_help: bool,
+    /// Wrap `--help` at this many columns instead of the terminal width.
+    pub term_width: Option<usize>,
+    /// clap's `help_template`, e.g. `"{name} {version}\n{usage}\n\n{all-args}"`.
+    pub help_template: Option<String>,
+    /// Drop clap's `-h`/`--help`, letting fields take them.
+    pub disable_help_flag: bool,
+    /// Accept unknown subcommands, git-style: `app plugin --flag` sets `command` to
+    /// `Some(Commands::External(["plugin", "--flag"]))`.
+    pub external_subcommands: bool,
+    /// How long flags are derived from the field ids when `long` is not given: `"kebab"`
+    /// (`--database-primary-url`), `"snake"` or `"last-segment"` (`--url`). By default the id
+    /// itself.
+    pub long_style: Option<String>,
+    /// `[app.style]`: the colors of the help and error output.
+    pub style: StyleSpec,
+}
+/// `config_file = true`, or `config_file = { name = "mytool.toml", lookup = "xdg" }` to also
+/// read `mytool.toml` from the platform config directory when `--config` is not given.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConfigFileSpec {
+    pub enabled: bool,
+    /// The file name looked up.
+    pub name: Option<String>,
+    /// Where the file is looked up: `"xdg"`, the platform config directory of the command.
+    pub lookup: Option<String>,
+}
+impl ConfigFileSpec {
+    /// Accepted values of `lookup`.
+    pub const LOOKUPS: [&'static str; 1] = ["xdg"];
+}
+impl<'de> serde::Deserialize<'de> for ConfigFileSpec {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        let table = match Value::deserialize(deserializer)? {
+            Value::Boolean(enabled) => {
+                return Ok(ConfigFileSpec {
+                    enabled,
+                    ..Default::default()
+                });
+            }
+            Value::Table(table) => table,
+            _ => return Err(D::Error::custom("config_file must be a bool or a table")),
+        };
+        let mut spec = ConfigFileSpec {
+            enabled: true,
+            ..Default::default()
+        };
+        for (key, value) in table {
+            let value = value
+                .as_str()
+                .map(String::from)
+                .ok_or_else(|| D::Error::custom(format!("config_file {key} must be a string")))?;
+            match key.as_str() {
+                "name" => spec.name = Some(value),
+                "lookup" if Self::LOOKUPS.contains(&value.as_str()) => spec.lookup = Some(value),
+                "lookup" => {
+                    return Err(D::Error::custom(format!(
+                        "unknown config_file lookup '{value}', expected one of {:?}",
+                        Self::LOOKUPS
+                    )));
+                }
+                key => return Err(D::Error::custom(format!("unknown config_file key '{key}'"))),
+            }
+        }
+        match (&spec.name, &spec.lookup) {
+            (Some(_), Some(_)) => Ok(spec),
+            _ => Err(D::Error::custom(
+                "config_file needs both a name and a lookup, e.g. { name = \"mytool.toml\", lookup = \"xdg\" }",
+            )),
+        }
+    }
+}
+/// The styles of `[app.style]`, each a color and effects, e.g. `"bold green"` or `"#ff8800"`.
+/// Unset ones keep clap's.
+#[derive(serde::Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct StyleSpec {
+    pub header: Option<String>,
+    pub usage: Option<String>,
+    pub literal: Option<String>,
+    pub placeholder: Option<String>,
+    pub error: Option<String>,
+    pub valid: Option<String>,
+    pub invalid: Option<String>,
+}
+impl StyleSpec {
+    pub fn styles(&self) -> Vec<(&'static str, &str)> {
+        [
+            ("header", &self.header),
+            ("usage", &self.usage),
+            ("literal", &self.literal),
+            ("placeholder", &self.placeholder),
+            ("error", &self.error),
+            ("valid", &self.valid),
+            ("invalid", &self.invalid),
+        ]
+        .into_iter()
+        .filter_map(|(name, style)| style.as_deref().map(|style| (name, style)))
+        .collect()
+    }
+
+    pub const EFFECTS: [&'static str; 4] = ["bold", "dimmed", "italic", "underline"];
+    /// The ANSI color names, each also available as `bright-{name}`.
+    pub const COLORS: [&'static str; 8] = [
+        "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    ];
+
+    /// Whether `color` is an ANSI color name, a `bright-` one or `#rrggbb`.
+    pub fn is_color(color: &str) -> bool {
+        match color.strip_prefix('#') {
+            Some(hex) => hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()),
+            None => Self::COLORS.contains(&color.strip_prefix("bright-").unwrap_or(color)),
+        }
+    }
+
+    /// Rejects a style made of anything but effects and colors.
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, style) in self.styles() {
+            let valid = style
+                .split_whitespace()
+                .all(|word| Self::EFFECTS.contains(&word) || Self::is_color(word));
+            if !valid {
+                return Err(format!(
+                    "Invalid style '{style}' for {name} in [app.style]: expected effects and a color such as \"bold green\", \"bright-blue\" or \"#ff8800\""
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+impl AppSpec {
+    /// Top-level table names read as settings.
+    pub const TABLES: [&'static str; 2] = ["app", "rclap"];
+
+    /// Whether a top-level table named in [`Self::TABLES`] holds the settings. `[rclap]` always
+    /// does; an `[app]` declaring a `type` or holding field tables other than `style` and
+    /// `config_file` is a regular `app` section.
+    pub fn is_app_table(name: &str, table: &toml::value::Table) -> bool {
+        name == "rclap"
+            || !table.contains_key("type")
+                && table
+                    .iter()
+                    .all(|(key, value)| !value.is_table() || key == "style" || key == "config_file")
+    }
+
+    /// The flags the generated command adds itself, as `(long, short)`: `--help`, `--version`
+    /// with a `version`, `--config` with `config_file`, `--print-config-template` with
+    /// `config_template`, and the hidden `--list-values` and `--dump-config` (with
+    /// `dump_config`).
+    pub fn reserved_flags(&self) -> Vec<(&'static str, Option<char>)> {
+        let mut flags = Vec::new();
+        if !self.disable_help_flag {
+            flags.push(("help", Some('h')));
+        }
+        if self.version.is_some() {
+            flags.push(("version", Some('V')));
+        }
+        if self.config_file.enabled {
+            flags.push(("config", None));
+        }
+        flags.push(("list-values", None));
+        if self.dump_config {
+            flags.push(("dump-config", None));
+        }
+        if self.config_template {
+            flags.push(("print-config-template", None));
+        }
+        flags
+    }
+
+    /// Sources accepted in `precedence`.
+    pub const SOURCES: [&'static str; 4] = ["cli", "env", "file", "default"];
+
+    /// The value sources, highest first: `precedence`, or the command line, the environment,
+    /// the config file (when enabled) and the spec defaults.
+    pub fn sources(&self) -> Vec<&str> {
+        if !self.precedence.is_empty() {
+            return self.precedence.iter().map(String::as_str).collect();
+        }
+        Self::SOURCES
+            .into_iter()
+            .filter(|source| *source != "file" || self.config_file.enabled)
+            .collect()
+    }
+
+    /// Rejects a `precedence` naming an unknown or repeated source, or moving the `cli` and
+    /// `default` layers: only `env` and `file` can be reordered.
+    pub fn validate_precedence(&self) -> Result<(), String> {
+        let precedence = &self.precedence;
+        if precedence.is_empty() {
+            return Ok(());
+        }
+        for (i, source) in precedence.iter().enumerate() {
+            if !Self::SOURCES.contains(&source.as_str()) {
+                return Err(format!(
+                    "Unknown source '{source}' in precedence, expected one of {:?}",
+                    Self::SOURCES
+                ));
+            }
+            if precedence[..i].contains(source) {
+                return Err(format!("Source '{source}' is listed twice in precedence"));
+            }
+        }
+        if precedence.first().map(String::as_str) != Some("cli")
+            || precedence.last().map(String::as_str) != Some("default")
+        {
+            return Err(
+                "precedence must start with \"cli\" and end with \"default\": only \"env\" and \"file\" can be reordered"
+                    .to_string(),
+            );
+        }
+        // clap reads the env vars of the args whatever the order, so they cannot be left out.
+        if !precedence.iter().any(|p| p == "env") {
+            return Err("precedence must list \"env\"".to_string());
+        }
+        match (
+            precedence.iter().any(|p| p == "file"),
+            self.config_file.enabled,
+        ) {
+            (true, false) => {
+                Err("precedence lists \"file\" but config_file is not enabled".to_string())
+            }
+            (false, true) => {
+                Err("precedence must list \"file\" when config_file is enabled".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+impl GenericSpec {
+    /// The env var of the field, for the variants that read one.
+    pub fn env_mut(&mut self) -> Option<&mut Option<String>> {
+        match self {
+            GenericSpec::FieldSpec(f) => Some(&mut f.env),
+            GenericSpec::EnumSpec(f) => Some(&mut f.env),
+            GenericSpec::VecSpec(f) => Some(&mut f.env),
+            GenericSpec::MapSpec(f) => Some(&mut f.env),
+            GenericSpec::SubtypeVecSpec(f) => Some(&mut f.env),
+            GenericSpec::SubtypeMapSpec(f) => Some(&mut f.env),
+            GenericSpec::SubtypeSpec(_) | GenericSpec::ExternalSpec(_) => None,
+        }
+    }
+
+    /// The `long` of the field, when set.
+    pub fn long(&self) -> Option<&str> {
+        self.long_flag().flatten()
+    }
+
+    /// The `long` of the field, for the variants that take one.
+    pub fn long_flag(&self) -> Option<Option<&str>> {
+        match self {
+            GenericSpec::FieldSpec(f) => Some(f.long_arg.as_deref()),
+            GenericSpec::EnumSpec(f) => Some(f.long_arg.as_deref()),
+            GenericSpec::VecSpec(f) if f.trailing => None,
+            GenericSpec::VecSpec(f) => Some(f.long_arg.as_deref()),
+            GenericSpec::MapSpec(f) => Some(f.long_arg.as_deref()),
+            GenericSpec::SubtypeVecSpec(f) => Some(f.long_arg.as_deref()),
+            GenericSpec::SubtypeMapSpec(f) => Some(f.long_arg.as_deref()),
+            GenericSpec::SubtypeSpec(_) | GenericSpec::ExternalSpec(_) => None,
+        }
+    }
+
+    /// The `short` of the field, when set.
+    pub fn short(&self) -> Option<char> {
+        match self {
+            GenericSpec::FieldSpec(f) => f.short_arg,
+            GenericSpec::EnumSpec(f) => f.short_arg,
+            GenericSpec::VecSpec(f) => f.short_arg,
+            GenericSpec::MapSpec(f) => f.short_arg,
+            GenericSpec::SubtypeVecSpec(f) => f.short_arg,
+            GenericSpec::SubtypeMapSpec(f) => f.short_arg,
+            GenericSpec::SubtypeSpec(_) | GenericSpec::ExternalSpec(_) => None,
+        }
+    }
+
+    /// The long flag of the field, for the variants that take one.
+    pub fn long_mut(&mut self) -> Option<&mut Option<String>> {
+        match self {
+            GenericSpec::FieldSpec(f) => Some(&mut f.long_arg),
+            GenericSpec::EnumSpec(f) => Some(&mut f.long_arg),
+            GenericSpec::VecSpec(f) => Some(&mut f.long_arg),
+            GenericSpec::MapSpec(f) => Some(&mut f.long_arg),
+            GenericSpec::SubtypeVecSpec(f) => Some(&mut f.long_arg),
+            GenericSpec::SubtypeMapSpec(f) => Some(&mut f.long_arg),
+            GenericSpec::SubtypeSpec(_) | GenericSpec::ExternalSpec(_) => None,
+        }
+    }
 }
 impl Spec {
     pub fn new(
@@ -78,6 +520,9 @@ impl Spec {
             GenericSpec::ExternalSpec(_) => false,
             GenericSpec::EnumSpec(f) => f.optional,
             GenericSpec::VecSpec(f) => f.optional,
+            GenericSpec::MapSpec(_) => false,
+            GenericSpec::SubtypeVecSpec(_) => false,
+            GenericSpec::SubtypeMapSpec(_) => false,
         };
         let secret = match &variant {
             GenericSpec::FieldSpec(f) => f.is_secret,
@@ -85,16 +530,127 @@ impl Spec {
             GenericSpec::ExternalSpec(_) => false,
             GenericSpec::EnumSpec(_) => false,
             GenericSpec::VecSpec(_) => false,
+            GenericSpec::MapSpec(_) => false,
+            GenericSpec::SubtypeVecSpec(_) => false,
+            GenericSpec::SubtypeMapSpec(_) => false,
         };
         Spec {
             toml_tag_name,
             id,
             field_type,
             doc,
+            long_doc: None,
+            aliases: Vec::new(),
             variant,
             name,
             optional,
             secret,
+            locked: false,
+            global: false,
+            deprecated: None,
+            sensitive: false,
+            env_file: None,
+            reuses: None,
+            cfg: None,
+            heading: None,
+            default_if: Vec::new(),
+            required_if: Vec::new(),
+            required_unless: Vec::new(),
+            overrides_with: Vec::new(),
+            hint: None,
+            num_args: None,
+            default_missing_value: None,
+        }
+    }
+
+    /// Marks a nested section as optional: generated as `Option<...>`, present only when one
+    /// of its args is given.
+    pub fn optional_section(mut self, optional: bool) -> Self {
+        if matches!(self.variant, GenericSpec::SubtypeSpec(_)) {
+            self.optional = optional;
+        }
+        self
+    }
+
+    /// Marks the field as settable only from the environment or a config file.
+    pub fn locked(mut self, locked: bool) -> Self {
+        self.locked = locked;
+        self
+    }
+
+    pub fn global(mut self, global: bool) -> Self {
+        self.global = global;
+        self
+    }
+
+    pub fn deprecated(mut self, deprecated: Option<String>) -> Self {
+        self.deprecated = deprecated;
+        self
+    }
+
+    pub fn default_if(mut self, default_if: Vec<DefaultIf>) -> Self {
+        self.default_if = default_if;
+        self
+    }
+
+    pub fn required_if(mut self, required_if: Vec<RequiredIf>, unless: Vec<String>) -> Self {
+        self.required_if = required_if;
+        self.required_unless = unless;
+        self
+    }
+
+    pub fn overrides_with(mut self, overrides_with: Vec<String>) -> Self {
+        self.overrides_with = overrides_with;
+        self
+    }
+
+    pub fn hint(mut self, hint: Option<String>) -> Self {
+        self.hint = hint;
+        self
+    }
+
+    pub fn env_file(mut self, env_file: Option<String>) -> Self {
+        self.env_file = env_file;
+        self
+    }
+
+    pub fn num_args(mut self, num_args: Option<String>, default_missing: Option<String>) -> Self {
+        self.num_args = num_args;
+        self.default_missing_value = default_missing;
+        self
+    }
+
+    pub fn sensitive(mut self, sensitive: bool) -> Self {
+        self.sensitive = sensitive;
+        self
+    }
+
+    pub fn long_doc(mut self, long_doc: Option<String>) -> Self {
+        self.long_doc = long_doc;
+        self
+    }
+
+    pub fn aliases(mut self, aliases: Vec<String>) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    /// Groups the args of a section under `heading` in `--help`.
+    pub fn heading(mut self, heading: Option<String>) -> Self {
+        let is_section = match &self.variant {
+            GenericSpec::SubtypeSpec(_) => true,
+            GenericSpec::ExternalSpec(e) => e.section,
+            _ => false,
+        };
+        if is_section {
+            self.heading = heading;
         }
+        self
+    }
+
+    /// Compiles the field only when the `cfg` predicate holds.
+    pub fn cfg(mut self, cfg: Option<String>) -> Self {
+        self.cfg = cfg;
+        self
     }
 }
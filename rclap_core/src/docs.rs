@@ -0,0 +1,389 @@
+//! Reference documentation of the options of a spec, and a config file template.
+
+use crate::{ConfigSpec, GenericSpec, INTEGER_TYPES, Spec};
+
+/// An option as documented: a field taking a value.
+struct Entry<'a> {
+    /// The `--long`, or `<name>...` for a trailing field.
+    flag: String,
+    short: Option<char>,
+    env: Option<&'a str>,
+    env_file: Option<&'a str>,
+    ty: String,
+    /// The accepted values of an enum or a `values` field.
+    values: &'a [String],
+    default: Option<String>,
+    doc: String,
+}
+
+impl ConfigSpec {
+    /// A Markdown table of every option: flag, env var, type, default and description, the
+    /// top-level fields under "Options" and those of each section under its heading.
+    pub fn markdown_help(&self) -> String {
+        let mut out = String::new();
+        for (heading, entries) in sections(&self.fields) {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(&format!("## {heading}\n\n"));
+            out.push_str("| Flag | Env | Type | Default | Description |\n");
+            out.push_str("|---|---|---|---|---|\n");
+            for entry in entries {
+                let code =
+                    |value: Option<&str>| value.map_or_else(String::new, |v| format!("`{v}`"));
+                let flag = match entry.short {
+                    Some(short) => format!("`{}`, `-{short}`", entry.flag),
+                    None => format!("`{}`", entry.flag),
+                };
+                let values = entry
+                    .values
+                    .iter()
+                    .map(|v| format!("`{v}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let ty = match entry.ty.as_str() {
+                    _ if values.is_empty() => format!("`{}`", entry.ty),
+                    "String" => values,
+                    ty => format!("`{ty}`: {values}"),
+                };
+                let cells = [
+                    flag,
+                    code(entry.env),
+                    ty,
+                    code(entry.default.as_deref()),
+                    entry.doc,
+                ];
+                let cells: Vec<String> =
+                    cells.iter().map(|cell| cell.replace('|', "\\|")).collect();
+                out.push_str(&format!("| {} |\n", cells.join(" | ")));
+            }
+        }
+        out
+    }
+
+    /// A man page of the command `name`, in roff, listing every option under OPTIONS and
+    /// those of each section in a subsection named after its heading.
+    pub fn man_page(&self, name: &str) -> String {
+        let name = self.app.name.as_deref().unwrap_or(name);
+        let mut out = format!(
+            ".TH {} 1\n.SH NAME\n{}",
+            roff(&name.to_uppercase()),
+            roff(name)
+        );
+        if let Some(about) = &self.app.about {
+            out.push_str(&format!(" \\- {}", roff(about)));
+        }
+        out.push_str("\n.SH OPTIONS\n");
+        for (heading, entries) in sections(&self.fields) {
+            if heading != "Options" {
+                out.push_str(&format!(".SS \"{}\"\n", roff(heading)));
+            }
+            for entry in entries {
+                out.push_str(&format!(".TP\n\\fB{}\\fR", roff(&entry.flag)));
+                if let Some(short) = entry.short {
+                    out.push_str(&format!(", \\fB\\-{short}\\fR"));
+                }
+                out.push_str(&format!(" \\fI<{}>\\fR\n", roff(&entry.ty)));
+                out.push_str(&roff(&entry.doc));
+                let mut notes = Vec::new();
+                if !entry.values.is_empty() {
+                    notes.push(format!("One of: {}.", entry.values.join(", ")));
+                }
+                if let Some(env) = entry.env {
+                    notes.push(format!("Env: {env}."));
+                }
+                if let Some(default) = &entry.default {
+                    notes.push(format!("Default: {default}."));
+                }
+                if !notes.is_empty() {
+                    if !entry.doc.is_empty() {
+                        out.push_str("\n.br\n");
+                    }
+                    out.push_str(&roff(&notes.join(" ")));
+                }
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    /// Every option as a JSON array of `{section, flag, short, env, type, values, default,
+    /// description}` objects, `section` being `null` for the top-level fields.
+    pub fn json_docs(&self) -> String {
+        let mut objects = Vec::new();
+        for (heading, entries) in sections(&self.fields) {
+            let section = (heading != "Options").then_some(heading);
+            for entry in entries {
+                let values: Vec<String> =
+                    entry.values.iter().map(|v| json_string(Some(v))).collect();
+                let short = entry.short.map(String::from);
+                let description = (!entry.doc.is_empty()).then_some(entry.doc.as_str());
+                let members = [
+                    ("section", json_string(section)),
+                    ("flag", json_string(Some(&entry.flag))),
+                    ("short", json_string(short.as_deref())),
+                    ("env", json_string(entry.env)),
+                    ("type", json_string(Some(&entry.ty))),
+                    ("values", format!("[{}]", values.join(","))),
+                    ("default", json_string(entry.default.as_deref())),
+                    ("description", json_string(description)),
+                ];
+                let members: Vec<String> = members
+                    .iter()
+                    .map(|(key, value)| format!("\"{key}\":{value}"))
+                    .collect();
+                objects.push(format!("{{{}}}", members.join(",")));
+            }
+        }
+        format!("[{}]\n", objects.join(","))
+    }
+
+    /// A `.env.example` listing the env var of every field, with its description, commented
+    /// out and set to its default, and its `env_file` var. The defaults of secret and
+    /// sensitive fields are left out.
+    pub fn env_example(&self) -> String {
+        let mut blocks = Vec::new();
+        for (_, entries) in sections(&self.fields) {
+            for entry in entries {
+                if entry.env.is_none() && entry.env_file.is_none() {
+                    continue;
+                }
+                let mut block = String::new();
+                if !entry.doc.is_empty() {
+                    block.push_str(&format!("# {}\n", entry.doc));
+                }
+                if let Some(env) = entry.env {
+                    let default = entry.default.as_deref().map(dotenv_value);
+                    block.push_str(&format!("# {env}={}\n", default.unwrap_or_default()));
+                }
+                if let Some(env_file) = entry.env_file {
+                    block.push_str(&format!("# {env_file}=\n"));
+                }
+                blocks.push(block);
+            }
+        }
+        blocks.join("\n")
+    }
+
+    /// A `--config` file listing every field with its description and env var, commented out
+    /// and set to its default, to uncomment the ones to change. Fields without a default are
+    /// left empty.
+    pub fn config_template(&self) -> String {
+        let mut out = String::new();
+        template_section(&mut out, &[], &self.fields);
+        out
+    }
+}
+
+/// The options of `fields` grouped by section, the top-level ones under "Options". Deprecated
+/// fields are left out.
+fn sections(fields: &[Spec]) -> Vec<(&str, Vec<Entry<'_>>)> {
+    let mut sections = Vec::new();
+    collect_sections("Options", fields, &mut sections);
+    sections
+}
+
+fn collect_sections<'a>(
+    heading: &'a str,
+    fields: &'a [Spec],
+    sections: &mut Vec<(&'a str, Vec<Entry<'a>>)>,
+) {
+    let entries: Vec<Entry> = fields
+        .iter()
+        .filter(|field| field.deprecated.is_none())
+        .filter_map(entry)
+        .collect();
+    if !entries.is_empty() {
+        sections.push((heading, entries));
+    }
+    for field in fields {
+        if let GenericSpec::SubtypeSpec(sub) = &field.variant {
+            let heading = field.heading.as_deref().unwrap_or(&field.name);
+            collect_sections(heading, sub, sections);
+        }
+    }
+}
+
+/// The entry of a field taking a value, `None` for sections.
+fn entry(field: &Spec) -> Option<Entry<'_>> {
+    let flag = match field.variant.long_flag() {
+        Some(long) => format!("--{}", long.unwrap_or(&field.id)),
+        None if matches!(field.variant, GenericSpec::VecSpec(_)) => format!("<{}>...", field.name),
+        None => return None,
+    };
+    let env = env(field);
+    let values: &[String] = match &field.variant {
+        GenericSpec::EnumSpec(e) => &e.variants,
+        GenericSpec::FieldSpec(f) => &f.values,
+        _ => &[],
+    };
+    let default = match &field.variant {
+        _ if field.secret || field.sensitive => None,
+        GenericSpec::FieldSpec(f) => f.default.clone(),
+        GenericSpec::EnumSpec(f) => f.default.clone(),
+        GenericSpec::VecSpec(f) => f.default.as_ref().and_then(|d| d.as_array()).map(|d| {
+            d.iter()
+                .map(|v| v.as_str().map_or_else(|| v.to_string(), String::from))
+                .collect::<Vec<_>>()
+                .join(",")
+        }),
+        GenericSpec::MapSpec(f) if !f.default.is_empty() => Some(
+            f.default
+                .iter()
+                .map(|(k, v)| format!("{k}={v}"))
+                .collect::<Vec<_>>()
+                .join(","),
+        ),
+        _ => None,
+    };
+    let doc = field.doc.as_deref().unwrap_or_default().replace('\n', " ");
+    Some(Entry {
+        flag,
+        short: field.variant.short(),
+        env,
+        env_file: field.env_file.as_deref(),
+        ty: short_type(&field.field_type),
+        values,
+        default,
+        doc: doc.trim().to_string(),
+    })
+}
+
+fn env(field: &Spec) -> Option<&str> {
+    match &field.variant {
+        GenericSpec::FieldSpec(f) => f.env.as_deref(),
+        GenericSpec::EnumSpec(f) => f.env.as_deref(),
+        GenericSpec::VecSpec(f) => f.env.as_deref(),
+        GenericSpec::MapSpec(f) => f.env.as_deref(),
+        GenericSpec::SubtypeVecSpec(f) => f.env.as_deref(),
+        GenericSpec::SubtypeMapSpec(f) => f.env.as_deref(),
+        GenericSpec::SubtypeSpec(_) | GenericSpec::ExternalSpec(_) => None,
+    }
+}
+
+fn template_section(out: &mut String, path: &[&str], fields: &[Spec]) {
+    let mut sections = Vec::new();
+    let mut lines = Vec::new();
+    for field in fields.iter().filter(|field| field.deprecated.is_none()) {
+        match &field.variant {
+            GenericSpec::SubtypeSpec(sub) => sections.push((field.name.as_str(), sub)),
+            GenericSpec::ExternalSpec(_) => {}
+            _ => {
+                if let Some(doc) = &field.doc {
+                    lines.extend(doc.lines().map(|line| format!("# {}", line.trim())));
+                }
+                if let Some(env) = env(field) {
+                    lines.push(format!("# Env: {env}"));
+                }
+                lines.push(match template_value(field) {
+                    Some(value) => format!("# {} = {value}", field.name),
+                    None => format!("# {} =", field.name),
+                });
+                lines.push(String::new());
+            }
+        }
+    }
+    if !lines.is_empty() {
+        if !path.is_empty() {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(&format!("[{}]\n", path.join(".")));
+        }
+        out.push_str(&lines.join("\n"));
+    }
+    for (name, sub) in sections {
+        let mut path = path.to_vec();
+        path.push(name);
+        template_section(out, &path, sub);
+    }
+}
+
+/// The default of a field as a TOML value.
+fn template_value(field: &Spec) -> Option<String> {
+    if field.secret || field.sensitive {
+        return None;
+    }
+    let value = match &field.variant {
+        GenericSpec::FieldSpec(f) => {
+            let default = f.default.as_deref()?;
+            let ty = field.field_type.as_str();
+            let literal = match ty {
+                _ if INTEGER_TYPES.contains(&ty) || ty.starts_with("std::num::NonZero") => {
+                    default.parse().ok().map(toml::Value::Integer)
+                }
+                "f32" | "f64" => default.parse().ok().map(toml::Value::Float),
+                "bool" => default.parse().ok().map(toml::Value::Boolean),
+                _ => None,
+            };
+            literal.unwrap_or_else(|| toml::Value::String(default.to_string()))
+        }
+        GenericSpec::EnumSpec(f) => toml::Value::String(f.default.clone()?),
+        GenericSpec::VecSpec(f) => f.default.clone()?,
+        GenericSpec::MapSpec(f) if !f.default.is_empty() => toml::Value::Table(
+            f.default
+                .iter()
+                .map(|(k, v)| (k.clone(), toml::Value::String(v.clone())))
+                .collect(),
+        ),
+        _ => return None,
+    };
+    Some(value.to_string())
+}
+
+/// `std::path::PathBuf` as `PathBuf`, `Vec<rclap::url::Url>` as `Vec<Url>`.
+fn short_type(ty: &str) -> String {
+    let mut out = String::new();
+    let mut path = String::new();
+    for c in ty.chars() {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            path.push(c);
+        } else {
+            out.push_str(path.rsplit("::").next().unwrap_or_default());
+            path.clear();
+            out.push(c);
+        }
+    }
+    out.push_str(path.rsplit("::").next().unwrap_or_default());
+    out
+}
+
+/// A value as written in a `.env` file, double-quoted unless made of plain characters.
+fn dotenv_value(value: &str) -> String {
+    let plain = |c: char| c.is_alphanumeric() || "_-.,:/=@+%".contains(c);
+    if value.chars().all(plain) {
+        value.to_string()
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+/// Escapes the backslashes and hyphens of roff text, and the dots and quotes starting a line.
+fn roff(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    match text.chars().next() {
+        Some('.' | '\'') => format!("\\&{text}"),
+        _ => text,
+    }
+}
+
+fn json_string(value: Option<&str>) -> String {
+    let Some(value) = value else {
+        return "null".to_string();
+    };
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
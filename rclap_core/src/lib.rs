@@ -1,81 +1,1226 @@
 pub mod ast;
-pub use ast::{EnumField, ExternalStruct, Field, GenericSpec, Spec, SubField};
+pub use ast::{
+    AppSpec, ConfigFileSpec, DefaultIf, EnumField, ExternalStruct, Field, GenericSpec, MapField,
+    RequiredIf, Spec, StyleSpec, SubField, SubtypeMapField, SubtypeVecField,
+};
+mod docs;
+pub mod parsers;
 mod utils;
-use std::{collections::HashMap, path::PathBuf};
-
-use crate::{ast::VecField, utils::get_field_type};
+pub mod validate;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+pub use utils::{INTEGER_TYPES, has_literal_default, to_pascal_case};
+
+use crate::{
+    ast::VecField,
+    utils::{
+        get_field_type, has_integer_type, integer_bounds, is_cfg_predicate, port_value_parser,
+        validate_default, validate_enum,
+    },
+    validate::Problem,
+};
 
 use serde::Deserialize;
 
 pub const PATH_BUF: &str = "std::path::PathBuf";
+pub const TIMEZONE: &str = "rclap::chrono_tz::Tz";
+pub const LANGUAGE_ID: &str = "rclap::unic_langid::LanguageIdentifier";
+pub const IP_NET: &str = "rclap::ipnet::IpNet";
+pub const COLOR: &str = "rclap::Color";
+pub const URL: &str = "rclap::url::Url";
+pub const UUID: &str = "rclap::uuid::Uuid";
+pub const DATETIME: &str = "rclap::toml_datetime::Datetime";
+pub const SECRET_STRING: &str = "rclap::secrecy::SecretString";
+pub const STRING_MAP: &str = "std::collections::HashMap<String, String>";
+/// The `hint`s of shell completion, clap's `ValueHint` variants in snake case.
+pub const VALUE_HINTS: [&str; 12] = [
+    "any_path",
+    "file_path",
+    "dir_path",
+    "executable_path",
+    "command_name",
+    "command_string",
+    "command_with_arguments",
+    "username",
+    "hostname",
+    "url",
+    "email_address",
+    "other",
+];
+/// Keys of the table of a field or a section. Any other table of a section is a nested field.
+pub const FIELD_KEYS: [&str; 43] = [
+    "aliases",
+    "auto_env",
+    "cfg",
+    "default",
+    "default_if",
+    "default_missing_value",
+    "delimiter",
+    "deprecated",
+    "doc",
+    "enum",
+    "env",
+    "env_file",
+    "extends",
+    "external",
+    "global",
+    "heading",
+    "hint",
+    "ignore_case",
+    "locked",
+    "long",
+    "long_doc",
+    "map_of",
+    "max",
+    "max_items",
+    "min",
+    "min_items",
+    "num_args",
+    "optional",
+    "overrides_with",
+    "pattern",
+    "raw",
+    "required_if",
+    "required_unless",
+    "requires",
+    "secret",
+    "sensitive",
+    "short",
+    "split_paths",
+    "strict_ports",
+    "trailing",
+    "type",
+    "values",
+    "variants",
+];
 #[derive(serde::Deserialize, Debug)]
 pub struct ConfigSpec {
     pub fields: Vec<Spec>,
+    pub app: AppSpec,
+    /// Every spec file read, includes first, for the build to track.
+    #[serde(skip)]
+    pub files: Vec<PathBuf>,
 }
 impl ConfigSpec {
-    pub fn from_file(
-        path: &PathBuf,
+    pub fn from_file(path: &Path, struct_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_file_with_profile(path, struct_name, None)
+    }
+
+    /// Reads the spec at `path` with the overrides of `[profiles.<profile>]` applied.
+    pub fn from_file_with_profile(
+        path: &Path,
         struct_name: &str,
+        profile: Option<&str>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        let content = std::fs::read_to_string(path)?;
-        if path.extension().and_then(|s| s.to_str()) == Some("toml") {
-            let spec = Self::load_toml_config(&content, struct_name);
-            Ok(spec)
-        } else {
-            Err("Unsupported file format. Only .toml is supported.".into())
-        }
+        GenericConfigSpec::from_file(path)?
+            .with_struct_name(struct_name.to_string())
+            .with_profile(profile.map(String::from))
+            .try_into()
+            .map_err(|problems: Vec<Problem>| validate::messages(&problems).into())
     }
+    #[cfg(test)]
     fn load_toml_config(toml_content: &str, struct_name: &str) -> ConfigSpec {
         let generic_config_spec: GenericConfigSpec = toml::from_str(toml_content)
             .unwrap_or_else(|e| panic!("Failed to parse TOML config: {}", e));
         generic_config_spec
             .with_struct_name(struct_name.to_string())
-            .into()
+            .try_into()
+            .unwrap_or_else(|problems: Vec<Problem>| panic!("{}", validate::messages(&problems)))
+    }
+}
+
+/// Reads a spec file with the files of its top-level `include = ["db.toml", ...]`, resolved
+/// relative to it. Included specs are merged in order, then overridden by the including one.
+fn load_spec_table(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> Result<toml::Table, Box<dyn std::error::Error>> {
+    if path.extension().and_then(|s| s.to_str()) != Some("toml") {
+        return Err("Unsupported file format. Only .toml is supported.".into());
+    }
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("cannot read spec '{}': {e}", path.display()))?;
+    let canonical = path.canonicalize()?;
+    if stack.contains(&canonical) {
+        let chain: Vec<String> = stack.iter().map(|p| p.display().to_string()).collect();
+        return Err(format!(
+            "include cycle: {} -> {}",
+            chain.join(" -> "),
+            path.display()
+        )
+        .into());
+    }
+    let mut own: toml::Table =
+        toml::from_str(&content).map_err(|e| format!("invalid spec '{}': {e}", path.display()))?;
+    // A table is a field named `include`.
+    let includes = match own.get("include") {
+        Some(toml::Value::String(file)) => vec![file.clone()],
+        Some(toml::Value::Array(files)) => files
+            .iter()
+            .map(|file| match file {
+                toml::Value::String(file) => Ok(file.clone()),
+                other => Err(format!(
+                    "include in '{}' lists {other}, not a file",
+                    path.display()
+                )),
+            })
+            .collect::<Result<_, _>>()?,
+        _ => Vec::new(),
+    };
+    if !own.get("include").is_some_and(|v| v.is_table()) {
+        own.remove("include");
     }
+    stack.push(canonical);
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut merged = toml::Table::new();
+    for file in includes {
+        override_table(&mut merged, load_spec_table(&dir.join(file), stack, files)?);
+    }
+    if let Some(canonical) = stack.pop()
+        && !files.contains(&canonical)
+    {
+        files.push(canonical);
+    }
+    override_table(&mut merged, own);
+    Ok(merged)
 }
 
 #[derive(Debug, Deserialize)]
 pub struct GenericConfigSpec {
     #[serde(flatten)]
-    pub fields: HashMap<String, toml::Value>,
+    pub fields: toml::Table,
     struct_name: Option<String>,
+    #[serde(skip)]
+    profile: Option<String>,
+    #[serde(skip)]
+    overrides: Vec<(String, toml::Value)>,
+    #[serde(skip)]
+    files: Vec<PathBuf>,
 }
 impl GenericConfigSpec {
+    /// Reads the spec at `path`, its `include`s merged in.
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut files = Vec::new();
+        Ok(GenericConfigSpec {
+            fields: load_spec_table(path, &mut Vec::new(), &mut files)?,
+            struct_name: None,
+            profile: None,
+            overrides: Vec::new(),
+            files,
+        })
+    }
+
+    /// Reads a spec given inline. It has no directory to resolve an `include` against.
+    pub fn from_toml_str(content: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let spec: GenericConfigSpec = toml::from_str(content)?;
+        if spec
+            .fields
+            .get("include")
+            .is_some_and(|include| !include.is_table())
+        {
+            return Err("include is only supported in spec files".into());
+        }
+        Ok(spec)
+    }
+
+    /// Reads `paths` in order, each spec overriding the fields of the previous ones.
+    pub fn from_files(paths: &[PathBuf]) -> Result<Self, Box<dyn std::error::Error>> {
+        let (first, rest) = paths.split_first().ok_or("no spec file given")?;
+        let mut merged = Self::from_file(first)?;
+        for path in rest {
+            merged = merged
+                .merge(Self::from_file(path)?)
+                .map_err(|e| format!("cannot merge spec '{}': {e}", path.display()))?;
+        }
+        Ok(merged)
+    }
+
+    /// Overrides the fields of this spec key by key with those of `other`, as `include` does.
+    /// Fails when `other` changes the type of a field, or turns a field into a section.
+    pub fn merge(mut self, other: GenericConfigSpec) -> Result<Self, String> {
+        check_merge(&self.fields, &other.fields, "")?;
+        override_table(&mut self.fields, other.fields);
+        for file in other.files {
+            if !self.files.contains(&file) {
+                self.files.push(file);
+            }
+        }
+        Ok(self)
+    }
+
     pub fn with_struct_name(self, struct_name: String) -> GenericConfigSpec {
         GenericConfigSpec {
-            fields: self.fields,
             struct_name: Some(struct_name),
+            ..self
         }
     }
+
+    /// Applies the `[profiles.<profile>]` overrides of the spec.
+    pub fn with_profile(self, profile: Option<String>) -> GenericConfigSpec {
+        GenericConfigSpec { profile, ..self }
+    }
+
+    /// Sets single field settings, e.g. `("port.default", "9090")`, over the spec and its
+    /// profile.
+    pub fn with_overrides(self, overrides: Vec<(String, toml::Value)>) -> GenericConfigSpec {
+        GenericConfigSpec { overrides, ..self }
+    }
 }
-impl From<GenericConfigSpec> for ConfigSpec {
-    fn from(generic: GenericConfigSpec) -> Self {
+/// Reads the spec as the build does, failing with every problem found: each field in error
+/// is left out of the checks run across fields, which would otherwise report it again.
+impl TryFrom<GenericConfigSpec> for ConfigSpec {
+    type Error = Vec<Problem>;
+
+    fn try_from(generic: GenericConfigSpec) -> Result<Self, Vec<Problem>> {
         let mut fields = Vec::new();
+        let mut app = AppSpec::default();
         let struct_name = generic.struct_name.unwrap_or_else(|| "Config".to_string());
-        for (field_name, value) in generic.fields {
+        let file = generic.files.last().cloned().unwrap_or_default();
+        let in_file = |problems: Vec<Problem>| {
+            let locate = |problem| Problem {
+                file: file.clone(),
+                ..problem
+            };
+            problems.into_iter().map(locate).collect::<Vec<_>>()
+        };
+        let mut root = generic.fields;
+        apply_profile(&mut root, generic.profile.as_deref())
+            .and_then(|()| apply_overrides(&mut root, &generic.overrides))
+            .map_err(|message| in_file(vec![Problem::of(None, message)]))?;
+        resolve_extends(&mut root).map_err(|problem| in_file(vec![problem]))?;
+        // Settings first: `auto_env` applies while the fields are read.
+        let mut values = Vec::new();
+        for (field_name, value) in root {
             match value {
-                toml::Value::Table(table) => {
-                    let field_spec =
-                        table_to_field_spec(field_name.clone(), &table, None, &struct_name);
-
-                    fields.push(field_spec);
+                toml::Value::Table(table)
+                    if AppSpec::TABLES.contains(&field_name.as_str())
+                        && AppSpec::is_app_table(&field_name, &table) =>
+                {
+                    let invalid = |message| in_file(vec![Problem::of(Some(&field_name), message)]);
+                    app = toml::Value::Table(table)
+                        .try_into()
+                        .map_err(|e| invalid(format!("Invalid [{field_name}] table: {e}")))?;
+                    app.validate_precedence().map_err(invalid)?;
+                    app.style.validate().map_err(invalid)?;
+                    if app.watch && !app.config_file.enabled {
+                        return Err(invalid(format!(
+                            "watch = true needs config_file in [{field_name}]"
+                        )));
+                    }
+                    if app.git_sha_env.is_some() && app.version.is_none() {
+                        return Err(invalid(format!(
+                            "git_sha_env needs a version in [{field_name}]"
+                        )));
+                    }
+                }
+                value => values.push((field_name, value)),
+            }
+        }
+        let mut problems = Vec::new();
+        if app.external_subcommands && values.iter().any(|(name, _)| name == "command") {
+            problems.push(Problem::of(
+                Some(&format!("{struct_name}.command")),
+                "external_subcommands adds a `command` field; rename the spec field 'command'"
+                    .to_string(),
+            ));
+        }
+        let mut left_out = Vec::new();
+        for (field_name, value) in values {
+            let field_spec = match value {
+                toml::Value::Table(table) => table_to_field_spec(
+                    field_name.clone(),
+                    &table,
+                    None,
+                    &struct_name,
+                    app.auto_env,
+                ),
+                toml::Value::Array(items) if items.iter().all(|item| item.is_table()) => {
+                    array_of_tables_to_field_spec(
+                        field_name.clone(),
+                        &items,
+                        None,
+                        &struct_name,
+                        app.auto_env,
+                    )
                 }
                 _ => {
                     eprintln!("Warning: Skipping non-table field '{}'", field_name);
+                    continue;
+                }
+            };
+            match field_spec {
+                Ok(field_spec) => fields.push(field_spec),
+                Err(found) => {
+                    problems.extend(found);
+                    left_out.push(format!("{struct_name}.{field_name}"));
+                }
+            }
+        }
+
+        if let Some(prefix) = &app.env_prefix {
+            prefix_env(&mut fields, prefix);
+        }
+        if let Some(style) = &app.long_style
+            && let Err(problem) = apply_long_style(&mut fields, style)
+        {
+            problems.push(problem);
+        }
+        check_aliases(&fields, &mut problems);
+        check_flag_collisions(&fields, &app, &mut problems);
+        check_conditions(&fields, &left_out, &mut problems);
+        if let Err(problem) = reuse_sections(&mut fields, &mut Vec::new()) {
+            problems.push(problem);
+        }
+        if !problems.is_empty() {
+            return Err(in_file(problems));
+        }
+        Ok(ConfigSpec {
+            fields,
+            app,
+            files: generic.files,
+        })
+    }
+}
+fn prefix_env(fields: &mut [Spec], prefix: &str) {
+    for field in fields {
+        if let Some(Some(env)) = field.variant.env_mut() {
+            *env = format!("{prefix}{env}");
+        }
+        if let Some(env_file) = &mut field.env_file {
+            *env_file = format!("{prefix}{env_file}");
+        }
+        match &mut field.variant {
+            GenericSpec::SubtypeSpec(sub) => prefix_env(&mut sub.0, prefix),
+            GenericSpec::SubtypeVecSpec(element) => prefix_env(&mut element.fields.0, prefix),
+            GenericSpec::SubtypeMapSpec(element) => prefix_env(&mut element.fields.0, prefix),
+            _ => {}
+        }
+    }
+}
+/// Gives every field without a `long` the flag of `long_style`. When two fields would share a
+/// flag, the `last-segment` ones are prefixed with their section names until they differ:
+/// `database.url` and `cache.url` take `--database-url` and `--cache-url`.
+fn apply_long_style(fields: &mut [Spec], style: &str) -> Result<(), Problem> {
+    if !["kebab", "snake", "last-segment"].contains(&style) {
+        return Err(Problem::of(
+            None,
+            format!(
+                "Unknown long_style '{style}', expected \"kebab\", \"snake\" or \"last-segment\""
+            ),
+        ));
+    }
+    let flag = |path: &[String], depth: usize| match style {
+        "kebab" => path.join("-").replace('_', "-"),
+        "snake" => path.join("_"),
+        _ => path[path.len() - depth..].join("-"),
+    };
+    let mut flags = Vec::new();
+    collect_flags(fields, &mut flags);
+    let mut depths = vec![1; flags.len()];
+    loop {
+        let longs: Vec<String> = flags
+            .iter()
+            .zip(&depths)
+            .map(|(flag_of, depth)| {
+                flag_of
+                    .long
+                    .clone()
+                    .unwrap_or_else(|| flag(&flag_of.path, *depth))
+            })
+            .collect();
+        let collides = |i: usize| {
+            flags[i].long.is_none()
+                && longs
+                    .iter()
+                    .enumerate()
+                    .any(|(j, long)| j != i && *long == longs[i])
+        };
+        let colliding: Vec<usize> = (0..flags.len()).filter(|&i| collides(i)).collect();
+        let Some(&first) = colliding.first() else {
+            let styled = flags.into_iter().zip(longs).map(|(f, long)| (f.id, long));
+            set_longs(fields, &styled.collect::<HashMap<_, _>>());
+            return Ok(());
+        };
+        let mut deepened = false;
+        for i in colliding {
+            if style == "last-segment" && depths[i] < flags[i].path.len() {
+                depths[i] += 1;
+                deepened = true;
+            }
+        }
+        if !deepened {
+            let other = (0..flags.len())
+                .find(|&j| j != first && longs[j] == longs[first])
+                .expect("colliding flag");
+            let (a, b) = (&flags[first.min(other)].id, &flags[first.max(other)].id);
+            return Err(Problem::of(
+                Some(a),
+                format!(
+                    "long_style = \"{style}\" gives '--{}' to both '{a}' and '{b}'; set long on one of them",
+                    longs[first]
+                ),
+            ));
+        }
+    }
+}
+
+/// The flag of a field: its `long`, or its path for `long_style` to derive one from.
+struct FlagOf {
+    id: String,
+    path: Vec<String>,
+    long: Option<String>,
+}
+
+fn collect_flags(fields: &[Spec], flags: &mut Vec<FlagOf>) {
+    for field in fields {
+        if let Some(long) = field.variant.long_flag() {
+            let path = field
+                .id
+                .split_once('.')
+                .map_or(field.id.as_str(), |(_, path)| path);
+            flags.push(FlagOf {
+                id: field.id.clone(),
+                path: path.split('.').map(String::from).collect(),
+                long: long.map(String::from),
+            });
+        }
+        if let GenericSpec::SubtypeSpec(sub) = &field.variant {
+            collect_flags(&sub.0, flags);
+        }
+    }
+}
+
+/// Reports two fields of the command, sections included, sharing a `short` or a `long`, or a
+/// field taking a flag the command adds itself, naming the fields and the spec keys that set
+/// the flag.
+fn check_flag_collisions(fields: &[Spec], app: &AppSpec, problems: &mut Vec<Problem>) {
+    let mut flags = Vec::new();
+    collect_flags(fields, &mut flags);
+    let mut args = Vec::new();
+    collect_args(fields, &mut args);
+    let path = |id: &str| id.split_once('.').map_or(id, |(_, path)| path).to_string();
+    let location = |id: &str, key: &str| format!("`{}.{key}`", path(id));
+    let mut longs: Vec<(&str, &str, &str)> = flags
+        .iter()
+        .map(|f| (f.id.as_str(), f.long.as_deref().unwrap_or(&f.id), "long"))
+        .collect();
+    for arg in &args {
+        longs.extend(
+            arg.aliases
+                .iter()
+                .map(|a| (arg.id.as_str(), a.as_str(), "aliases")),
+        );
+    }
+    let shorts: Vec<(&str, char)> = args
+        .iter()
+        .filter_map(|arg| Some((arg.id.as_str(), arg.variant.short()?)))
+        .collect();
+    let hint = |flag: &str| match flag {
+        "help" => "; set disable_help_flag = true in [app] to define your own",
+        _ => "",
+    };
+    for (flag, short) in app.reserved_flags() {
+        if let Some((id, _, key)) = longs.iter().find(|(_, long, _)| *long == flag) {
+            problems.push(Problem::of(
+                Some(id),
+                format!(
+                    "Field '{}' ({}) takes '--{flag}', which the command reserves for its {flag} flag{}",
+                    path(id),
+                    location(id, key),
+                    hint(flag),
+                ),
+            ));
+        }
+        if let Some((id, short)) = shorts.iter().find(|(_, s)| Some(*s) == short) {
+            problems.push(Problem::of(
+                Some(id),
+                format!(
+                    "Field '{}' ({}) takes '-{short}', which the command reserves for its {flag} flag{}",
+                    path(id),
+                    location(id, "short"),
+                    hint(flag),
+                ),
+            ));
+        }
+    }
+    for (i, (id, long, key)) in longs.iter().enumerate() {
+        if let Some((other, _, other_key)) = longs[i + 1..].iter().find(|(_, l, _)| l == long) {
+            problems.push(Problem::of(
+                Some(id),
+                format!(
+                    "Fields '{}' ({}) and '{}' ({}) both take '--{long}'",
+                    path(id),
+                    location(id, key),
+                    path(other),
+                    location(other, other_key),
+                ),
+            ));
+        }
+    }
+    for (i, (id, short)) in shorts.iter().enumerate() {
+        if let Some((other, _)) = shorts[i + 1..].iter().find(|(_, s)| s == short) {
+            problems.push(Problem::of(
+                Some(id),
+                format!(
+                    "Fields '{}' ({}) and '{}' ({}) both take '-{short}'",
+                    path(id),
+                    location(id, "short"),
+                    path(other),
+                    location(other, "short"),
+                ),
+            ));
+        }
+    }
+}
+
+/// Reports a `default_if`, `required_if`, `required_unless` or `overrides_with` naming a field
+/// missing from the command, and a conditionally required field that would be required anyway.
+/// A field of `left_out`, in error, is missing for a reason already reported.
+fn check_conditions(fields: &[Spec], left_out: &[String], problems: &mut Vec<Problem>) {
+    let mut args = Vec::new();
+    collect_args(fields, &mut args);
+    let path = |id: &str| id.split_once('.').map_or(id, |(_, path)| path).to_string();
+    for arg in &args {
+        let named = arg
+            .default_if
+            .iter()
+            .map(|c| ("default_if", &c.field))
+            .chain(arg.required_if.iter().map(|c| ("required_if", &c.field)))
+            .chain(arg.required_unless.iter().map(|f| ("required_unless", f)))
+            .chain(arg.overrides_with.iter().map(|f| ("overrides_with", f)));
+        let reported = |field: &str| {
+            left_out
+                .iter()
+                .any(|id| field == id || field.starts_with(&format!("{id}.")))
+        };
+        for (key, field) in named {
+            if !args.iter().any(|other| other.id == *field) && !reported(field) {
+                problems.push(Problem::of(
+                    Some(&arg.id),
+                    format!(
+                        "Field '{}': {key} names no field '{}'",
+                        path(&arg.id),
+                        path(field)
+                    ),
+                ));
+            }
+        }
+        let has_default = match &arg.variant {
+            GenericSpec::FieldSpec(f) => f.default.is_some(),
+            GenericSpec::EnumSpec(f) => f.default.is_some(),
+            GenericSpec::VecSpec(f) => f.default.is_some(),
+            _ => true,
+        };
+        let conditional = !arg.required_if.is_empty() || !arg.required_unless.is_empty();
+        if conditional && !arg.optional && !has_default {
+            problems.push(Problem::of(
+                Some(&arg.id),
+                format!(
+                    "Field '{}' is always required: required_if and required_unless need optional = true or a default",
+                    path(&arg.id)
+                ),
+            ));
+        }
+    }
+}
+
+/// The fields of the command taking a flag, those of its sections included.
+fn collect_args<'a>(fields: &'a [Spec], args: &mut Vec<&'a Spec>) {
+    for field in fields {
+        match &field.variant {
+            GenericSpec::SubtypeSpec(sub) => collect_args(&sub.0, args),
+            GenericSpec::ExternalSpec(_) => {}
+            _ => args.push(field),
+        }
+    }
+}
+
+/// Reports the sections setting `aliases`: they take no flag.
+fn check_aliases(fields: &[Spec], problems: &mut Vec<Problem>) {
+    for field in fields {
+        match &field.variant {
+            GenericSpec::SubtypeSpec(_) | GenericSpec::ExternalSpec(_)
+                if !field.aliases.is_empty() =>
+            {
+                problems.push(Problem::of(
+                    Some(&field.id),
+                    format!("Section '{}' has no flag to alias", field.id),
+                ));
+            }
+            GenericSpec::SubtypeSpec(sub) => check_aliases(&sub.0, problems),
+            _ => {}
+        }
+    }
+}
+
+fn set_longs(fields: &mut [Spec], longs: &HashMap<String, String>) {
+    for field in fields {
+        if let (Some(long_arg @ None), Some(long)) =
+            (field.variant.long_mut(), longs.get(&field.id))
+        {
+            *long_arg = Some(long.clone());
+        }
+        if let GenericSpec::SubtypeSpec(sub) = &mut field.variant {
+            set_longs(&mut sub.0, longs);
+        }
+    }
+}
+
+/// Removes the `[profiles]` table, overriding the spec with the one named `profile`. A spec
+/// without profiles ignores `profile`, so a build-wide `RCLAP_PROFILE` leaves it alone.
+fn apply_profile(root: &mut toml::Table, profile: Option<&str>) -> Result<(), String> {
+    let profiles = match root.remove("profiles") {
+        None => return Ok(()),
+        Some(toml::Value::Table(profiles)) => profiles,
+        Some(other) => {
+            return Err(format!(
+                "[profiles] must be a table of profiles, got {other}"
+            ));
+        }
+    };
+    let Some(profile) = profile else {
+        return Ok(());
+    };
+    match profiles.get(profile) {
+        Some(toml::Value::Table(overrides)) => {
+            override_table(root, overrides.clone());
+            Ok(())
+        }
+        _ => Err(format!(
+            "Unknown profile '{profile}', the spec declares {:?}",
+            profiles.keys().collect::<Vec<_>>()
+        )),
+    }
+}
+
+/// Sets each `field.setting` of `overrides`; the field must be declared by the spec.
+fn apply_overrides(
+    root: &mut toml::Table,
+    overrides: &[(String, toml::Value)],
+) -> Result<(), String> {
+    for (path, value) in overrides {
+        let Some((field, setting)) = path.rsplit_once('.') else {
+            return Err(format!(
+                "Override '{path}' must name a field setting, e.g. port.default"
+            ));
+        };
+        let table = field
+            .split('.')
+            .try_fold(&mut *root, |table, key| match table.get_mut(key) {
+                Some(toml::Value::Table(table)) => Some(table),
+                _ => None,
+            })
+            .ok_or_else(|| format!("Override '{path}' names no field '{field}' of the spec"))?;
+        table.insert(setting.to_string(), value.clone());
+    }
+    Ok(())
+}
+
+/// Replaces every `extends = "path"` by the fields of the section at `path` (itself resolved
+/// first), overridden key by key by the fields listed next to `extends`.
+fn resolve_extends(root: &mut toml::Table) -> Result<(), Problem> {
+    loop {
+        let mut pending = Vec::new();
+        extends_paths(root, &mut Vec::new(), &mut pending);
+        let Some(first) = pending.first() else {
+            return Ok(());
+        };
+        let invalid =
+            |path: &[String], message| Problem::of(Some(&format!(".{}", path.join("."))), message);
+        let base_of = |path: &[String]| -> Result<Vec<String>, Problem> {
+            match section(root, path).and_then(|t| t.get("extends")) {
+                Some(toml::Value::String(base)) => Ok(base.split('.').map(String::from).collect()),
+                _ => Err(invalid(
+                    path,
+                    format!(
+                        "`extends` of section '{}' must be a section path",
+                        path.join(".")
+                    ),
+                )),
+            }
+        };
+        // A base must be fully resolved before it is copied; an invalid one is reported first.
+        let ready = pending.iter().find(|path| {
+            let Ok(base) = base_of(path) else {
+                return true;
+            };
+            match section(root, &base) {
+                Some(base) => {
+                    let mut nested = Vec::new();
+                    extends_paths(base, &mut Vec::new(), &mut nested);
+                    !base.contains_key("extends") && nested.is_empty()
                 }
+                None => true,
+            }
+        });
+        let Some(path) = ready.cloned() else {
+            return Err(invalid(
+                first,
+                format!(
+                    "Section '{}' extends a section that extends it back",
+                    first.join(".")
+                ),
+            ));
+        };
+        let base_path = base_of(&path)?;
+        let Some(mut merged) = section(root, &base_path).cloned() else {
+            return Err(invalid(
+                &path,
+                format!(
+                    "Section '{}' extends '{}', which is not a section",
+                    path.join("."),
+                    base_path.join(".")
+                ),
+            ));
+        };
+        let table = section_mut(root, &path).expect("pending section");
+        let mut own = std::mem::take(table);
+        own.remove("extends");
+        override_table(&mut merged, own);
+        *table = merged;
+    }
+}
+
+/// Paths of the tables holding an `extends` key, outermost first.
+fn extends_paths(table: &toml::Table, path: &mut Vec<String>, found: &mut Vec<Vec<String>>) {
+    for (key, value) in table {
+        if let toml::Value::Table(sub) = value {
+            path.push(key.clone());
+            if sub.contains_key("extends") {
+                found.push(path.clone());
+            }
+            extends_paths(sub, path, found);
+            path.pop();
+        }
+    }
+}
+
+fn section<'a>(root: &'a toml::Table, path: &[String]) -> Option<&'a toml::Table> {
+    path.iter()
+        .try_fold(root, |table, key| table.get(key)?.as_table())
+}
+
+fn section_mut<'a>(root: &'a mut toml::Table, path: &[String]) -> Option<&'a mut toml::Table> {
+    path.iter()
+        .try_fold(root, |table, key| table.get_mut(key)?.as_table_mut())
+}
+
+/// Overrides `base` with `own`: field tables and sections key by key, anything else (`default`
+/// tables included) as a whole.
+fn override_table(base: &mut toml::Table, own: toml::Table) {
+    for (key, value) in own {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(own)) if key != "default" => {
+                override_table(base, own)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// The conflicts `merge` rejects, `prefix` being the path of the tables compared.
+fn check_merge(base: &toml::Table, other: &toml::Table, prefix: &str) -> Result<(), String> {
+    for (key, value) in other {
+        let (Some(toml::Value::Table(base)), toml::Value::Table(other)) = (base.get(key), value)
+        else {
+            continue;
+        };
+        if key == "default" {
+            continue;
+        }
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        if let (Some(from), Some(to)) = (base.get("type"), other.get("type"))
+            && from != to
+        {
+            return Err(format!(
+                "field '{path}' changes its type from {from} to {to}"
+            ));
+        }
+        match (is_section(base), is_section(other)) {
+            (false, true) if is_value_field(base) => {
+                return Err(format!("field '{path}' is redeclared as a section"));
+            }
+            (true, false) if is_value_field(other) => {
+                return Err(format!("section '{path}' is redeclared as a field"));
+            }
+            _ => check_merge(base, other, &path)?,
+        }
+    }
+    Ok(())
+}
+
+/// Whether the table states the kind of its field, by a `type`, `enum` or `external` key.
+fn is_value_field(table: &toml::Table) -> bool {
+    ["type", "enum", "external"]
+        .iter()
+        .any(|key| table.contains_key(*key))
+}
+
+/// Whether the table declares nested fields.
+fn is_section(table: &toml::Table) -> bool {
+    table
+        .iter()
+        .filter(|(key, _)| !FIELD_KEYS.contains(&key.as_str()))
+        .any(|(_, value)| value.is_table() || is_array_of_tables(value))
+}
+
+fn is_array_of_tables(value: &toml::Value) -> bool {
+    value
+        .as_array()
+        .is_some_and(|items| !items.is_empty() && items.iter().all(|item| item.is_table()))
+}
+
+/// Points every section declaring the type of an earlier section at that type, giving it an
+/// `{Path}Args` struct of its own for its flags. Fails when the two declare different fields.
+fn reuse_sections(
+    fields: &mut [Spec],
+    seen: &mut Vec<(String, String, Vec<String>)>,
+) -> Result<(), Problem> {
+    for field in fields {
+        let GenericSpec::SubtypeSpec(sub) = &mut field.variant else {
+            continue;
+        };
+        let shape = section_shape(sub);
+        match seen.iter().find(|(ty, _, _)| *ty == field.field_type) {
+            Some((ty, first, first_shape)) if *first_shape != shape => {
+                return Err(Problem::of(
+                    Some(&field.id),
+                    format!(
+                        "Sections '{first}' and '{}' both use type {ty} but declare different fields: {first_shape:?} vs {shape:?}",
+                        field.id
+                    ),
+                ));
+            }
+            Some((ty, _, _)) => {
+                let path = field
+                    .id
+                    .split_once('.')
+                    .map_or(field.id.as_str(), |(_, p)| p);
+                let args: String = path.split(['.', '_']).map(utils::to_pascal_case).collect();
+                field.reuses = Some(ty.clone());
+                field.field_type = format!("{args}Args");
             }
+            None => seen.push((field.field_type.clone(), field.id.clone(), shape)),
         }
+        reuse_sections(&mut sub.0, seen)?;
+    }
+    Ok(())
+}
+
+/// `name: Type` of every field of a section, optional ones as `Option<Type>`.
+fn section_shape(fields: &[Spec]) -> Vec<String> {
+    fields
+        .iter()
+        .map(|field| {
+            let ty = field.reuses.as_ref().unwrap_or(&field.field_type);
+            let ty = if field.secret {
+                format!("Secret<{ty}>")
+            } else {
+                ty.clone()
+            };
+            let ty = if field.optional {
+                format!("Option<{ty}>")
+            } else {
+                ty
+            };
+            match &field.cfg {
+                Some(cfg) => format!("#[cfg({cfg})] {}: {ty}", field.name),
+                None => format!("{}: {ty}", field.name),
+            }
+        })
+        .collect()
+}
+/// Reads the `delimiter` key: a single character, or `false` to disable splitting.
+fn delimiter(
+    table: &toml::value::Table,
+    id: &str,
+    default: Option<char>,
+) -> Result<Option<char>, String> {
+    match table.get("delimiter") {
+        None => Ok(default),
+        Some(toml::Value::Boolean(false)) => Ok(None),
+        Some(toml::Value::String(d)) if d.chars().count() == 1 => Ok(d.chars().next()),
+        Some(d) => Err(format!(
+            "Invalid delimiter for field '{id}': {d} must be a single character or false"
+        )),
+    }
+}
+/// Reads the `env` key, or derives `DATABASE_PRIMARY_URL` from the id `app.database.primary.url`
+/// when `auto_env` is in effect (set on the field, else inherited from the section or spec).
+fn env(table: &toml::value::Table, id: &str, auto_env: bool) -> Option<String> {
+    let explicit = table.get("env").and_then(|v| v.as_str()).map(String::from);
+    explicit.or_else(|| {
+        auto_env.then(|| {
+            let path = id.split_once('.').map_or(id, |(_, path)| path);
+            path.replace(['.', '-'], "_").to_uppercase()
+        })
+    })
+}
+fn auto_env(table: &toml::value::Table, inherited: bool) -> bool {
+    table
+        .get("auto_env")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(inherited)
+}
+/// Reads a `[[name]]` array of tables: its single table describes the element struct, its
+/// scalar keys (`env`, `doc`, `long`, `short`) the field holding the `Vec`.
+fn array_of_tables_to_field_spec(
+    name: String,
+    items: &[toml::Value],
+    parent_id: Option<String>,
+    struct_name: &str,
+    auto_env: bool,
+) -> Result<Spec, Vec<Problem>> {
+    let [toml::Value::Table(table)] = items else {
+        let id = format!("{}.{name}", parent_id.as_deref().unwrap_or(struct_name));
+        return Err(vec![Problem::of(
+            Some(&id),
+            format!(
+                "Array of tables '{name}' must contain exactly one table describing its elements"
+            ),
+        )]);
+    };
+    // Element fields are parsed from each `key=value,...` entry, never from the environment.
+    let element = table_to_field_spec(name.clone(), table, parent_id, struct_name, false)?;
+    let GenericSpec::SubtypeSpec(fields) = element.variant else {
+        return Err(vec![Problem::of(
+            Some(&element.id),
+            format!(
+                "Array of tables '{}' must declare at least one field",
+                element.id
+            ),
+        )]);
+    };
+    let text = |key: &str| table.get(key).and_then(|v| v.as_str()).map(String::from);
+    let variant = GenericSpec::SubtypeVecSpec(SubtypeVecField {
+        fields,
+        env: env(table, &element.id, self::auto_env(table, auto_env)),
+        long_arg: text("long"),
+        short_arg: text("short")
+            .filter(|s| s.chars().count() == 1)
+            .and_then(|s| s.chars().next()),
+    });
+    Ok(Spec::new(
+        name,
+        element.id,
+        format!("Vec<{}>", element.field_type),
+        element.doc,
+        variant,
+    )
+    .cfg(element.cfg)
+    .aliases(element.aliases))
+}
+/// Reads `required_if = { field = "tls", equals = true }` (or an array of them) and
+/// `required_unless = ["token"]`, the fields named by their path from the root of the spec.
+fn required_ifs(
+    table: &toml::value::Table,
+    name: &str,
+    parent_id: Option<&str>,
+    struct_name: &str,
+) -> Result<(Vec<RequiredIf>, Vec<String>), String> {
+    let id = format!("{}.{name}", parent_id.unwrap_or(struct_name));
+    let invalid =
+        || format!("Invalid required_if of field '{id}': expected {{ field, equals }} tables");
+    let text = |entry: &toml::value::Table, key: &str| match entry.get(key) {
+        Some(toml::Value::String(s)) => Ok(s.clone()),
+        Some(toml::Value::Table(_) | toml::Value::Array(_)) | None => Err(invalid()),
+        Some(other) => Ok(other.to_string()),
+    };
+    let entries = match table.get("required_if") {
+        None => Vec::new(),
+        Some(toml::Value::Table(entry)) => vec![entry],
+        Some(toml::Value::Array(entries)) => entries
+            .iter()
+            .map(|entry| entry.as_table().ok_or_else(invalid))
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err(invalid()),
+    };
+    let required_if = entries
+        .into_iter()
+        .map(|entry| {
+            Ok(RequiredIf {
+                field: format!("{struct_name}.{}", text(entry, "field")?),
+                equals: text(entry, "equals")?,
+            })
+        })
+        .collect::<Result<_, String>>()?;
+    let paths = || format!("Invalid required_unless of field '{id}': expected field paths");
+    let required_unless = match table.get("required_unless") {
+        None => Vec::new(),
+        Some(toml::Value::Array(fields)) => fields
+            .iter()
+            .map(|field| match field.as_str() {
+                Some(field) => Ok(format!("{struct_name}.{field}")),
+                None => Err(paths()),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err(paths()),
+    };
+    Ok((required_if, required_unless))
+}
 
-        ConfigSpec { fields }
+/// Checks a `num_args` range, `"1"`, `"0..=1"`, `"1.."` or `"..=2"`, given as a string or an
+/// integer.
+fn num_args(id: &str, value: &toml::Value) -> Result<String, String> {
+    let range = match value {
+        toml::Value::Integer(n) => n.to_string(),
+        toml::Value::String(s) => s.trim().to_string(),
+        _ => {
+            return Err(format!(
+                "Invalid num_args of field '{id}': expected a range such as \"0..=1\""
+            ));
+        }
+    };
+    let bound = |s: &str| s.is_empty() || s.parse::<usize>().is_ok();
+    let valid = match range.split_once("..") {
+        Some((min, max)) => {
+            let (inclusive, max) = match max.strip_prefix('=') {
+                Some(max) => (true, max),
+                None => (false, max),
+            };
+            // `1..=` and `..` are no ranges, `2..1` and `1..1` are empty.
+            let nonempty = match (min.parse::<usize>(), max.parse::<usize>()) {
+                (Ok(min), Ok(max)) => min < max || inclusive && min == max,
+                (Err(_), Ok(max)) => inclusive || max > 0,
+                _ => true,
+            };
+            bound(min)
+                && bound(max)
+                && !(max.is_empty() && (inclusive || min.is_empty()))
+                && nonempty
+        }
+        None => range.parse::<usize>().is_ok(),
+    };
+    if !valid {
+        return Err(format!(
+            "Invalid num_args of field '{id}': '{range}' is not a range such as \"0..=1\""
+        ));
     }
+    Ok(range)
+}
+
+/// Reads `default_if = [{ field = "env", equals = "prod", value = "warn" }]`, `field` being
+/// the path of another field from the root of the spec.
+fn default_ifs(
+    table: &toml::value::Table,
+    name: &str,
+    parent_id: Option<&str>,
+    struct_name: &str,
+) -> Result<Vec<DefaultIf>, String> {
+    let Some(entries) = table.get("default_if") else {
+        return Ok(Vec::new());
+    };
+    let id = format!("{}.{name}", parent_id.unwrap_or(struct_name));
+    let invalid = || {
+        format!(
+            "Invalid default_if of field '{id}': expected an array of {{ field, equals, value }} tables"
+        )
+    };
+    let text = |entry: &toml::value::Table, key: &str| match entry.get(key) {
+        Some(toml::Value::String(s)) => Ok(s.clone()),
+        Some(toml::Value::Table(_) | toml::Value::Array(_)) | None => Err(invalid()),
+        Some(other) => Ok(other.to_string()),
+    };
+    let entries = entries.as_array().ok_or_else(invalid)?;
+    entries
+        .iter()
+        .map(|entry| {
+            let entry = entry.as_table().ok_or_else(invalid)?;
+            Ok(DefaultIf {
+                field: format!("{struct_name}.{}", text(entry, "field")?),
+                equals: text(entry, "equals")?,
+                value: text(entry, "value")?,
+            })
+        })
+        .collect()
 }
 fn table_to_field_spec(
     toml_tag_name: String,
     table: &toml::value::Table,
     parent_id: Option<String>,
     struct_name: &str,
-) -> Spec {
+    auto_env: bool,
+) -> Result<Spec, Vec<Problem>> {
+    let name = &toml_tag_name;
+    let id = match &parent_id {
+        None => format!("{struct_name}.{name}").to_string(),
+        Some(pname) => format!("{pname}.{name}").to_string(),
+    };
+    let invalid = |message: String| vec![Problem::of(Some(&id), message)];
+    if ["self", "Self", "super", "crate", "_"].contains(&name.as_str()) {
+        return Err(invalid(format!(
+            "Field '{id}' cannot be named `{name}`; rename it and set long"
+        )));
+    }
     let doc = table.get("doc").and_then(|v| v.as_str()).map(String::from);
+    let cfg = table.get("cfg").and_then(|v| v.as_str()).map(String::from);
+    if let Some(cfg) = &cfg
+        && !is_cfg_predicate(cfg)
+    {
+        return Err(invalid(format!("Invalid cfg for field '{id}': {cfg}")));
+    }
+    let long_doc = table
+        .get("long_doc")
+        .and_then(|v| v.as_str())
+        .map(String::from);
+    let aliases: Vec<String> = table
+        .get("aliases")
+        .and_then(|v| v.as_array())
+        .map(|arr| {
+            arr.iter()
+                .map(|alias| {
+                    alias.as_str().map(String::from).ok_or_else(|| {
+                        invalid(format!(
+                            "Aliases of field '{toml_tag_name}' must be strings"
+                        ))
+                    })
+                })
+                .collect::<Result<_, _>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+    let deprecated = table
+        .get("deprecated")
+        .and_then(|v| v.as_str())
+        .map(String::from);
+    let default_if =
+        default_ifs(table, &toml_tag_name, parent_id.as_deref(), struct_name).map_err(invalid)?;
+    let (required_if, required_unless) =
+        required_ifs(table, &toml_tag_name, parent_id.as_deref(), struct_name).map_err(invalid)?;
+    let hint = table
+        .get("hint")
+        .map(|hint| match hint.as_str() {
+            Some(hint) if VALUE_HINTS.contains(&hint) => Ok(hint.to_string()),
+            _ => Err(invalid(format!(
+                "Invalid hint of field '{toml_tag_name}': expected one of {}",
+                VALUE_HINTS.join(", ")
+            ))),
+        })
+        .transpose()?;
+    let paths = || {
+        invalid(format!(
+            "Invalid overrides_with of field '{toml_tag_name}': expected field paths"
+        ))
+    };
+    let overrides_with: Vec<String> = match table.get("overrides_with") {
+        None => Vec::new(),
+        Some(toml::Value::String(field)) => vec![format!("{struct_name}.{field}")],
+        Some(toml::Value::Array(fields)) => fields
+            .iter()
+            .map(|field| match field.as_str() {
+                Some(field) => Ok(format!("{struct_name}.{field}")),
+                None => Err(paths()),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err(paths()),
+    };
     let enum_name = table.get("enum").and_then(|v| v.as_str()).map(String::from);
     let enum_values = table
         .get("variants")
@@ -86,33 +1231,79 @@ fn table_to_field_spec(
                 .collect::<Vec<&str>>()
         })
         .unwrap_or_default();
-    let env = table.get("env").and_then(|v| v.as_str()).map(String::from);
     let long_arg = table.get("long").and_then(|v| v.as_str()).map(String::from);
     let short_arg = table
         .get("short")
         .and_then(|v| v.as_str())
         .filter(|s| s.chars().count() == 1)
         .and_then(|s| s.chars().next());
-    let name = &toml_tag_name;
-    let id = match parent_id {
-        None => format!("{struct_name}.{name}").to_string(),
-        Some(pname) => format!("{pname}.{name}").to_string(),
-    };
-    let reserved_keys = [
-        "type", "default", "doc", "env", "optional", "long", "short", "secret",
-    ];
+    let auto_env = self::auto_env(table, auto_env);
+    let map_of = table.get("map_of").and_then(|v| v.as_str());
+    let env = env(table, &id, auto_env);
+    let sub_auto_env = auto_env && map_of.is_none();
 
+    // Every problem of the nested fields is reported, not only the first one.
     let mut subtype_fields = Vec::new();
+    let mut problems = Vec::new();
     for (sub_name, sub_value) in table {
-        if !reserved_keys.contains(&sub_name.as_str())
-            && let toml::Value::Table(sub_table) = sub_value
-        {
-            let sub_field =
-                table_to_field_spec(sub_name.clone(), sub_table, Some(id.clone()), struct_name);
-            subtype_fields.push(sub_field);
+        if FIELD_KEYS.contains(&sub_name.as_str()) {
+            continue;
+        }
+        let sub_field = match sub_value {
+            toml::Value::Table(sub_table) => table_to_field_spec(
+                sub_name.clone(),
+                sub_table,
+                Some(id.clone()),
+                struct_name,
+                sub_auto_env,
+            ),
+            toml::Value::Array(items)
+                if !items.is_empty() && items.iter().all(|i| i.is_table()) =>
+            {
+                array_of_tables_to_field_spec(
+                    sub_name.clone(),
+                    items,
+                    Some(id.clone()),
+                    struct_name,
+                    sub_auto_env,
+                )
+            }
+            _ => continue,
+        };
+        match sub_field {
+            Ok(sub_field) => subtype_fields.push(sub_field),
+            Err(found) => problems.extend(found),
         }
     }
-    let field_type = get_field_type(table, !subtype_fields.is_empty(), name.clone());
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+    if let Some(element) = map_of {
+        if subtype_fields.is_empty() {
+            return Err(invalid(format!(
+                "Section '{id}' with map_of must declare the fields of {element}"
+            )));
+        }
+        let variant = GenericSpec::SubtypeMapSpec(SubtypeMapField {
+            element: element.to_string(),
+            fields: SubField(subtype_fields),
+            env,
+            long_arg,
+            short_arg,
+        });
+        let field_type = format!("std::collections::HashMap<String, {element}>");
+        return Ok(Spec::new(toml_tag_name, id, field_type, doc, variant)
+            .cfg(cfg)
+            .long_doc(long_doc)
+            .aliases(aliases)
+            .deprecated(deprecated)
+            .default_if(default_if)
+            .required_if(required_if, required_unless)
+            .overrides_with(overrides_with)
+            .hint(hint));
+    }
+    let field_type =
+        get_field_type(table, !subtype_fields.is_empty(), name.clone()).map_err(invalid)?;
     let optional = table
         .get("optional")
         .and_then(|v| v.as_bool())
@@ -121,35 +1312,421 @@ fn table_to_field_spec(
         .get("secret")
         .and_then(|v| v.as_bool())
         .unwrap_or(false);
-    if field_type.is_vec {
-        let default = table.get("default").cloned();
-        let variant = GenericSpec::VecSpec(VecField {
-            default,
-            env,
-            long_arg,
-            short_arg,
-            optional,
-        });
-        return Spec::new(toml_tag_name, id, field_type.type_name, doc, variant);
+    let locked = table
+        .get("locked")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if locked && !subtype_fields.is_empty() {
+        return Err(invalid(format!(
+            "Section '{id}' cannot be locked; lock its fields instead"
+        )));
     }
-    let default = table
-        .get("default")
+    if deprecated.is_some() && !subtype_fields.is_empty() {
+        return Err(invalid(format!(
+            "Section '{id}' cannot be deprecated; deprecate its fields instead"
+        )));
+    }
+    let env_file = table
+        .get("env_file")
         .and_then(|v| v.as_str())
         .map(String::from);
-    let variant = if subtype_fields.is_empty() && field_type.is_native {
-        GenericSpec::FieldSpec(Field {
-            default,
-            env,
-            long_arg,
-            short_arg,
-            optional,
-            is_secret,
+    if env_file.is_some() && !subtype_fields.is_empty() {
+        return Err(invalid(format!(
+            "Section '{id}' cannot set env_file; set it on its fields instead"
+        )));
+    }
+    let global = table
+        .get("global")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if global && !subtype_fields.is_empty() {
+        return Err(invalid(format!(
+            "Section '{id}' cannot be global; mark its fields instead"
+        )));
+    }
+    // Secrets are sensitive unless stated otherwise.
+    let is_secret_string = field_type.type_name.contains(SECRET_STRING);
+    let sensitive = table.get("sensitive").and_then(|v| v.as_bool());
+    if sensitive == Some(true) && !subtype_fields.is_empty() {
+        return Err(invalid(format!(
+            "Section '{id}' cannot be sensitive; mark its fields instead"
+        )));
+    }
+    let sensitive = sensitive.unwrap_or(is_secret || is_secret_string);
+    let values = table
+        .get("values")
+        .and_then(|v| v.as_array())
+        .map(|arr| {
+            arr.iter()
+                .map(|val| match val.as_str() {
+                    Some(val) => Ok(val.to_string()),
+                    None => Err(invalid(format!(
+                        "Invalid values for field '{id}': {val} is not a string"
+                    ))),
+                })
+                .collect::<Result<Vec<String>, _>>()
         })
-    } else if !subtype_fields.is_empty() {
-        GenericSpec::SubtypeSpec(SubField(subtype_fields.clone()))
-    } else {
-        match enum_name {
-            Some(enum_name) => GenericSpec::EnumSpec(EnumField {
+        .transpose()?
+        .unwrap_or_default();
+    if !values.is_empty() && (field_type.type_name != "String" || is_secret) {
+        return Err(invalid(format!(
+            "values are only supported on plain string fields, '{name}' has type {}",
+            field_type.type_name
+        )));
+    }
+    let pattern = table
+        .get("pattern")
+        .and_then(|v| v.as_str())
+        .map(String::from);
+    if pattern.is_some()
+        && (!matches!(field_type.type_name.as_str(), "String" | "Vec<String>") || is_secret)
+    {
+        return Err(invalid(format!(
+            "pattern is only supported on plain string fields, '{name}' has type {}",
+            field_type.type_name
+        )));
+    }
+    if !values.is_empty() && pattern.is_some() {
+        return Err(invalid(format!(
+            "Field '{id}': values and pattern cannot be combined"
+        )));
+    }
+    let regex = pattern
+        .as_ref()
+        .map(|pattern| {
+            regex::Regex::new(pattern)
+                .map_err(|e| invalid(format!("Invalid pattern for field '{id}': {e}")))
+        })
+        .transpose()?;
+    // `email`, `hostname`... run their own parser, which a pattern would replace.
+    if pattern.is_some() && field_type.value_parser.is_some() {
+        let ty = table
+            .get("type")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default();
+        return Err(invalid(format!(
+            "Field '{id}': pattern cannot be combined with type = \"{ty}\""
+        )));
+    }
+    let check_pattern = |default: &str| match &regex {
+        Some(regex) if !regex.is_match(default) => Err(invalid(format!(
+            "Invalid default for field '{id}': '{default}' does not match the pattern {}",
+            regex.as_str()
+        ))),
+        _ => Ok(()),
+    };
+    let ignore_case = table
+        .get("ignore_case")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if ignore_case && enum_name.is_none() {
+        return Err(invalid(format!(
+            "Field '{id}' sets ignore_case but is not an enum"
+        )));
+    }
+    // TOML integers stop at `i64::MAX`: larger `u64` bounds are given as strings.
+    let bound = |key: &str| match table.get(key) {
+        None => Ok(None),
+        Some(toml::Value::Integer(n)) => Ok(Some(i128::from(*n))),
+        Some(toml::Value::String(s)) if s.trim().parse::<i128>().is_ok() => {
+            Ok(s.trim().parse().ok())
+        }
+        Some(other) => Err(invalid(format!(
+            "Invalid {key} for field '{id}': {other} is not an integer"
+        ))),
+    };
+    let (min, max) = (bound("min")?, bound("max")?);
+    if min.is_some() || max.is_some() {
+        let ty = field_type.type_name.as_str();
+        let Some((lowest, highest, _)) = integer_bounds(ty).filter(|_| INTEGER_TYPES.contains(&ty))
+        else {
+            return Err(invalid(format!(
+                "min/max are only supported on integer fields, '{name}' has type {ty}"
+            )));
+        };
+        if is_secret {
+            return Err(invalid(format!(
+                "min/max are not supported on secret field '{name}'"
+            )));
+        }
+        for (key, bound) in [("min", min), ("max", max)] {
+            if let Some(bound) = bound
+                && !(lowest..=highest).contains(&bound)
+            {
+                return Err(invalid(format!(
+                    "Invalid {key} for field '{id}': {bound} is out of range for {ty} ({lowest}..={highest})"
+                )));
+            }
+        }
+    }
+    if let (Some(min), Some(max)) = (min, max)
+        && min > max
+    {
+        return Err(invalid(format!(
+            "Invalid bounds for field '{id}': min {min} is greater than max {max}"
+        )));
+    }
+    let strict_ports = table
+        .get("strict_ports")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let is_port = table
+        .get("type")
+        .and_then(|v| v.as_str())
+        .is_some_and(|t| t.eq_ignore_ascii_case("port"));
+    // `--color` alone takes `default_missing_value`, `--color never` still sets a value.
+    let default_missing_value = table
+        .get("default_missing_value")
+        .map(|v| v.as_str().map_or_else(|| v.to_string(), String::from));
+    let num_args = table
+        .get("num_args")
+        .map(|v| num_args(&id, v))
+        .transpose()
+        .map_err(invalid)?
+        .or_else(|| default_missing_value.as_ref().map(|_| "0..=1".to_string()));
+    if num_args.is_some()
+        && (field_type.is_vec || field_type.type_name == STRING_MAP || !subtype_fields.is_empty())
+    {
+        return Err(invalid(format!(
+            "Field '{id}': num_args and default_missing_value apply to single-value fields; use min_items and max_items on lists"
+        )));
+    }
+    if field_type.type_name == STRING_MAP {
+        if optional {
+            return Err(invalid(format!(
+                "Map field '{id}' cannot be optional: an empty map means no pairs were given"
+            )));
+        }
+        let default = match table.get("default") {
+            None => Vec::new(),
+            Some(toml::Value::Table(pairs)) => pairs
+                .iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        toml::Value::String(s) => s.clone(),
+                        toml::Value::Table(_) | toml::Value::Array(_) => {
+                            return Err(invalid(format!(
+                                "Invalid default for field '{id}': '{key}' must be a scalar"
+                            )));
+                        }
+                        other => other.to_string(),
+                    };
+                    Ok((key.clone(), value))
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => {
+                return Err(invalid(format!(
+                    "Invalid default for field '{id}': expected a table, e.g. default = {{ env = \"prod\" }}"
+                )));
+            }
+        };
+        let delimiter = delimiter(table, &id, Some(',')).map_err(invalid)?;
+        let variant = GenericSpec::MapSpec(MapField {
+            default,
+            env,
+            long_arg,
+            short_arg,
+            delimiter,
+        });
+        return Ok(
+            Spec::new(toml_tag_name, id, field_type.type_name, doc, variant)
+                .locked(locked)
+                .global(global)
+                .sensitive(sensitive)
+                .cfg(cfg)
+                .long_doc(long_doc)
+                .aliases(aliases)
+                .deprecated(deprecated)
+                .default_if(default_if)
+                .required_if(required_if, required_unless)
+                .overrides_with(overrides_with)
+                .hint(hint)
+                .env_file(env_file),
+        );
+    }
+    let flag = |key: &str| table.get(key).and_then(|v| v.as_bool()).unwrap_or(false);
+    let (trailing, raw) = (flag("trailing"), flag("raw"));
+    if (trailing || raw) && !field_type.is_vec {
+        return Err(invalid(format!(
+            "Field '{id}': trailing and raw apply to list fields"
+        )));
+    }
+    if raw && !trailing {
+        return Err(invalid(format!(
+            "Field '{id}': raw requires trailing = true"
+        )));
+    }
+    if trailing && (long_arg.is_some() || short_arg.is_some()) {
+        return Err(invalid(format!(
+            "Field '{id}': a trailing field is positional and takes no long or short"
+        )));
+    }
+    if field_type.is_vec {
+        let default = table.get("default").cloned();
+        let inner_type = field_type
+            .type_name
+            .trim_start_matches("Vec<")
+            .trim_end_matches('>');
+        let split_paths = table
+            .get("split_paths")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        if split_paths && inner_type != PATH_BUF {
+            return Err(invalid(format!(
+                "Field '{id}': split_paths requires type = \"[path]\""
+            )));
+        }
+        if split_paths && table.contains_key("delimiter") {
+            return Err(invalid(format!(
+                "Field '{id}': split_paths and delimiter cannot be combined"
+            )));
+        }
+        // Without an explicit delimiter, only env values are split, on commas.
+        let delimiter = delimiter(table, &id, env.as_ref().map(|_| ',')).map_err(invalid)?;
+        let items = |key: &str| {
+            table
+                .get(key)
+                .map(|v| {
+                    v.as_integer()
+                        .and_then(|n| usize::try_from(n).ok())
+                        .ok_or_else(|| {
+                            invalid(format!(
+                                "Invalid {key} for field '{id}': expected a non-negative integer"
+                            ))
+                        })
+                })
+                .transpose()
+        };
+        let (min_items, max_items) = (items("min_items")?, items("max_items")?);
+        if let (Some(min), Some(max)) = (min_items, max_items)
+            && min > max
+        {
+            return Err(invalid(format!(
+                "Invalid bounds for field '{id}': min_items {min} is greater than max_items {max}"
+            )));
+        }
+        let items = default.iter().filter_map(|v| v.as_array()).flatten();
+        for item in items {
+            let item = match item {
+                toml::Value::String(s) => s.clone(),
+                toml::Value::Datetime(dt) if inner_type != DATETIME => {
+                    return Err(invalid(format!(
+                        "Invalid default for field '{id}': datetime literal {dt} requires type = \"[datetime]\""
+                    )));
+                }
+                other => other.to_string(),
+            };
+            validate_default(inner_type, field_type.value_parser.as_deref(), &item)
+                .map_err(|e| invalid(format!("Invalid default for field '{id}': {e}")))?;
+            check_pattern(&item)?;
+        }
+        let variant = GenericSpec::VecSpec(VecField {
+            default,
+            env,
+            long_arg,
+            short_arg,
+            optional,
+            value_parser: field_type.value_parser,
+            pattern,
+            split_paths,
+            delimiter,
+            min_items,
+            max_items,
+            trailing,
+            raw,
+        });
+        return Ok(
+            Spec::new(toml_tag_name, id, field_type.type_name, doc, variant)
+                .locked(locked)
+                .global(global)
+                .sensitive(sensitive)
+                .cfg(cfg)
+                .long_doc(long_doc)
+                .aliases(aliases)
+                .deprecated(deprecated)
+                .default_if(default_if)
+                .required_if(required_if, required_unless)
+                .overrides_with(overrides_with)
+                .hint(hint)
+                .env_file(env_file),
+        );
+    }
+    let default = match table.get("default") {
+        Some(toml::Value::Integer(i)) if has_integer_type(&field_type.type_name) => {
+            Some(i.to_string())
+        }
+        Some(toml::Value::Datetime(dt)) if field_type.type_name == DATETIME => Some(dt.to_string()),
+        Some(toml::Value::Datetime(dt)) => {
+            return Err(invalid(format!(
+                "Invalid default for field '{id}': datetime literal {dt} requires type = \"datetime\""
+            )));
+        }
+        other => other.and_then(|v| v.as_str()).map(String::from),
+    };
+    if let Some(default) = &default
+        && let Err(e) = validate_default(
+            &field_type.type_name,
+            field_type.value_parser.as_deref(),
+            default,
+        )
+    {
+        return Err(invalid(format!("Invalid default for field '{id}': {e}")));
+    }
+    if let Some(default) = &default {
+        check_pattern(default)?;
+    }
+    if let Some(default) = &default
+        && !values.is_empty()
+        && !values.contains(default)
+    {
+        return Err(invalid(format!(
+            "Invalid default for field '{id}': '{default}' is not one of {values:?}"
+        )));
+    }
+    if let Some(value) = default
+        .as_deref()
+        .and_then(|d| d.trim().parse::<i128>().ok())
+    {
+        match (min, max) {
+            (Some(min), _) if value < min => {
+                return Err(invalid(format!(
+                    "Invalid default for field '{id}': {value} is below min {min}"
+                )));
+            }
+            (_, Some(max)) if value > max => {
+                return Err(invalid(format!(
+                    "Invalid default for field '{id}': {value} is above max {max}"
+                )));
+            }
+            _ => {}
+        }
+    }
+    if let Some(enum_name) = &enum_name {
+        validate_enum(&id, enum_name, &enum_values, default.as_deref()).map_err(invalid)?;
+    }
+    let variant = if subtype_fields.is_empty() && field_type.is_native {
+        GenericSpec::FieldSpec(Field {
+            default,
+            env,
+            long_arg,
+            short_arg,
+            optional,
+            is_secret,
+            min,
+            max,
+            values,
+            value_parser: if is_port {
+                Some(port_value_parser(strict_ports, min, max))
+            } else {
+                field_type.value_parser.clone()
+            },
+            pattern,
+        })
+    } else if !subtype_fields.is_empty() {
+        GenericSpec::SubtypeSpec(SubField(subtype_fields.clone()))
+    } else {
+        match enum_name {
+            Some(enum_name) => GenericSpec::EnumSpec(EnumField {
                 env,
                 long_arg,
                 short_arg,
@@ -157,15 +1734,60 @@ fn table_to_field_spec(
                 enum_name,
                 variants: enum_values.iter().map(|s| s.to_string()).collect(),
                 default,
+                ignore_case,
             }),
             None => GenericSpec::ExternalSpec(ExternalStruct {
                 long_arg,
                 short_arg,
+                section: table.get("external").is_some_and(|v| v.is_str()),
+                args: table.get("external").and_then(|v| v.as_bool()) == Some(true),
+                requires: match table.get("requires") {
+                    Some(toml::Value::Array(arr)) => arr
+                        .iter()
+                        .filter_map(|val| val.as_str())
+                        .map(|field| (field.to_string(), None))
+                        .collect(),
+                    Some(toml::Value::Table(types)) => types
+                        .iter()
+                        .filter_map(|(field, ty)| {
+                            Some((field.clone(), Some(ty.as_str()?.replace(' ', ""))))
+                        })
+                        .collect(),
+                    _ => vec![],
+                },
             }),
         }
     };
 
-    Spec::new(toml_tag_name, id, field_type.type_name, doc, variant)
+    // A section is headed by its name, `http_server` by "Http Server", unless it sets `heading`.
+    let heading = table
+        .get("heading")
+        .and_then(|v| v.as_str())
+        .map(String::from)
+        .unwrap_or_else(|| {
+            name.split('_')
+                .map(utils::to_pascal_case)
+                .collect::<Vec<_>>()
+                .join(" ")
+        });
+    Ok(
+        Spec::new(toml_tag_name, id, field_type.type_name, doc, variant)
+            .optional_section(optional)
+            .locked(locked)
+            .global(global)
+            .sensitive(sensitive)
+            .cfg(cfg)
+            .long_doc(long_doc)
+            .aliases(aliases)
+            .deprecated(deprecated)
+            .default_if(default_if)
+            .required_if(required_if, required_unless)
+            .overrides_with(overrides_with)
+            .hint(hint)
+            .env_file(env_file)
+            .num_args(num_args, default_missing_value)
+            .heading(Some(heading)),
+    )
 }
 
 #[cfg(test)]
@@ -204,6 +1826,11 @@ mod tests {
     }
 
     // Helper function to create a temporary TOML file
+    /// Panics with the problems of a spec, as the macro does.
+    fn panic_on(problems: Vec<Problem>) -> ConfigSpec {
+        panic!("{}", validate::messages(&problems))
+    }
+
     fn create_temp_toml(content: &str) -> (TempDir, PathBuf) {
         let temp_dir = TempDir::new().unwrap();
         let file_path = temp_dir.path().join("test_config.toml");
@@ -212,165 +1839,1842 @@ mod tests {
     }
 
     #[test]
-    fn test_simple_field_parsing() {
+    fn test_simple_field_parsing() {
+        let toml_content = r#"
+        port = { type = "int", default = "8080", doc = "Server port", env = "PORT" }
+        name = { type = "String", default = "test", long = "name", short = "n" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+
+        assert_eq!(config_spec.fields.len(), 2);
+
+        let port_field = config_spec.get_field("port").unwrap();
+
+        assert_eq!(port_field.name, "port");
+        assert_eq!(port_field.field_type, "i64");
+        assert_eq!(port_field.doc, Some("Server port".to_string()));
+        assert_eq!(port_field.id, ".port");
+        let port = port_field.as_field_spec();
+
+        assert_eq!(port.default, Some("8080".to_string()));
+        assert_eq!(port.env, Some("PORT".to_string()));
+        assert_eq!(port.long_arg, None);
+        assert_eq!(port.short_arg, None);
+
+        let name_field = config_spec.get_field("name").unwrap();
+
+        assert_eq!(name_field.name, "name");
+        assert_eq!(name_field.field_type, "String");
+
+        let name = name_field.as_field_spec();
+
+        assert_eq!(name.default, Some("test".to_string()));
+        assert_eq!(name.env, None);
+        assert_eq!(name.long_arg, Some("name".to_string()));
+        assert_eq!(name.short_arg, Some('n'));
+    }
+
+    #[test]
+    fn test_nested_field_parsing() {
+        let toml_content = r#"
+        [database]
+        type = "DatabaseConfig"
+        doc = "Database configuration"
+
+        [database.host]
+        default = "localhost"
+        env = "DB_HOST"
+        
+        [database.port]
+        type = "int"
+        default = "5432"
+        env = "DB_PORT"
+        "#;
+
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+        assert_eq!(config_spec.fields.len(), 1);
+
+        // Test database field
+        let db_field = &config_spec.fields[0];
+        assert_eq!(db_field.name, "database");
+        assert_eq!(db_field.field_type, "DatabaseConfig");
+        assert_eq!(db_field.id, ".database");
+
+        // Extract and test database subtype
+
+        let fields = db_field.as_subtype_spec();
+        assert_eq!(fields.len(), 2);
+
+        // Test host field
+        let host_field = &fields[0];
+        assert_eq!(host_field.name, "host");
+        assert_eq!(host_field.field_type, "String");
+        assert_eq!(host_field.id, ".database.host");
+
+        let host = host_field.as_field_spec();
+
+        assert_eq!(host.default, Some("localhost".to_string()));
+        assert_eq!(host.env, Some("DB_HOST".to_string()));
+        assert_eq!(host.long_arg, None);
+        assert_eq!(host.short_arg, None);
+
+        // Test port field
+        let port_field = &fields[1];
+        assert_eq!(port_field.name, "port");
+        assert_eq!(port_field.field_type, "i64");
+        assert_eq!(port_field.id, ".database.port");
+
+        let port = port_field.as_field_spec();
+        assert_eq!(port.default, Some("5432".to_string()));
+        assert_eq!(port.env, Some("DB_PORT".to_string()));
+        assert_eq!(port.long_arg, None);
+        assert_eq!(port.short_arg, None);
+    }
+
+    #[test]
+    fn test_deep_nested_structure() {
+        let toml_content = r#"
+        [app]
+        type = "AppConfig"
+
+        [app.server]
+        type = "ServerConfig"
+        
+            [app.server.http]
+            type = "HttpConfig"
+            port = { type = "int", default = "8080" }
+            host = {  default = "localhost" }
+            
+            [app.server.tls]
+            type = "TlsConfig"
+            cert = {  env = "TLS_CERT" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+
+        assert_eq!(config_spec.fields.len(), 1);
+
+        let app_field = config_spec.get_field("app").unwrap();
+        assert_eq!(app_field.name, "app");
+        assert_eq!(app_field.field_type, "AppConfig");
+
+        let fields = app_field.as_subtype_spec();
+        let server_field = get_field(fields, "server").unwrap();
+        assert_eq!(server_field.name, "server");
+        assert_eq!(server_field.field_type, "ServerConfig");
+        assert_eq!(server_field.id, ".app.server");
+
+        let fields = server_field.as_subtype_spec();
+        assert_eq!(fields.len(), 2);
+
+        let http_field = &fields[0];
+        assert_eq!(http_field.name, "http");
+        assert_eq!(http_field.field_type, "HttpConfig");
+        assert_eq!(http_field.id, ".app.server.http");
+
+        let fields = http_field.as_subtype_spec();
+        assert_eq!(fields.len(), 2);
+
+        let port_field = get_field(fields, "port").unwrap();
+        assert_eq!(port_field.name, "port");
+        assert_eq!(port_field.id, ".app.server.http.port");
+    }
+
+    #[test]
+    fn test_optional_fields() {
+        let toml_content = r#"
+        port = { type = "int", default = "8080", optional = true }
+        host = { type = "String", env = "HOST" }
+        debug = { type = "bool", optional = false }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+
+        assert_eq!(config_spec.fields.len(), 3);
+
+        let port_field = config_spec.get_field("port").unwrap().as_field_spec();
+        assert!(port_field.optional);
+
+        let host_field = config_spec.get_field("host").unwrap().as_field_spec();
+        assert!(!host_field.optional); // Not specified
+
+        let debug_field = config_spec.get_field("debug").unwrap().as_field_spec();
+        assert!(!debug_field.optional);
+    }
+
+    #[test]
+    #[should_panic(expected = "min 2000 is greater than max 1000")]
+    fn test_range_min_above_max() {
+        let toml_content = r#"
+        workers = { type = "u16", min = 2000, max = 1000 }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    fn test_range_bounds_of_type() {
+        let problem = |toml_content: &str| {
+            let spec: GenericConfigSpec = toml::from_str(toml_content).unwrap();
+            let problems = ConfigSpec::try_from(spec.with_struct_name("app".to_string()))
+                .map(drop)
+                .unwrap_err();
+            problems[0].message.clone()
+        };
+        assert_eq!(
+            problem(r#"retries = { type = "u32", min = -1 }"#),
+            "Invalid min for field 'app.retries': -1 is out of range for u32 (0..=4294967295)"
+        );
+        assert_eq!(
+            problem(r#"level = { type = "i8", max = 200 }"#),
+            "Invalid max for field 'app.level': 200 is out of range for i8 (-128..=127)"
+        );
+        assert_eq!(
+            problem(r#"level = { type = "u8", max = "many" }"#),
+            "Invalid max for field 'app.level': \"many\" is not an integer"
+        );
+
+        let toml_content = r#"
+        bytes = { type = "u64", min = 1, max = "18446744073709551615" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+        let bytes = config_spec.get_field("bytes").unwrap().as_field_spec();
+        assert_eq!(bytes.min, Some(1));
+        assert_eq!(bytes.max, Some(u64::MAX.into()));
+    }
+
+    #[test]
+    #[should_panic(expected = "5 is below min 1000")]
+    fn test_range_default_outside() {
+        let toml_content = r#"
+        workers = { type = "u16", min = 1000, max = 2000, default = 5 }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    fn test_range_fields() {
+        let toml_content = r#"
+        port = { type = "u16", min = 1024, max = 65535 }
+        workers = { type = "usize", min = 1 }
+        name = { type = "String" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+
+        let port_field = config_spec.get_field("port").unwrap();
+        assert_eq!(port_field.field_type, "u16");
+        let port = port_field.as_field_spec();
+        assert_eq!(port.min, Some(1024));
+        assert_eq!(port.max, Some(65535));
+
+        let workers = config_spec.get_field("workers").unwrap().as_field_spec();
+        assert_eq!(workers.min, Some(1));
+        assert_eq!(workers.max, None);
+
+        let name = config_spec.get_field("name").unwrap().as_field_spec();
+        assert_eq!(name.min, None);
+        assert_eq!(name.max, None);
+    }
+
+    #[test]
+    fn test_timezone_field() {
+        let toml_content = r#"
+        zone = { type = "timezone", default = "UTC" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+
+        let zone_field = config_spec.get_field("zone").unwrap();
+        assert_eq!(zone_field.field_type, TIMEZONE);
+        assert_eq!(zone_field.as_field_spec().default, Some("UTC".to_string()));
+        assert!(zone_field.as_field_spec().value_parser.is_some());
+    }
+
+    #[test]
+    fn test_lang_field() {
+        let toml_content = r#"
+        lang = { type = "lang", default = "en-US" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+
+        let lang_field = config_spec.get_field("lang").unwrap();
+        assert_eq!(lang_field.field_type, LANGUAGE_ID);
+        assert!(!has_literal_default(&lang_field.field_type));
+    }
+
+    #[test]
+    fn test_allowed_values() {
+        let toml_content = r#"
+        format = { default = "json", values = ["json", "text", "yaml"] }
+        name = { default = "test" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+
+        let format = config_spec.get_field("format").unwrap().as_field_spec();
+        assert_eq!(format.values, vec!["json", "text", "yaml"]);
+
+        let name = config_spec.get_field("name").unwrap().as_field_spec();
+        assert!(name.values.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid values for field '.level': 1 is not a string")]
+    fn test_values_not_strings() {
+        let toml_content = r#"
+        level = { values = ["a", 1] }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid default for field '.level': 'zzz' is not one of")]
+    fn test_default_not_in_values() {
+        let toml_content = r#"
+        level = { values = ["a", "b"], default = "zzz" }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    #[should_panic(expected = "Field '.level': values and pattern cannot be combined")]
+    fn test_values_with_pattern() {
+        let toml_content = r#"
+        level = { values = ["a", "b"], pattern = "^[a-z]$" }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    fn test_validated_string_fields() {
+        let toml_content = r#"
+        alert = { type = "email", default = "ops@example.com" }
+        host = { type = "hostname", default = "localhost" }
+        recipients = { type = "[email]" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+
+        let alert = config_spec.get_field("alert").unwrap();
+        assert_eq!(alert.field_type, "String");
+        assert_eq!(
+            alert.as_field_spec().value_parser,
+            Some("rclap::parsers::email".to_string())
+        );
+
+        let host = config_spec.get_field("host").unwrap();
+        assert_eq!(
+            host.as_field_spec().value_parser,
+            Some("rclap::parsers::hostname".to_string())
+        );
+
+        let recipients = config_spec.get_field("recipients").unwrap();
+        assert_eq!(recipients.field_type, "Vec<String>");
+        match &recipients.variant {
+            GenericSpec::VecSpec(v) => {
+                assert_eq!(v.value_parser, Some("rclap::parsers::email".to_string()))
+            }
+            _ => panic!("Not a VecSpec variant"),
+        }
+    }
+
+    #[test]
+    fn test_pattern_field() {
+        let toml_content = r#"
+        slug = { default = "my-app", pattern = "^[a-z0-9-]+$" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+
+        let slug = config_spec.get_field("slug").unwrap().as_field_spec();
+        assert_eq!(slug.pattern, Some("^[a-z0-9-]+$".to_string()));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Invalid default for field '.slug': 'My App' does not match the pattern"
+    )]
+    fn test_default_not_matching_pattern() {
+        let toml_content = r#"
+        slug = { default = "My App", pattern = "^[a-z0-9-]+$" }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    #[should_panic(expected = "Field '.alert': pattern cannot be combined with type = \"email\"")]
+    fn test_pattern_on_email() {
+        let toml_content = r#"
+        alert = { type = "email", pattern = "@example\\.com$" }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid pattern for field '.slug'")]
+    fn test_invalid_pattern() {
+        let toml_content = r#"
+        slug = { pattern = "^[a-z" }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    fn test_port_field() {
+        let toml_content = r#"
+        http = { type = "port", default = "8080" }
+        admin = { type = "port", min = 1024, strict_ports = true }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+
+        let http = config_spec.get_field("http").unwrap();
+        assert_eq!(http.field_type, "u16");
+        assert_eq!(
+            http.as_field_spec().value_parser,
+            Some("rclap::parsers::PortValueParser::new()".to_string())
+        );
+
+        let admin = config_spec.get_field("admin").unwrap().as_field_spec();
+        assert_eq!(
+            admin.value_parser,
+            Some("rclap::parsers::PortValueParser::new().range(1024..).strict(true)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_bytesize_field() {
+        let toml_content = r#"
+        cache = { type = "bytesize", default = "10MB" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+
+        let cache = config_spec.get_field("cache").unwrap();
+        assert_eq!(cache.field_type, "u64");
+        let cache = cache.as_field_spec();
+        assert_eq!(cache.default, Some("10MB".to_string()));
+        assert_eq!(
+            cache.value_parser,
+            Some("rclap::parsers::byte_size".to_string())
+        );
+    }
+
+    #[test]
+    fn test_cidr_fields() {
+        let toml_content = r#"
+        allow = { type = "[cidr]", default = ["10.0.0.0/8"], env = "ALLOW" }
+        gateway = { type = "cidr" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+
+        let allow = config_spec.get_field("allow").unwrap();
+        assert_eq!(allow.field_type, format!("Vec<{IP_NET}>"));
+
+        let gateway = config_spec.get_field("gateway").unwrap();
+        assert_eq!(gateway.field_type, IP_NET);
+        assert_eq!(
+            gateway.as_field_spec().value_parser,
+            Some("rclap::parsers::cidr".to_string())
+        );
+    }
+
+    #[test]
+    fn test_url_field() {
+        let toml_content = r#"
+        endpoint = { type = "url", default = "https://api.example.com/v1" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+
+        let endpoint = config_spec.get_field("endpoint").unwrap();
+        assert_eq!(endpoint.field_type, URL);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid default for field '.endpoint'")]
+    fn test_invalid_url_default() {
+        let toml_content = r#"
+        endpoint = { type = "url", default = "not a url" }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    fn test_markdown_help() {
+        let toml_content = r#"
+        args = { type = "[string]", trailing = true, doc = "Command to run" }
+        old = { deprecated = "use --new", doc = "Old flag" }
+        [database.replica]
+        url = { default = "db:5433", sensitive = true, env = "REPLICA_URL", doc = "Replica\nURL" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "config");
+        assert_eq!(
+            config_spec.markdown_help(),
+            "\
+## Options
+
+| Flag | Env | Type | Default | Description |
+|---|---|---|---|---|
+| `<args>...` |  | `Vec<String>` |  | Command to run |
+
+## Replica
+
+| Flag | Env | Type | Default | Description |
+|---|---|---|---|---|
+| `--config.database.replica.url` | `REPLICA_URL` | `String` |  | Replica URL |
+"
+        );
+    }
+
+    #[test]
+    fn test_man_page_and_json_docs() {
+        let toml_content = r#"
+        port = { type = "u16", default = "8080", short = "p", env = "PORT", doc = "Listen port" }
+        [app]
+        about = "A server"
+        [log]
+        level = { values = ["info", "debug"], doc = "Log \"level\"" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "config");
+        assert_eq!(
+            config_spec.man_page("my-server"),
+            r#".TH MY\-SERVER 1
+.SH NAME
+my\-server \- A server
+.SH OPTIONS
+.TP
+\fB\-\-config.port\fR, \fB\-p\fR \fI<u16>\fR
+Listen port
+.br
+Env: PORT. Default: 8080.
+.SS "Log"
+.TP
+\fB\-\-config.log.level\fR \fI<String>\fR
+Log "level"
+.br
+One of: info, debug.
+"#
+        );
+        assert_eq!(
+            config_spec.json_docs(),
+            concat!(
+                r#"[{"section":null,"flag":"--config.port","short":"p","env":"PORT","type":"u16","values":[],"default":"8080","description":"Listen port"},"#,
+                r#"{"section":"Log","flag":"--config.log.level","short":null,"env":null,"type":"String","values":["info","debug"],"default":null,"description":"Log \"level\""}]"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_config_template() {
+        let toml_content = r#"
+        port = { type = "u16", default = "8080", doc = "Listen port", env = "PORT" }
+        hosts = { type = "[string]", default = ["a", "b"] }
+        token = { default = "dev", secret = true }
+        [database.replica]
+        url = { optional = true, doc = "Replica URL" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "config");
+        assert_eq!(
+            config_spec.config_template(),
+            r#"# hosts = ["a", "b"]
+
+# Listen port
+# Env: PORT
+# port = 8080
+
+# token =
+
+[database.replica]
+# Replica URL
+# url =
+"#
+        );
+    }
+
+    #[test]
+    fn test_env_example() {
+        let toml_content = r#"
+        port = { type = "u16", default = "8080", doc = "Listen port", env = "PORT" }
+        greeting = { default = "hello world", env = "GREETING" }
+        token = { default = "dev", secret = true, env = "TOKEN", env_file = "TOKEN_FILE" }
+        local = { default = "x" }
+        [database]
+        hosts = { type = "[string]", default = ["a", "b"], env = "DB_HOSTS" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "config");
+        assert_eq!(
+            config_spec.env_example(),
+            r#"# GREETING="hello world"
+
+# Listen port
+# PORT=8080
+
+# TOKEN=
+# TOKEN_FILE=
+
+# DB_HOSTS=a,b
+"#
+        );
+    }
+
+    #[test]
+    fn test_env_file() {
+        let toml_content = r#"
+        password = { env = "DB_PASSWORD", env_file = "DB_PASSWORD_FILE" }
+        [app]
+        env_prefix = "APP_"
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "config");
+        let password = config_spec.get_field("password").unwrap();
+        assert_eq!(password.env_file.as_deref(), Some("APP_DB_PASSWORD_FILE"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Section 'config.database' cannot set env_file")]
+    fn test_env_file_on_section() {
+        let toml_content = r#"
+        [database]
+        env_file = "DB_FILE"
+        url = { default = "localhost" }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "config");
+    }
+
+    #[test]
+    fn test_config_file_lookup() {
+        let toml_content = r#"
+        port = { type = "int", default = "8080" }
+        [app]
+        config_file = { name = "mytool.toml", lookup = "xdg" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "config");
+        let config_file = &config_spec.app.config_file;
+        assert!(config_file.enabled);
+        assert_eq!(config_file.name.as_deref(), Some("mytool.toml"));
+        assert_eq!(config_file.lookup.as_deref(), Some("xdg"));
+        assert_eq!(config_spec.app.sources(), ["cli", "env", "file", "default"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown config_file lookup 'etc'")]
+    fn test_config_file_unknown_lookup() {
+        let toml_content = r#"
+        port = { type = "int", default = "8080" }
+        [app]
+        config_file = { name = "mytool.toml", lookup = "etc" }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "config");
+    }
+
+    #[test]
+    #[should_panic(expected = "watch = true needs config_file in [app]")]
+    fn test_watch_without_config_file() {
+        let toml_content = r#"
+        port = { type = "int", default = "8080" }
+        [app]
+        watch = true
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "config");
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid [app] table: unknown field `verison`")]
+    fn test_app_unknown_key() {
+        let toml_content = r#"
+        port = { type = "int", default = "8080" }
+        [app]
+        name = "mytool"
+        verison = "1.0"
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "config");
+    }
+
+    #[test]
+    fn test_problems() {
+        let toml_content = r#"
+        port = { type = "u16", default = "80x" }
+        host = { default_if = [{ field = "port", equals = "1", value = "a" }] }
+        [database]
+        url = { hint = "nope" }
+        pool = { type = "u8", min = 5, max = 1 }
+        "#;
+        let spec: GenericConfigSpec = toml::from_str(toml_content).unwrap();
+        let problems = ConfigSpec::try_from(spec.with_struct_name("app".to_string())).unwrap_err();
+        let fields: Vec<_> = problems.iter().map(|p| p.field.as_deref()).collect();
+        assert_eq!(
+            fields,
+            [Some("database.pool"), Some("database.url"), Some("port")]
+        );
+        assert_eq!(
+            problems[2].message,
+            "Invalid default for field 'app.port': '80x' is not an integer"
+        );
+    }
+
+    #[test]
+    fn test_section_headings() {
+        let toml_content = r#"
+        port = { type = "int", default = "8080" }
+        [http_server]
+        timeout = { type = "int", default = "30" }
+        [database]
+        heading = "Storage"
+        url = { default = "localhost:5432" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+
+        assert_eq!(config_spec.get_field("port").unwrap().heading, None);
+        let http_server = config_spec.get_field("http_server").unwrap();
+        assert_eq!(http_server.heading.as_deref(), Some("Http Server"));
+        let database = config_spec.get_field("database").unwrap();
+        assert_eq!(database.heading.as_deref(), Some("Storage"));
+    }
+
+    #[test]
+    fn test_app_table() {
+        let toml_content = r#"
+        port = { type = "int", default = "8080" }
+        [app]
+        error_json = true
+        config_file = true
+        derives = ["Eq", "Hash"]
+        name = "myapp"
+        version = "1.0.0"
+        [app.style]
+        header = "bold green"
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+
+        assert_eq!(config_spec.fields.len(), 1);
+        assert!(config_spec.app.error_json);
+        assert!(config_spec.app.config_file.enabled);
+        assert_eq!(config_spec.app.derives, ["Eq", "Hash"]);
+        assert_eq!(config_spec.app.name.as_deref(), Some("myapp"));
+        assert_eq!(config_spec.app.style.styles(), [("header", "bold green")]);
+        assert_eq!(config_spec.app.version.as_deref(), Some("1.0.0"));
+
+        let toml_content = r#"
+        [app]
+        type = "AppConfig"
+        port = { type = "int", default = "8080" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+
+        assert_eq!(config_spec.fields.len(), 1);
+        assert!(!config_spec.app.error_json);
+
+        let toml_content = r#"
+        [rclap]
+        name = "myapp"
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+        assert!(config_spec.fields.is_empty());
+        assert_eq!(config_spec.app.name.as_deref(), Some("myapp"));
+    }
+
+    #[test]
+    fn test_env_prefix() {
+        let toml_content = r#"
+        port = { type = "int", default = "8080", env = "PORT" }
+        name = { default = "svc" }
+        [rclap]
+        env_prefix = "MYAPP_"
+        [database]
+        url = { default = "localhost", env = "DB_URL" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+
+        assert_eq!(config_spec.app.env_prefix.as_deref(), Some("MYAPP_"));
+        let env = |spec: &Spec| match &spec.variant {
+            GenericSpec::FieldSpec(f) => f.env.clone(),
+            _ => panic!("Expected FieldSpec"),
+        };
+        assert_eq!(
+            env(config_spec.get_field("port").unwrap()).as_deref(),
+            Some("MYAPP_PORT")
+        );
+        assert_eq!(env(config_spec.get_field("name").unwrap()), None);
+        let database = config_spec.get_field("database").unwrap().as_subtype_spec();
+        assert_eq!(
+            env(get_field(database, "url").unwrap()).as_deref(),
+            Some("MYAPP_DB_URL")
+        );
+    }
+
+    #[test]
+    fn test_auto_env() {
+        let toml_content = r#"
+        port = { type = "int", default = "8080" }
+        name = { default = "svc", env = "SERVICE_NAME" }
+        debug = { type = "bool", default = "false", auto_env = false }
+        [rclap]
+        auto_env = true
+        env_prefix = "MYAPP_"
+        [database.primary]
+        url = { default = "localhost" }
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "app");
+
+        let env = |spec: &Spec| match &spec.variant {
+            GenericSpec::FieldSpec(f) => f.env.clone(),
+            _ => panic!("Expected FieldSpec"),
+        };
+        assert_eq!(
+            env(config_spec.get_field("port").unwrap()).as_deref(),
+            Some("MYAPP_PORT")
+        );
+        assert_eq!(
+            env(config_spec.get_field("name").unwrap()).as_deref(),
+            Some("MYAPP_SERVICE_NAME")
+        );
+        assert_eq!(env(config_spec.get_field("debug").unwrap()), None);
+        let database = config_spec.get_field("database").unwrap().as_subtype_spec();
+        let primary = get_field(database, "primary").unwrap().as_subtype_spec();
+        assert_eq!(
+            env(get_field(primary, "url").unwrap()).as_deref(),
+            Some("MYAPP_DATABASE_PRIMARY_URL")
+        );
+    }
+
+    #[test]
+    fn test_precedence() {
+        let toml_content = r#"
+        port = { type = "int", default = "8080", env = "PORT" }
+        [app]
+        config_file = true
+        precedence = ["cli", "file", "env", "default"]
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+        assert_eq!(config_spec.app.sources(), ["cli", "file", "env", "default"]);
+
+        let toml_content = r#"
+        port = { type = "int", default = "8080", env = "PORT" }
+        [app]
+        config_file = true
+        "#;
+        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+        assert_eq!(config_spec.app.sources(), ["cli", "env", "file", "default"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "only \"env\" and \"file\" can be reordered")]
+    fn test_precedence_moving_cli() {
+        let toml_content = r#"
+        port = { type = "int", default = "8080", env = "PORT" }
+        [app]
+        precedence = ["env", "cli", "default"]
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    #[should_panic(expected = "precedence must list \"env\"")]
+    fn test_precedence_without_env() {
+        let toml_content = r#"
+        port = { type = "int", default = "8080", env = "PORT" }
+        [app]
+        config_file = true
+        precedence = ["cli", "file", "default"]
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    #[should_panic(expected = "config_file is not enabled")]
+    fn test_precedence_without_file() {
+        let toml_content = r#"
+        port = { type = "int", default = "8080", env = "PORT" }
+        [app]
+        precedence = ["cli", "file", "env", "default"]
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a valid UUID")]
+    fn test_invalid_uuid_default() {
+        let toml_content = r#"
+        tenant = { type = "uuid", default = "67e55044-10b1-426f" }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    fn test_integer_width_fields() {
+        let toml_content = r#"
+        port = { type = "u16", default = 8080 }
+        retries = { type = "nonzero_u8", default = "3" }
+        offsets = { type = "[i32]", default = [-1, 2] }
+        workers = { type = "[nonzero_usize]", default = [1, 4] }
+        "#;
+        let config = ConfigSpec::load_toml_config(toml_content, "");
+        let field = |name: &str| config.fields.iter().find(|f| f.name == name).unwrap();
+        assert_eq!(field("port").field_type, "u16");
+        assert_eq!(field("retries").field_type, "std::num::NonZeroU8");
+        assert_eq!(field("offsets").field_type, "Vec<i32>");
+        assert_eq!(field("workers").field_type, "Vec<std::num::NonZeroUsize>");
+        let GenericSpec::FieldSpec(port) = &field("port").variant else {
+            panic!("Expected FieldSpec");
+        };
+        assert_eq!(port.default.as_deref(), Some("8080"));
+    }
+
+    #[test]
+    #[should_panic(expected = "is out of range for u8")]
+    fn test_integer_default_out_of_range() {
+        let toml_content = r#"
+        level = { type = "[u8]", default = [1, 300] }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    #[should_panic(expected = "is not allowed for std::num::NonZeroU16")]
+    fn test_nonzero_default_zero() {
+        let toml_content = r#"
+        port = { type = "nonzero_u16", default = "0" }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    fn test_datetime_field() {
+        let toml_content = r#"
+        since = { type = "datetime", default = 2024-01-31T08:30:00Z }
+        day = { type = "datetime", default = "2024-01-31" }
+        dates = { type = "[datetime]", default = [2024-01-31, "2024-02-29"] }
+        "#;
+        let config = ConfigSpec::load_toml_config(toml_content, "");
+        let field = |name: &str| config.fields.iter().find(|f| f.name == name).unwrap();
+        assert_eq!(field("since").field_type, DATETIME);
+        assert_eq!(field("dates").field_type, format!("Vec<{DATETIME}>"));
+        let GenericSpec::FieldSpec(since) = &field("since").variant else {
+            panic!("Expected FieldSpec");
+        };
+        assert_eq!(since.default.as_deref(), Some("2024-01-31T08:30:00Z"));
+    }
+
+    #[test]
+    #[should_panic(expected = "datetime literal 2024-01-31 requires type = \"datetime\"")]
+    fn test_datetime_default_on_string_field() {
+        let toml_content = r#"
+        since = { default = 2024-01-31 }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a valid datetime")]
+    fn test_invalid_datetime_default() {
+        let toml_content = r#"
+        since = { type = "datetime", default = "yesterday" }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    fn test_map_field() {
+        let toml_content = r#"
+        labels = { type = "map", default = { env = "prod", replicas = 3 }, env = "LABELS", delimiter = ";" }
+        "#;
+        let config = ConfigSpec::load_toml_config(toml_content, "");
+        let labels = config.get_field("labels").unwrap();
+        assert_eq!(labels.field_type, STRING_MAP);
+        let GenericSpec::MapSpec(map) = &labels.variant else {
+            panic!("Expected MapSpec");
+        };
+        assert_eq!(
+            map.default,
+            vec![
+                ("env".to_string(), "prod".to_string()),
+                ("replicas".to_string(), "3".to_string())
+            ]
+        );
+        assert_eq!(map.env.as_deref(), Some("LABELS"));
+        assert_eq!(map.delimiter, Some(';'));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot be optional")]
+    fn test_optional_map_field() {
+        let toml_content = r#"
+        labels = { type = "map", optional = true }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    fn test_ignore_case_enum() {
+        let toml_content = r#"
+        level = { enum = "Level", variants = ["Debug", "Info"], default = "Info", ignore_case = true }
+        format = { enum = "Format", variants = ["Json", "Text"], default = "Text" }
+        "#;
+        let config = ConfigSpec::load_toml_config(toml_content, "");
+        let ignore_case = |name: &str| match &config.get_field(name).unwrap().variant {
+            GenericSpec::EnumSpec(e) => e.ignore_case,
+            _ => panic!("expected an enum"),
+        };
+        assert!(ignore_case("level"));
+        assert!(!ignore_case("format"));
+    }
+
+    #[test]
+    fn test_cfg_fields() {
+        let toml_content = r#"
+        port = { type = "u16", default = 8080 }
+        metrics_port = { type = "u16", default = 9100, cfg = 'feature = "metrics"' }
+        [tracing]
+        cfg = 'not(feature = "metrics")'
+        endpoint = { default = "http://localhost:4317" }
+        "#;
+        let config = ConfigSpec::load_toml_config(toml_content, "");
+        let cfg = |name: &str| config.get_field(name).unwrap().cfg.clone();
+        assert_eq!(cfg("port"), None);
+        assert_eq!(
+            cfg("metrics_port").as_deref(),
+            Some(r#"feature = "metrics""#)
+        );
+        assert_eq!(
+            cfg("tracing").as_deref(),
+            Some(r#"not(feature = "metrics")"#)
+        );
+        let GenericSpec::SubtypeSpec(tracing) = &config.get_field("tracing").unwrap().variant
+        else {
+            panic!("expected a section");
+        };
+        assert_eq!(tracing.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "sets ignore_case but is not an enum")]
+    fn test_ignore_case_non_enum() {
+        let toml_content = r#"
+        name = { default = "a", ignore_case = true }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    fn test_external_enum_path() {
+        let toml_content = r#"
+        qualified = { enum = "crate::cli::Format", default = "Json" }
+        bare = { enum = "Format" }
+        inline = { enum = "Level", variants = ["Debug", "Info"], default = "Info" }
+        "#;
+        let config = ConfigSpec::load_toml_config(toml_content, "");
+        let field_type = |name: &str| config.get_field(name).unwrap().field_type.as_str();
+        assert_eq!(field_type("qualified"), "crate::cli::Format");
+        assert_eq!(field_type("bare"), "super::Format");
+        assert_eq!(field_type("inline"), "Level");
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a valid Rust path")]
+    fn test_external_enum_invalid_path() {
+        let toml_content = r#"
+        format = { enum = "crate::my-format", default = "Json" }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a variant of Level")]
+    fn test_inline_enum_unknown_default() {
+        let toml_content = r#"
+        level = { enum = "Level", variants = ["Debug", "Info"], default = "Trace" }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    #[should_panic(expected = "both use type ConnectionConfig but declare different fields")]
+    fn test_reused_section_type_mismatch() {
+        let toml_content = r#"
+        [primary]
+        type = "ConnectionConfig"
+        url = { default = "a" }
+        [replica]
+        type = "ConnectionConfig"
+        url = { default = "b", optional = true }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    fn test_extends_section() {
+        let toml_content = r#"
+        [database.primary]
+        type = "ConnectionConfig"
+        url = { default = "primary:5432", env = "PRIMARY_URL" }
+        pool_size = { type = "u32", default = 10 }
+
+        [database.replica]
+        extends = "database.primary"
+        url = { env = "REPLICA_URL" }
+
+        [database.analytics]
+        extends = "database.replica"
+        pool_size = { default = 2 }
+        "#;
+        let config = ConfigSpec::load_toml_config(toml_content, "");
+        let database = config.get_field("database").unwrap().as_subtype_spec();
+        let field = |section: &str, name: &str| {
+            let section = get_field(database, section).unwrap().as_subtype_spec();
+            get_field(section, name).unwrap().clone()
+        };
+
+        let url = field("replica", "url");
+        assert_eq!(url.as_field_spec().env.as_deref(), Some("REPLICA_URL"));
+        assert_eq!(url.as_field_spec().default.as_deref(), Some("primary:5432"));
+        assert_eq!(field("replica", "pool_size").field_type, "u32");
+
+        let pool_size = field("analytics", "pool_size");
+        assert_eq!(pool_size.as_field_spec().default.as_deref(), Some("2"));
+        assert_eq!(pool_size.field_type, "u32");
+        let url = field("analytics", "url");
+        assert_eq!(url.as_field_spec().env.as_deref(), Some("REPLICA_URL"));
+        assert_eq!(
+            get_field(database, "replica").unwrap().reuses.as_deref(),
+            Some("ConnectionConfig")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "extends a section that extends it back")]
+    fn test_extends_cycle() {
+        let toml_content = r#"
+        [a]
+        extends = "b"
+        url = { default = "a" }
+        [b]
+        extends = "a"
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    #[should_panic(expected = "extends 'missing', which is not a section")]
+    fn test_extends_unknown_section() {
+        let toml_content = r#"
+        [a]
+        extends = "missing"
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    fn test_profiles() {
+        let toml_content = r#"
+        port = { type = "u16", default = 8080, env = "PORT" }
+        [database]
+        url = { default = "localhost:5432" }
+
+        [profiles.prod]
+        port = { default = 80 }
+        database = { url = { default = "db.prod:5432", env = "PROD_DB_URL" } }
+        "#;
+        let load = |profile: Option<&str>| -> ConfigSpec {
+            toml::from_str::<GenericConfigSpec>(toml_content)
+                .unwrap()
+                .with_profile(profile.map(String::from))
+                .try_into()
+                .unwrap_or_else(panic_on)
+        };
+
+        let config = load(None);
+        assert!(config.get_field("profiles").is_none());
+        assert_eq!(
+            config
+                .get_field("port")
+                .unwrap()
+                .as_field_spec()
+                .default
+                .as_deref(),
+            Some("8080")
+        );
+
+        let config = load(Some("prod"));
+        let port = config.get_field("port").unwrap().as_field_spec();
+        assert_eq!(port.default.as_deref(), Some("80"));
+        assert_eq!(port.env.as_deref(), Some("PORT"));
+        let database = config.get_field("database").unwrap().as_subtype_spec();
+        let url = get_field(database, "url").unwrap().as_field_spec();
+        assert_eq!(url.default.as_deref(), Some("db.prod:5432"));
+        assert_eq!(url.env.as_deref(), Some("PROD_DB_URL"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown profile 'qa'")]
+    fn test_unknown_profile() {
+        let toml_content = r#"
+        port = { type = "u16", default = 8080 }
+        [profiles.prod]
+        port = { default = 80 }
+        "#;
+        let _: ConfigSpec = toml::from_str::<GenericConfigSpec>(toml_content)
+            .unwrap()
+            .with_profile(Some("qa".to_string()))
+            .try_into()
+            .unwrap_or_else(panic_on);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let toml_content = r#"
+        port = { type = "u16", default = 8080 }
+        [database]
+        url = { default = "localhost:5432" }
+        [profiles.prod]
+        port = { default = 80 }
+        "#;
+        let spec: GenericConfigSpec = toml::from_str(toml_content).unwrap();
+        let config: ConfigSpec = spec
+            .with_profile(Some("prod".to_string()))
+            .with_overrides(vec![
+                ("port.default".to_string(), toml::Value::from("9090")),
+                ("database.url.env".to_string(), toml::Value::from("DB_URL")),
+            ])
+            .try_into()
+            .unwrap_or_else(panic_on);
+        let port = config.get_field("port").unwrap().as_field_spec();
+        assert_eq!(port.default.as_deref(), Some("9090"));
+        let database = config.get_field("database").unwrap().as_subtype_spec();
+        let url = get_field(database, "url").unwrap().as_field_spec();
+        assert_eq!(url.default.as_deref(), Some("localhost:5432"));
+        assert_eq!(url.env.as_deref(), Some("DB_URL"));
+    }
+
+    #[test]
+    fn test_long_style() {
+        let longs = |style: &str| {
+            let toml_content = format!(
+                r#"
+                log_level = {{ default = "info" }}
+                [database.primary]
+                url = {{ default = "localhost:5432" }}
+                pool_size = {{ type = "int", default = "10", long = "pool" }}
+                [app]
+                long_style = "{style}"
+                "#
+            );
+            let spec: GenericConfigSpec = toml::from_str(&toml_content).unwrap();
+            let config: ConfigSpec = spec
+                .with_struct_name("app".to_string())
+                .try_into()
+                .unwrap_or_else(panic_on);
+            let database = config.get_field("database").unwrap().as_subtype_spec();
+            let primary = get_field(database, "primary").unwrap().as_subtype_spec();
+            [
+                config.get_field("log_level"),
+                get_field(primary, "url"),
+                get_field(primary, "pool_size"),
+            ]
+            .map(|field| field.unwrap().variant.long().map(String::from))
+        };
+        assert_eq!(
+            longs("kebab"),
+            ["log-level", "database-primary-url", "pool"].map(|l| Some(l.to_string()))
+        );
+        assert_eq!(
+            longs("snake"),
+            ["log_level", "database_primary_url", "pool"].map(|l| Some(l.to_string()))
+        );
+        assert_eq!(
+            longs("last-segment"),
+            ["log_level", "url", "pool"].map(|l| Some(l.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_long_style_prefixes_collisions() {
+        let toml_content = r#"
+        url = { default = "localhost" }
+        [cache]
+        url = { default = "localhost:6379" }
+        [database]
+        url = { default = "localhost:5432" }
+        [app]
+        long_style = "last-segment"
+        "#;
+        let spec: GenericConfigSpec = toml::from_str(toml_content).unwrap();
+        let config: ConfigSpec = spec
+            .with_struct_name("app".to_string())
+            .try_into()
+            .unwrap_or_else(panic_on);
+        let long = |field: Option<&Spec>| field.unwrap().variant.long().map(String::from);
+
+        assert_eq!(long(config.get_field("url")).as_deref(), Some("url"));
+        let cache = config.get_field("cache").unwrap().as_subtype_spec();
+        assert_eq!(long(get_field(cache, "url")).as_deref(), Some("cache-url"));
+        let database = config.get_field("database").unwrap().as_subtype_spec();
+        assert_eq!(
+            long(get_field(database, "url")).as_deref(),
+            Some("database-url")
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "long_style = \"kebab\" gives '--a-b-c' to both 'app.a.b_c' and 'app.a_b.c'"
+    )]
+    fn test_long_style_collision() {
+        let toml_content = r#"
+        [a]
+        b_c = { default = "x" }
+        [a_b]
+        c = { default = "y" }
+        [app]
+        long_style = "kebab"
+        "#;
+        let spec: GenericConfigSpec = toml::from_str(toml_content).unwrap();
+        let _: ConfigSpec = spec
+            .with_struct_name("app".to_string())
+            .try_into()
+            .unwrap_or_else(panic_on);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Fields 'database.port' (`database.port.short`) and 'port' (`port.short`) both take '-p'"
+    )]
+    fn test_duplicate_short() {
+        let toml_content = r#"
+        port = { type = "u16", default = "8080", short = "p" }
+        [database]
+        port = { type = "u16", default = "5432", short = "p" }
+        "#;
+        let spec: GenericConfigSpec = toml::from_str(toml_content).unwrap();
+        let _: ConfigSpec = spec.try_into().unwrap_or_else(panic_on);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Fields 'database.host' (`database.host.long`) and 'host' (`host.long`) both take '--host'"
+    )]
+    fn test_duplicate_long() {
+        let toml_content = r#"
+        host = { default = "0.0.0.0", long = "host" }
+        [database]
+        host = { default = "localhost", long = "host" }
+        "#;
+        let spec: GenericConfigSpec = toml::from_str(toml_content).unwrap();
+        let _: ConfigSpec = spec.try_into().unwrap_or_else(panic_on);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Field 'help' (`help.long`) takes '--help', which the command reserves for its help flag; set disable_help_flag = true in [app] to define your own"
+    )]
+    fn test_reserved_long() {
+        let toml_content = r#"
+        help = { type = "bool", default = "false" }
+        [app]
+        long_style = "kebab"
+        "#;
+        let spec: GenericConfigSpec = toml::from_str(toml_content).unwrap();
+        let _: ConfigSpec = spec.try_into().unwrap_or_else(panic_on);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Field 'verbose' (`verbose.short`) takes '-V', which the command reserves for its version flag"
+    )]
+    fn test_reserved_short() {
+        let toml_content = r#"
+        verbose = { type = "bool", default = "false", short = "V" }
+        [app]
+        version = "1.0.0"
+        "#;
+        let spec: GenericConfigSpec = toml::from_str(toml_content).unwrap();
+        let _: ConfigSpec = spec.try_into().unwrap_or_else(panic_on);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Fields 'host' (`host.long`) and 'endpoint' (`endpoint.aliases`) both take '--host'"
+    )]
+    fn test_alias_collision() {
+        let toml_content = r#"
+        endpoint = { default = "localhost:8080", aliases = ["host"] }
+        host = { default = "localhost", long = "host" }
+        "#;
+        let spec: GenericConfigSpec = toml::from_str(toml_content).unwrap();
+        let _: ConfigSpec = spec.try_into().unwrap_or_else(panic_on);
+    }
+
+    #[test]
+    fn test_default_if() {
+        let toml_content = r#"
+        debug = { type = "bool", default = "false" }
+        [log]
+        level = { default = "info", default_if = [{ field = "debug", equals = true, value = "trace" }] }
+        "#;
+        let config = ConfigSpec::load_toml_config(toml_content, "config");
+        let level = &config.get_field("log").unwrap().as_subtype_spec()[0];
+        assert_eq!(
+            level.default_if,
+            vec![DefaultIf {
+                field: "config.debug".to_string(),
+                equals: "true".to_string(),
+                value: "trace".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Field 'log_level': default_if names no field 'env'")]
+    fn test_default_if_unknown_field() {
+        let toml_content = r#"
+        log_level = { default = "info", default_if = [{ field = "env", equals = "prod", value = "warn" }] }
+        "#;
+        let _ = ConfigSpec::load_toml_config(toml_content, "");
+    }
+
+    #[test]
+    fn test_num_args() {
         let toml_content = r#"
-        port = { type = "int", default = "8080", doc = "Server port", env = "PORT" }
-        name = { type = "String", default = "test", long = "name", short = "n" }
+        color = { default = "auto", default_missing_value = "always" }
+        retries = { type = "u8", optional = true, num_args = "0..=1", default_missing_value = 3 }
         "#;
-        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+        let config = ConfigSpec::load_toml_config(toml_content, "config");
+        let color = config.get_field("color").unwrap();
+        assert_eq!(color.num_args.as_deref(), Some("0..=1"));
+        assert_eq!(color.default_missing_value.as_deref(), Some("always"));
+        let retries = config.get_field("retries").unwrap();
+        assert_eq!(retries.default_missing_value.as_deref(), Some("3"));
+    }
 
-        assert_eq!(config_spec.fields.len(), 2);
+    #[test]
+    #[should_panic(expected = "Invalid num_args of field 'config.color': '0-1' is not a range")]
+    fn test_invalid_num_args() {
+        let toml_content = r#"
+        color = { default = "auto", num_args = "0-1" }
+        "#;
+        let _ = ConfigSpec::load_toml_config(toml_content, "config");
+    }
 
-        let port_field = config_spec.get_field("port").unwrap();
+    #[test]
+    fn test_required_if() {
+        let toml_content = r#"
+        tls = { type = "bool", default = "false" }
+        token = { optional = true }
+        [tls_files]
+        cert = { optional = true, required_if = { field = "tls", equals = true }, required_unless = ["token"] }
+        "#;
+        let config = ConfigSpec::load_toml_config(toml_content, "config");
+        let cert = &config.get_field("tls_files").unwrap().as_subtype_spec()[0];
+        assert_eq!(
+            cert.required_if,
+            vec![RequiredIf {
+                field: "config.tls".to_string(),
+                equals: "true".to_string(),
+            }]
+        );
+        assert_eq!(cert.required_unless, vec!["config.token".to_string()]);
+    }
 
-        assert_eq!(port_field.name, "port");
-        assert_eq!(port_field.field_type, "i64");
-        assert_eq!(port_field.doc, Some("Server port".to_string()));
-        assert_eq!(port_field.id, ".port");
-        let port = port_field.as_field_spec();
+    #[test]
+    #[should_panic(
+        expected = "Field 'cert' is always required: required_if and required_unless need optional = true or a default"
+    )]
+    fn test_required_if_without_optional() {
+        let toml_content = r#"
+        tls = { type = "bool", default = "false" }
+        cert = { required_if = { field = "tls", equals = true } }
+        "#;
+        let _ = ConfigSpec::load_toml_config(toml_content, "config");
+    }
 
-        assert_eq!(port.default, Some("8080".to_string()));
-        assert_eq!(port.env, Some("PORT".to_string()));
-        assert_eq!(port.long_arg, None);
-        assert_eq!(port.short_arg, None);
+    #[test]
+    #[should_panic(
+        expected = "Field 'config.args': a trailing field is positional and takes no long or short"
+    )]
+    fn test_trailing_with_long() {
+        let toml_content = r#"
+        args = { type = "[string]", trailing = true, long = "args" }
+        "#;
+        let _ = ConfigSpec::load_toml_config(toml_content, "config");
+    }
 
-        let name_field = config_spec.get_field("name").unwrap();
+    #[test]
+    #[should_panic(
+        expected = "Invalid hint of field 'log_file': expected one of any_path, file_path"
+    )]
+    fn test_invalid_hint() {
+        let toml_content = r#"
+        log_file = { type = "path", default = "app.log", hint = "file" }
+        "#;
+        let _ = ConfigSpec::load_toml_config(toml_content, "config");
+    }
 
-        assert_eq!(name_field.name, "name");
-        assert_eq!(name_field.field_type, "String");
+    #[test]
+    #[should_panic(expected = "Override 'database.host.default' names no field 'database.host'")]
+    fn test_override_unknown_field() {
+        let toml_content = r#"
+        [database]
+        url = { default = "localhost:5432" }
+        "#;
+        let spec: GenericConfigSpec = toml::from_str(toml_content).unwrap();
+        let _: ConfigSpec = spec
+            .with_overrides(vec![(
+                "database.host.default".to_string(),
+                toml::Value::from("db"),
+            )])
+            .try_into()
+            .unwrap_or_else(panic_on);
+    }
 
-        let name = name_field.as_field_spec();
+    #[test]
+    fn test_from_toml_str() {
+        let spec = GenericConfigSpec::from_toml_str("port = { type = \"u16\" }").unwrap();
+        let config: ConfigSpec = spec
+            .with_struct_name("app".to_string())
+            .try_into()
+            .unwrap_or_else(panic_on);
+        assert_eq!(config.get_field("port").unwrap().id, "app.port");
+        assert!(config.files.is_empty());
+
+        let err = GenericConfigSpec::from_toml_str("include = \"db.toml\"").unwrap_err();
+        assert_eq!(err.to_string(), "include is only supported in spec files");
+    }
 
-        assert_eq!(name.default, Some("test".to_string()));
-        assert_eq!(name.env, None);
-        assert_eq!(name.long_arg, Some("name".to_string()));
-        assert_eq!(name.short_arg, Some('n'));
+    #[test]
+    fn test_merge_specs() {
+        let base: GenericConfigSpec = toml::from_str(
+            r#"
+            port = { type = "u16", default = 8080, env = "PORT" }
+            [database]
+            url = { default = "localhost:5432" }
+            "#,
+        )
+        .unwrap();
+        let overrides: GenericConfigSpec = toml::from_str(
+            r#"
+            port = { default = 9090 }
+            debug = { type = "bool" }
+            [database]
+            pool_size = { type = "u32", default = 4 }
+            "#,
+        )
+        .unwrap();
+        let config: ConfigSpec = base
+            .merge(overrides)
+            .unwrap()
+            .try_into()
+            .unwrap_or_else(panic_on);
+        let port = config.get_field("port").unwrap();
+        assert_eq!(port.field_type, "u16");
+        assert_eq!(port.as_field_spec().default.as_deref(), Some("9090"));
+        assert_eq!(port.as_field_spec().env.as_deref(), Some("PORT"));
+        assert!(config.get_field("debug").is_some());
+        let database = config.get_field("database").unwrap().as_subtype_spec();
+        assert!(get_field(database, "url").is_some());
+        assert!(get_field(database, "pool_size").is_some());
     }
 
     #[test]
-    fn test_nested_field_parsing() {
-        let toml_content = r#"
-        [database]
-        type = "DatabaseConfig"
-        doc = "Database configuration"
+    fn test_merge_conflicts() {
+        let merge = |base: &str, other: &str| {
+            let base: GenericConfigSpec = toml::from_str(base).unwrap();
+            base.merge(toml::from_str(other).unwrap()).unwrap_err()
+        };
+        assert_eq!(
+            merge(
+                r#"port = { type = "u16" }"#,
+                r#"port = { type = "string" }"#
+            ),
+            r#"field 'port' changes its type from "u16" to "string""#
+        );
+        assert_eq!(
+            merge(
+                r#"db = { type = "string" }"#,
+                r#"db = { url = { default = "x" } }"#
+            ),
+            "field 'db' is redeclared as a section"
+        );
+        assert_eq!(
+            merge(
+                r#"db = { url = { default = "x" } }"#,
+                r#"db = { type = "string" }"#
+            ),
+            "section 'db' is redeclared as a field"
+        );
+    }
 
-        [database.host]
-        default = "localhost"
-        env = "DB_HOST"
-        
-        [database.port]
-        type = "int"
-        default = "5432"
-        env = "DB_PORT"
+    #[test]
+    fn test_locked_field() {
+        let toml_content = r#"
+        audit = { type = "bool", default = "true", env = "AUDIT", locked = true }
+        level = { default = "info" }
         "#;
+        let config = ConfigSpec::load_toml_config(toml_content, "");
+        assert!(config.get_field("audit").unwrap().locked);
+        assert!(!config.get_field("level").unwrap().locked);
+    }
 
-        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
-        assert_eq!(config_spec.fields.len(), 1);
+    #[test]
+    fn test_sensitive_field() {
+        let toml_content = r#"
+        token = { default = "abc", env = "TOKEN", sensitive = true }
+        password = { default = "changeme", secret = true }
+        shown = { default = "changeme", secret = true, sensitive = false }
+        level = { default = "info" }
+        "#;
+        let config = ConfigSpec::load_toml_config(toml_content, "");
 
-        // Test database field
-        let db_field = &config_spec.fields[0];
-        assert_eq!(db_field.name, "database");
-        assert_eq!(db_field.field_type, "DatabaseConfig");
-        assert_eq!(db_field.id, ".database");
+        assert!(config.get_field("token").unwrap().sensitive);
+        assert!(config.get_field("password").unwrap().sensitive);
+        assert!(!config.get_field("shown").unwrap().sensitive);
+        assert!(!config.get_field("level").unwrap().sensitive);
+    }
 
-        // Extract and test database subtype
+    #[test]
+    fn test_secret_string_type() {
+        let toml_content = r#"
+        api_key = { type = "secret_string", env = "API_KEY" }
+        "#;
+        let config = ConfigSpec::load_toml_config(toml_content, "");
+        let api_key = config.get_field("api_key").unwrap();
 
-        let fields = db_field.as_subtype_spec();
-        assert_eq!(fields.len(), 2);
+        assert_eq!(api_key.field_type, SECRET_STRING);
+        assert!(api_key.sensitive);
+        let field = api_key.as_field_spec();
+        assert_eq!(
+            field.value_parser.as_deref(),
+            Some("rclap::parsers::secret_string")
+        );
+    }
 
-        // Test host field
-        let host_field = &fields[0];
-        assert_eq!(host_field.name, "host");
-        assert_eq!(host_field.field_type, "String");
-        assert_eq!(host_field.id, ".database.host");
+    #[test]
+    #[should_panic(expected = "cannot be locked")]
+    fn test_locked_section() {
+        let toml_content = r#"
+        [database]
+        locked = true
+        url = { default = "localhost" }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
 
-        let host = host_field.as_field_spec();
+    #[test]
+    fn test_external_section() {
+        let toml_content = r#"
+        cache = { external = "redis_lib::RedisConfig", requires = ["url", "pool.size"] }
+        "#;
+        let config = ConfigSpec::load_toml_config(toml_content, "");
+        let cache = config.get_field("cache").unwrap();
+        assert_eq!(cache.field_type, "redis_lib::RedisConfig");
+        let GenericSpec::ExternalSpec(external) = &cache.variant else {
+            panic!("Expected ExternalSpec");
+        };
+        assert!(external.section);
+        assert_eq!(
+            external.requires,
+            vec![("url".to_string(), None), ("pool.size".to_string(), None)]
+        );
 
-        assert_eq!(host.default, Some("localhost".to_string()));
-        assert_eq!(host.env, Some("DB_HOST".to_string()));
-        assert_eq!(host.long_arg, None);
-        assert_eq!(host.short_arg, None);
+        let toml_content = r#"
+        cache = { external = "redis_lib::RedisConfig", requires = { url = "String", "pool.size" = "Option< u16 >" } }
+        "#;
+        let config = ConfigSpec::load_toml_config(toml_content, "");
+        let GenericSpec::ExternalSpec(external) = &config.get_field("cache").unwrap().variant
+        else {
+            panic!("Expected ExternalSpec");
+        };
+        assert_eq!(
+            external.requires,
+            vec![
+                ("pool.size".to_string(), Some("Option<u16>".to_string())),
+                ("url".to_string(), Some("String".to_string())),
+            ]
+        );
+    }
 
-        // Test port field
-        let port_field = &fields[1];
-        assert_eq!(port_field.name, "port");
-        assert_eq!(port_field.field_type, "i64");
-        assert_eq!(port_field.id, ".database.port");
+    #[test]
+    fn test_external_args() {
+        let toml_content = r#"
+        logging = { type = "LoggingArgs", external = true }
+        tracing = { type = "other_crate::TracingArgs", external = true }
+        "#;
+        let config = ConfigSpec::load_toml_config(toml_content, "");
+        let logging = config.get_field("logging").unwrap();
+        assert_eq!(logging.field_type, "super::LoggingArgs");
+        let GenericSpec::ExternalSpec(external) = &logging.variant else {
+            panic!("Expected ExternalSpec");
+        };
+        assert!(external.args && !external.section);
+        let tracing = config.get_field("tracing").unwrap();
+        assert_eq!(tracing.field_type, "other_crate::TracingArgs");
+    }
 
-        let port = port_field.as_field_spec();
-        assert_eq!(port.default, Some("5432".to_string()));
-        assert_eq!(port.env, Some("DB_PORT".to_string()));
-        assert_eq!(port.long_arg, None);
-        assert_eq!(port.short_arg, None);
+    #[test]
+    #[should_panic(expected = "sets `external = true` but names no `type`")]
+    fn test_external_args_without_type() {
+        let toml_content = r#"
+        logging = { external = true }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
     }
 
     #[test]
-    fn test_deep_nested_structure() {
+    #[should_panic(expected = "cannot set both `type` and `external`")]
+    fn test_external_with_type() {
         let toml_content = r#"
-        [app]
-        type = "AppConfig"
+        cache = { type = "string", external = "redis_lib::RedisConfig" }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
 
-        [app.server]
-        type = "ServerConfig"
-        
-            [app.server.http]
-            type = "HttpConfig"
-            port = { type = "int", default = "8080" }
-            host = {  default = "localhost" }
-            
-            [app.server.tls]
-            type = "TlsConfig"
-            cert = {  env = "TLS_CERT" }
+    #[test]
+    fn test_path_vec_field() {
+        let toml_content = r#"
+        include = { type = "[path]", default = ["/etc/app", "conf.d"], env = "INCLUDE", split_paths = true }
         "#;
-        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
+        let config = ConfigSpec::load_toml_config(toml_content, "");
+        let include = config.get_field("include").unwrap();
+        assert_eq!(include.field_type, format!("Vec<{PATH_BUF}>"));
+        let GenericSpec::VecSpec(vec) = &include.variant else {
+            panic!("Expected VecSpec");
+        };
+        assert!(vec.split_paths);
+    }
 
-        assert_eq!(config_spec.fields.len(), 1);
+    #[test]
+    #[should_panic(expected = "split_paths requires type")]
+    fn test_split_paths_on_strings() {
+        let toml_content = r#"
+        names = { type = "[string]", split_paths = true }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
 
-        let app_field = config_spec.get_field("app").unwrap();
-        assert_eq!(app_field.name, "app");
-        assert_eq!(app_field.field_type, "AppConfig");
+    #[test]
+    fn test_vec_delimiter() {
+        let toml_content = r#"
+        hosts = { type = "[string]", delimiter = ";" }
+        notes = { type = "[string]", env = "NOTES", delimiter = false }
+        tags = { type = "[string]", env = "TAGS" }
+        names = { type = "[string]" }
+        "#;
+        let config = ConfigSpec::load_toml_config(toml_content, "");
+        let delimiter = |name: &str| match &config.get_field(name).unwrap().variant {
+            GenericSpec::VecSpec(v) => v.delimiter,
+            _ => panic!("Expected VecSpec"),
+        };
+        assert_eq!(delimiter("hosts"), Some(';'));
+        assert_eq!(delimiter("notes"), None);
+        assert_eq!(delimiter("tags"), Some(','));
+        assert_eq!(delimiter("names"), None);
+    }
 
-        let fields = app_field.as_subtype_spec();
-        let server_field = get_field(fields, "server").unwrap();
-        assert_eq!(server_field.name, "server");
-        assert_eq!(server_field.field_type, "ServerConfig");
-        assert_eq!(server_field.id, ".app.server");
+    #[test]
+    #[should_panic(expected = "must be a single character or false")]
+    fn test_invalid_delimiter() {
+        let toml_content = r#"
+        hosts = { type = "[string]", delimiter = "::" }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
 
-        let fields = server_field.as_subtype_spec();
-        assert_eq!(fields.len(), 2);
+    #[test]
+    fn test_vec_item_bounds() {
+        let toml_content = r#"
+        replicas = { type = "[string]", min_items = 1, max_items = 5 }
+        "#;
+        let config = ConfigSpec::load_toml_config(toml_content, "");
+        let GenericSpec::VecSpec(replicas) = &config.get_field("replicas").unwrap().variant else {
+            panic!("Expected VecSpec");
+        };
+        assert_eq!((replicas.min_items, replicas.max_items), (Some(1), Some(5)));
+    }
 
-        let http_field = &fields[0];
-        assert_eq!(http_field.name, "http");
-        assert_eq!(http_field.field_type, "HttpConfig");
-        assert_eq!(http_field.id, ".app.server.http");
+    #[test]
+    #[should_panic(expected = "min_items 3 is greater than max_items 2")]
+    fn test_invalid_vec_item_bounds() {
+        let toml_content = r#"
+        replicas = { type = "[string]", min_items = 3, max_items = 2 }
+        "#;
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
 
-        let fields = http_field.as_subtype_spec();
-        assert_eq!(fields.len(), 2);
+    #[test]
+    fn test_array_of_tables() {
+        let toml_content = r#"
+        [[listeners]]
+        env = "LISTENERS"
+        doc = "Sockets to listen on"
+        host = { default = "0.0.0.0" }
+        port = { type = "u16", default = 8080 }
+
+        [server]
+        name = { default = "api" }
+        [[server.routes]]
+        path = { default = "/" }
+        "#;
+        let config = ConfigSpec::load_toml_config(toml_content, "app");
+        let listeners = config.get_field("listeners").unwrap();
+        assert_eq!(listeners.id, "app.listeners");
+        assert_eq!(listeners.field_type, "Vec<ListenersConfig>");
+        assert_eq!(listeners.doc.as_deref(), Some("Sockets to listen on"));
+        let GenericSpec::SubtypeVecSpec(vec) = &listeners.variant else {
+            panic!("Expected SubtypeVecSpec");
+        };
+        assert_eq!(vec.env.as_deref(), Some("LISTENERS"));
+        assert!(get_field(&vec.fields, "host").is_some());
+        assert_eq!(
+            get_field(&vec.fields, "port").unwrap().id,
+            "app.listeners.port"
+        );
 
-        let port_field = get_field(fields, "port").unwrap();
-        assert_eq!(port_field.name, "port");
-        assert_eq!(port_field.id, ".app.server.http.port");
+        let server = config.get_field("server").unwrap().as_subtype_spec();
+        let routes = get_field(server, "routes").unwrap();
+        assert_eq!(routes.field_type, "Vec<RoutesConfig>");
     }
 
     #[test]
-    fn test_optional_fields() {
+    #[should_panic(expected = "must contain exactly one table")]
+    fn test_array_of_tables_with_two_tables() {
         let toml_content = r#"
-        port = { type = "int", default = "8080", optional = true }
-        host = { type = "String", env = "HOST" }
-        debug = { type = "bool", optional = false }
+        [[listeners]]
+        host = { default = "a" }
+        [[listeners]]
+        host = { default = "b" }
         "#;
-        let config_spec = ConfigSpec::load_toml_config(toml_content, "");
-
-        assert_eq!(config_spec.fields.len(), 3);
+        ConfigSpec::load_toml_config(toml_content, "");
+    }
 
-        let port_field = config_spec.get_field("port").unwrap().as_field_spec();
-        assert!(port_field.optional);
+    #[test]
+    fn test_map_of_section() {
+        let toml_content = r#"
+        [upstreams]
+        map_of = "UpstreamConfig"
+        env = "UPSTREAMS"
+        url = { default = "http://localhost" }
+        weight = { type = "u16", default = 1 }
+        "#;
+        let config = ConfigSpec::load_toml_config(toml_content, "app");
+        let upstreams = config.get_field("upstreams").unwrap();
+        assert_eq!(
+            upstreams.field_type,
+            "std::collections::HashMap<String, UpstreamConfig>"
+        );
+        let GenericSpec::SubtypeMapSpec(map) = &upstreams.variant else {
+            panic!("Expected SubtypeMapSpec");
+        };
+        assert_eq!(map.element, "UpstreamConfig");
+        assert_eq!(map.env.as_deref(), Some("UPSTREAMS"));
+        assert_eq!(
+            get_field(&map.fields, "url").unwrap().id,
+            "app.upstreams.url"
+        );
+    }
 
-        let host_field = config_spec.get_field("host").unwrap().as_field_spec();
-        assert!(!host_field.optional); // Not specified
+    #[test]
+    fn test_optional_section() {
+        let toml_content = r#"
+        [tls]
+        optional = true
+        cert = { type = "path" }
+        key = { type = "path" }
 
-        let debug_field = config_spec.get_field("debug").unwrap().as_field_spec();
-        assert!(!debug_field.optional);
+        [database]
+        url = { default = "localhost" }
+        "#;
+        let config = ConfigSpec::load_toml_config(toml_content, "");
+        assert!(config.get_field("tls").unwrap().optional);
+        assert!(!config.get_field("database").unwrap().optional);
     }
 
     #[test]
@@ -380,6 +3684,8 @@ port = { type = "int", short = "p" }
 host = { type = "String", short = "h" }
 invalid_short = { type = "String", short = "invalid" }
 empty_short = { type = "String", short = "" }
+[app]
+disable_help_flag = true
 "#;
         let config_spec = ConfigSpec::load_toml_config(toml_content, "");
 
@@ -414,6 +3720,172 @@ host = { type = "String", default = "localhost" }
         assert_eq!(config_spec.fields.len(), 2);
     }
 
+    #[test]
+    fn test_validate() {
+        let (_dir, path) = create_temp_toml(
+            r#"port = { type = "u16", default = "80x" }
+host = { short = "ab", defualt = "x" }
+name = { short = "n" }
+user = { short = "n" }
+[database]
+url = { default_if = [{ field = "port", equals = "1", value = "a" }] }
+"#,
+        );
+        let problems: Vec<String> = validate::validate(&path, "config")
+            .iter()
+            .map(|problem| {
+                let (line, column) = problem.position.unwrap();
+                format!("{line}:{column}: {}", problem.message)
+            })
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                "1:1: Invalid default for field 'config.port': '80x' is not an integer",
+                "2:18: Field 'host': short 'ab' must be a single character",
+                "2:24: Field 'host': unknown key 'defualt'",
+                "3:1: Fields 'name' (`name.short`) and 'user' (`user.short`) both take '-n'",
+            ]
+        );
+        let (_dir, path) = create_temp_toml("port = { type = \"u16\" }");
+        assert!(validate::validate(&path, "config").is_empty());
+    }
+
+    #[test]
+    fn test_parsed_defaults() {
+        let cases = [
+            (
+                r#"p = { type = "port", default = "70000" }"#,
+                "'70000' is out of range for u16 (0..=65535)",
+            ),
+            (
+                r#"p = { type = "bytesize", default = "10XB" }"#,
+                "'10XB' is not a valid byte size: unknown size unit 'xb'",
+            ),
+            (
+                r#"p = { type = "cidr", default = "10.0.0.0/99" }"#,
+                "'10.0.0.0/99' is not a valid CIDR network: '10.0.0.0/99' is not a valid CIDR network or IP address",
+            ),
+            (
+                r#"p = { type = "timezone", default = "Mars/Base" }"#,
+                "'Mars/Base' is not a known IANA time zone",
+            ),
+            (
+                r#"p = { type = "color", default = "blurple" }"#,
+                "'blurple' is not a valid color: 'blurple' is not a known color name",
+            ),
+            (
+                r#"p = { type = "email", default = "nope" }"#,
+                "'nope' is not a valid e-mail address: missing '@' in e-mail address",
+            ),
+            (
+                r#"p = { type = "hostname", default = "-web" }"#,
+                "'-web' is not a valid host name: label '-web' must not start or end with '-'",
+            ),
+            (
+                r#"p = { type = "lang", default = "not a tag" }"#,
+                "'not a tag' is not a valid language tag",
+            ),
+            (
+                r#"p = { type = "[email]", default = ["ops@example.com", "nope"] }"#,
+                "'nope' is not a valid e-mail address: missing '@' in e-mail address",
+            ),
+        ];
+        for (content, expected) in cases {
+            let (_dir, path) = create_temp_toml(content);
+            let problems = validate::validate(&path, "config");
+            assert_eq!(problems.len(), 1, "{content}");
+            let message = &problems[0].message;
+            assert!(
+                message.starts_with(&format!("Invalid default for field 'config.p': {expected}")),
+                "{message}"
+            );
+        }
+        let (_dir, path) = create_temp_toml(
+            r##"port = { type = "port", default = "8080" }
+size = { type = "bytesize", default = "10MB" }
+net = { type = "cidr", default = "10.0.0.0/8" }
+zone = { type = "timezone", default = "Europe/Paris" }
+accent = { type = "color", default = "#f80" }
+alert = { type = "email", default = "ops@example.com" }
+host = { type = "hostname", default = "db.local" }
+lang = { type = "lang", default = "fr-FR" }
+"##,
+        );
+        assert!(validate::validate(&path, "config").is_empty());
+    }
+
+    #[test]
+    fn test_validate_build_checks() {
+        let cases = [
+            (
+                r#"ratio = { type = "float", min = 0, max = 1 }"#,
+                "min/max are only supported on integer fields, 'ratio' has type f64",
+            ),
+            (
+                r#"pin = { type = "u16", secret = true, max = 9999 }"#,
+                "min/max are not supported on secret field 'pin'",
+            ),
+            (
+                r#"level = { type = "int", values = ["1", "2"] }"#,
+                "values are only supported on plain string fields, 'level' has type i64",
+            ),
+            (
+                r#"count = { type = "int", pattern = "^[0-9]+$" }"#,
+                "pattern is only supported on plain string fields, 'count' has type i64",
+            ),
+            (
+                r#"trace = { type = "bool", cfg = "feature = \"otel" }"#,
+                "Invalid cfg for field 'config.trace': feature = \"otel",
+            ),
+            (
+                r#"trace = { type = "bool", cfg = "some(unix)" }"#,
+                "Invalid cfg for field 'config.trace': some(unix)",
+            ),
+            (
+                r#"color = { num_args = "1..=" }"#,
+                "Invalid num_args of field 'config.color': '1..=' is not a range such as \"0..=1\"",
+            ),
+            (
+                r#"color = { num_args = "2..1" }"#,
+                "Invalid num_args of field 'config.color': '2..1' is not a range such as \"0..=1\"",
+            ),
+            (
+                r#"crate = { default = "x" }"#,
+                "Field 'config.crate' cannot be named `crate`; rename it and set long",
+            ),
+            (
+                "[app.style]\nheader = \"bold blurple\"",
+                "Invalid style 'bold blurple' for header in [app.style]: expected effects and a color such as \"bold green\", \"bright-blue\" or \"#ff8800\"",
+            ),
+            (
+                "[app]\ngit_sha_env = \"GIT_SHA\"",
+                "git_sha_env needs a version in [app]",
+            ),
+            (
+                "command = { default = \"run\" }\n[app]\nexternal_subcommands = true",
+                "external_subcommands adds a `command` field; rename the spec field 'command'",
+            ),
+        ];
+        for (content, expected) in cases {
+            let (_dir, path) = create_temp_toml(content);
+            let problems: Vec<String> = validate::validate(&path, "config")
+                .into_iter()
+                .map(|problem| problem.message)
+                .collect();
+            assert_eq!(problems, [expected], "{content}");
+        }
+        let (_dir, path) = create_temp_toml(
+            r##"trace = { type = "bool", cfg = 'all(unix, not(feature = "a\"b"), any())' }
+color = { num_args = "0..=1" }
+[app.style]
+header = "bold bright-green"
+literal = "#ff8800"
+"##,
+        );
+        assert!(validate::validate(&path, "config").is_empty());
+    }
+
     #[test]
     fn test_from_file_unsupported_format() {
         let temp_dir = TempDir::new().unwrap();
@@ -435,6 +3907,53 @@ host = { type = "String", default = "localhost" }
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_from_file_include() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("specs")).unwrap();
+        fs::write(
+            temp_dir.path().join("specs/db.toml"),
+            "include = \"pool.toml\"\n[database]\nurl = { default = \"localhost\" }\n",
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join("specs/pool.toml"),
+            "[database]\npool = { type = \"u32\", default = 4 }\n",
+        )
+        .unwrap();
+        let file_path = temp_dir.path().join("config.toml");
+        fs::write(
+            &file_path,
+            "include = [\"specs/db.toml\"]\nport = { type = \"u16\" }\n[database]\nurl = { env = \"DB_URL\" }\n",
+        )
+        .unwrap();
+
+        let config = ConfigSpec::from_file(&file_path, "").unwrap();
+        assert!(config.get_field("port").is_some());
+        let database = config.get_field("database").unwrap().as_subtype_spec();
+        let url = get_field(database, "url").unwrap().as_field_spec();
+        assert_eq!(url.default.as_deref(), Some("localhost"));
+        assert_eq!(url.env.as_deref(), Some("DB_URL"));
+        assert!(get_field(database, "pool").is_some());
+        let files: Vec<_> = config
+            .files
+            .iter()
+            .map(|file| file.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(files, ["pool.toml", "db.toml", "config.toml"]);
+    }
+
+    #[test]
+    fn test_from_file_include_cycle() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("a.toml");
+        fs::write(&file_path, "include = \"b.toml\"\n").unwrap();
+        fs::write(temp_dir.path().join("b.toml"), "include = \"a.toml\"\n").unwrap();
+
+        let err = ConfigSpec::from_file(&file_path, "").unwrap_err();
+        assert!(err.to_string().contains("include cycle"));
+    }
+
     #[test]
     fn test_to_pascal_case() {
         assert_eq!(to_pascal_case("hello"), "Hello");
@@ -455,20 +3974,26 @@ host = { type = "String", default = "localhost" }
             toml::Value::String("CustomType".to_string()),
         );
         assert_eq!(
-            get_field_type(&table, false, "test".to_string()).type_name,
+            get_field_type(&table, false, "test".to_string())
+                .unwrap()
+                .type_name,
             "CustomType"
         );
 
         // Test auto-generated type with subfields
         table.clear();
         assert_eq!(
-            get_field_type(&table, true, "redis_config".to_string()).type_name,
+            get_field_type(&table, true, "redis_config".to_string())
+                .unwrap()
+                .type_name,
             "Redis_configConfig"
         );
 
         // Test default string type
         assert_eq!(
-            get_field_type(&table, false, "simple".to_string()).type_name,
+            get_field_type(&table, false, "simple".to_string())
+                .unwrap()
+                .type_name,
             "String"
         );
     }
@@ -502,7 +4027,7 @@ host = { type = "String", default = "localhost" }
                                             
                                             [database.replica]
                                             type = "ConnectionConfig" 
-                                            url = { type = "string", env = "DB_REPLICA_URL", optional = true }
+                                            url = { type = "string", env = "DB_REPLICA_URL" }
                                             pool_size = { type = "int", default = "5" }
 
                                             # Another top-level section  
@@ -539,6 +4064,13 @@ host = { type = "String", default = "localhost" }
         let url_field = get_field(primary_subtype, "url").unwrap();
         assert_eq!(url_field.id, ".database.primary.url");
 
+        // The replica reuses the type declared by the primary
+        let replica = get_field(fields, "replica").unwrap();
+        assert_eq!(primary.field_type, "ConnectionConfig");
+        assert_eq!(primary.reuses, None);
+        assert_eq!(replica.field_type, "DatabaseReplicaArgs");
+        assert_eq!(replica.reuses.as_deref(), Some("ConnectionConfig"));
+
         let logging = config_spec.get_field("logging").unwrap();
         assert_eq!(logging.name, "logging");
         assert_eq!(logging.field_type, "LoggingConfig");
@@ -0,0 +1,104 @@
+use std::net::IpAddr;
+
+const MAX_HOSTNAME_LEN: usize = 253;
+const MAX_LABEL_LEN: usize = 63;
+const MAX_LOCAL_PART_LEN: usize = 64;
+const MAX_EMAIL_LEN: usize = 254;
+
+/// Validates a host name as defined by RFC 1123: dot separated labels of 1 to 63
+/// alphanumeric or `-` characters, not starting or ending with `-`, 253 characters at most.
+/// A single trailing dot (fully qualified form) is accepted.
+pub fn hostname(s: &str) -> Result<String, String> {
+    check_hostname(s)?;
+    Ok(s.to_string())
+}
+
+/// Validates an e-mail address (RFC 5321 mailbox): a dot-atom or quoted local part of at most
+/// 64 characters, followed by `@` and a host name or a bracketed IP literal.
+pub fn email(s: &str) -> Result<String, String> {
+    if s.len() > MAX_EMAIL_LEN {
+        return Err(format!(
+            "e-mail addresses are limited to {MAX_EMAIL_LEN} characters"
+        ));
+    }
+    let (local, domain) = s
+        .rsplit_once('@')
+        .ok_or_else(|| "missing '@' in e-mail address".to_string())?;
+    check_local_part(local)?;
+    if let Some(literal) = domain.strip_prefix('[').and_then(|d| d.strip_suffix(']')) {
+        let literal = literal.strip_prefix("IPv6:").unwrap_or(literal);
+        literal
+            .parse::<IpAddr>()
+            .map_err(|_| format!("'{domain}' is not a valid address literal"))?;
+    } else {
+        check_hostname(domain)?;
+    }
+    Ok(s.to_string())
+}
+
+fn check_hostname(s: &str) -> Result<(), String> {
+    let name = s.strip_suffix('.').unwrap_or(s);
+    if name.is_empty() {
+        return Err("host name is empty".to_string());
+    }
+    if name.len() > MAX_HOSTNAME_LEN {
+        return Err(format!(
+            "host names are limited to {MAX_HOSTNAME_LEN} characters"
+        ));
+    }
+    for label in name.split('.') {
+        if label.is_empty() || label.len() > MAX_LABEL_LEN {
+            return Err(format!(
+                "'{s}' has a label that is empty or longer than {MAX_LABEL_LEN} characters"
+            ));
+        }
+        if label.starts_with('-') || label.ends_with('-') {
+            return Err(format!("label '{label}' must not start or end with '-'"));
+        }
+        if let Some(c) = label
+            .chars()
+            .find(|c| !c.is_ascii_alphanumeric() && *c != '-')
+        {
+            return Err(format!("invalid character '{c}' in host name '{s}'"));
+        }
+    }
+    Ok(())
+}
+
+fn check_local_part(local: &str) -> Result<(), String> {
+    if local.is_empty() {
+        return Err("e-mail local part is empty".to_string());
+    }
+    if local.len() > MAX_LOCAL_PART_LEN {
+        return Err(format!(
+            "e-mail local parts are limited to {MAX_LOCAL_PART_LEN} characters"
+        ));
+    }
+    if local.len() >= 2 && local.starts_with('"') && local.ends_with('"') {
+        let quoted = &local[1..local.len() - 1];
+        let mut escaped = false;
+        for c in quoted.chars() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => return Err("unescaped '\"' in quoted local part".to_string()),
+                c if c.is_ascii_control() => {
+                    return Err("control character in quoted local part".to_string());
+                }
+                _ => {}
+            }
+        }
+        return Ok(());
+    }
+    if local.starts_with('.') || local.ends_with('.') || local.contains("..") {
+        return Err(format!("misplaced '.' in local part '{local}'"));
+    }
+    if let Some(c) = local.chars().find(|c| !is_atext(*c) && *c != '.') {
+        return Err(format!("invalid character '{c}' in local part '{local}'"));
+    }
+    Ok(())
+}
+
+fn is_atext(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~".contains(c)
+}
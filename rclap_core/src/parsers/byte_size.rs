@@ -0,0 +1,57 @@
+/// Decimal (`KB`, `MB`, ...) and binary (`KiB`, `MiB`, ...) units, matched case-insensitively.
+const UNITS: [(&str, u64); 16] = [
+    ("b", 1),
+    ("k", 1 << 10),
+    ("kb", 1_000),
+    ("kib", 1 << 10),
+    ("m", 1 << 20),
+    ("mb", 1_000_000),
+    ("mib", 1 << 20),
+    ("g", 1 << 30),
+    ("gb", 1_000_000_000),
+    ("gib", 1 << 30),
+    ("t", 1 << 40),
+    ("tb", 1_000_000_000_000),
+    ("tib", 1 << 40),
+    ("p", 1 << 50),
+    ("pb", 1_000_000_000_000_000),
+    ("pib", 1 << 50),
+];
+
+/// Parses a human readable size such as `512`, `10MB`, `1.5 GiB` into a number of bytes.
+///
+/// Decimal units are powers of 1000 and binary units powers of 1024; a bare `K`, `M`, `G`, `T` or `P`
+/// is read as the binary unit. A number without unit is a byte count.
+pub fn byte_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    if number.is_empty() {
+        return Err(format!("'{s}' does not start with a number"));
+    }
+    let unit = unit.trim().to_ascii_lowercase();
+    let multiplier = if unit.is_empty() {
+        1
+    } else {
+        UNITS
+            .iter()
+            .find(|(name, _)| *name == unit)
+            .map(|(_, multiplier)| *multiplier)
+            .ok_or_else(|| format!("unknown size unit '{unit}'"))?
+    };
+    if let Ok(count) = number.parse::<u64>() {
+        return count
+            .checked_mul(multiplier)
+            .ok_or_else(|| format!("'{s}' does not fit in 64 bits"));
+    }
+    let count: f64 = number
+        .parse()
+        .map_err(|_| format!("'{number}' is not a valid number"))?;
+    let bytes = (count * multiplier as f64).round();
+    if bytes >= u64::MAX as f64 {
+        return Err(format!("'{s}' does not fit in 64 bits"));
+    }
+    Ok(bytes as u64)
+}
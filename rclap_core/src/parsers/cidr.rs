@@ -0,0 +1,16 @@
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+
+/// Parses an IPv4 or IPv6 network in CIDR notation (`10.0.0.0/8`, `fd00::/8`).
+///
+/// A bare address is accepted as a single host network (`/32` or `/128`).
+pub fn cidr(s: &str) -> Result<IpNet, String> {
+    let s = s.trim();
+    if let Ok(net) = s.parse::<IpNet>() {
+        return Ok(net);
+    }
+    s.parse::<IpAddr>()
+        .map(IpNet::from)
+        .map_err(|_| format!("'{s}' is not a valid CIDR network or IP address"))
+}
@@ -0,0 +1,47 @@
+/// The 17 named colors of CSS 2.1.
+const NAMED_COLORS: [(&str, u32); 17] = [
+    ("black", 0x000000),
+    ("silver", 0xc0c0c0),
+    ("gray", 0x808080),
+    ("white", 0xffffff),
+    ("maroon", 0x800000),
+    ("red", 0xff0000),
+    ("purple", 0x800080),
+    ("fuchsia", 0xff00ff),
+    ("green", 0x008000),
+    ("lime", 0x00ff00),
+    ("olive", 0x808000),
+    ("yellow", 0xffff00),
+    ("navy", 0x000080),
+    ("blue", 0x0000ff),
+    ("teal", 0x008080),
+    ("aqua", 0x00ffff),
+    ("orange", 0xffa500),
+];
+
+/// Parses `#RRGGBB`, the `#RGB` shorthand and the CSS 2.1 color names (case-insensitive) into
+/// `(r, g, b)`.
+pub fn color(s: &str) -> Result<(u8, u8, u8), String> {
+    let s = s.trim();
+    let split = |rgb: u32| ((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8);
+    if let Some(hex) = s.strip_prefix('#') {
+        let invalid = || format!("'{s}' is not a #RRGGBB or #RGB color");
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let rgb = u32::from_str_radix(hex, 16).map_err(|_| invalid())?;
+        return match hex.len() {
+            6 => Ok(split(rgb)),
+            3 => {
+                let expand = |nibble: u32| ((nibble & 0xf) * 0x11) as u8;
+                Ok((expand(rgb >> 8), expand(rgb >> 4), expand(rgb)))
+            }
+            _ => Err(invalid()),
+        };
+    }
+    NAMED_COLORS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(s))
+        .map(|(_, rgb)| split(*rgb))
+        .ok_or_else(|| format!("'{s}' is not a known color name"))
+}
@@ -0,0 +1,11 @@
+//! The parsers of the spec types that depend on no runtime environment, shared by the
+//! generated code (through `rclap::parsers`) and the checks of the spec defaults.
+
+pub mod address;
+pub use address::{email, hostname};
+pub mod byte_size;
+pub use byte_size::byte_size;
+pub mod cidr;
+pub use cidr::cidr;
+pub mod color;
+pub use color::color;
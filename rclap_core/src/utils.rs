@@ -1,11 +1,95 @@
-use crate::PATH_BUF;
+use crate::{
+    COLOR, DATETIME, IP_NET, LANGUAGE_ID, PATH_BUF, SECRET_STRING, STRING_MAP, TIMEZONE, URL, UUID,
+    parsers,
+};
 
-pub const NATIVE_TYPES: [&str; 7] = ["usize", "int", "float", "bool", "string", "path", "char"];
+pub const NATIVE_TYPES: [&str; 19] = [
+    "usize",
+    "int",
+    "float",
+    "bool",
+    "string",
+    "path",
+    "char",
+    "timezone",
+    "lang",
+    "email",
+    "hostname",
+    "port",
+    "bytesize",
+    "cidr",
+    "color",
+    "url",
+    "uuid",
+    "datetime",
+    "secret_string",
+];
+pub const INTEGER_TYPES: [&str; 10] = [
+    "u8", "u16", "u32", "u64", "usize", "i8", "i16", "i32", "i64", "isize",
+];
+/// Spec aliases for the `std::num::NonZero*` integers, e.g. `type = "nonzero_u16"`.
+pub const NONZERO_TYPES: [&str; 10] = [
+    "nonzero_u8",
+    "nonzero_u16",
+    "nonzero_u32",
+    "nonzero_u64",
+    "nonzero_usize",
+    "nonzero_i8",
+    "nonzero_i16",
+    "nonzero_i32",
+    "nonzero_i64",
+    "nonzero_isize",
+];
+fn nonzero_path(alias: &str) -> Option<String> {
+    let width = alias.strip_prefix("nonzero_")?;
+    INTEGER_TYPES.contains(&width).then(|| {
+        let mut chars = width.chars();
+        let first = chars.next().unwrap_or_default().to_ascii_uppercase();
+        format!("std::num::NonZero{first}{}", chars.as_str())
+    })
+}
+/// The inclusive bounds of an integer field type, `NonZero*` types included.
+pub(crate) fn integer_bounds(ty: &str) -> Option<(i128, i128, bool)> {
+    let (width, nonzero) = match ty.strip_prefix("std::num::NonZero") {
+        Some(width) => (width.to_ascii_lowercase(), true),
+        None => (ty.to_string(), false),
+    };
+    let (min, max) = match width.as_str() {
+        "u8" => (0, u8::MAX as i128),
+        "u16" => (0, u16::MAX as i128),
+        "u32" => (0, u32::MAX as i128),
+        "u64" => (0, u64::MAX as i128),
+        "usize" => (0, usize::MAX as i128),
+        "i8" => (i8::MIN as i128, i8::MAX as i128),
+        "i16" => (i16::MIN as i128, i16::MAX as i128),
+        "i32" => (i32::MIN as i128, i32::MAX as i128),
+        "i64" => (i64::MIN as i128, i64::MAX as i128),
+        "isize" => (isize::MIN as i128, isize::MAX as i128),
+        _ => return None,
+    };
+    Some((min, max, nonzero))
+}
+/// Whether `ty` is one of the sized integer types, `NonZero*` included.
+pub(crate) fn has_integer_type(ty: &str) -> bool {
+    integer_bounds(ty).is_some()
+}
 fn is_native_type(ty: &str) -> bool {
     NATIVE_TYPES.contains(&ty.to_lowercase().as_str())
+        || NONZERO_TYPES.contains(&ty.to_lowercase().as_str())
+        || integer_bounds(ty).is_some()
+        || INTEGER_TYPES.contains(&ty)
         || ty == "usize"
         || ty == "i64"
         || ty == PATH_BUF
+        || ty == TIMEZONE
+        || ty == LANGUAGE_ID
+        || ty == IP_NET
+        || ty == COLOR
+        || ty == URL
+        || ty == UUID
+        || ty == DATETIME
+        || ty == SECRET_STRING
+        || ty == "f32"
         || ty == "f64"
         || ty == "String"
 }
@@ -19,65 +103,339 @@ fn to_type(ty: &str) -> String {
         "string" => "String".to_string(),
         "path" => PATH_BUF.to_string(),
         "char" => "char".to_string(),
-        _ => ty.to_string(),
+        "timezone" => TIMEZONE.to_string(),
+        "lang" => LANGUAGE_ID.to_string(),
+        "email" | "hostname" => "String".to_string(),
+        "port" => "u16".to_string(),
+        "bytesize" => "u64".to_string(),
+        "cidr" => IP_NET.to_string(),
+        "color" => COLOR.to_string(),
+        "url" => URL.to_string(),
+        "uuid" => UUID.to_string(),
+        "datetime" => DATETIME.to_string(),
+        "secret_string" => SECRET_STRING.to_string(),
+        "map" => STRING_MAP.to_string(),
+        alias if INTEGER_TYPES.contains(&alias) => alias.to_string(),
+        alias => nonzero_path(alias).unwrap_or_else(|| ty.to_string()),
+    }
+}
+/// Whether a default for this type can be emitted as a Rust literal (`default_value_t`);
+/// every other type takes its default as a string parsed by clap at runtime.
+pub fn has_literal_default(ty: &str) -> bool {
+    INTEGER_TYPES.contains(&ty) || matches!(ty, "f32" | "f64" | "bool" | "char")
+}
+/// Returns the clap `value_parser` expression needed by types that do not parse through
+/// clap's default `FromStr`-based parser. `ty` is the type as declared in the spec.
+pub(crate) fn value_parser_for(ty: &str) -> Option<&'static str> {
+    match ty.to_lowercase().as_str() {
+        "timezone" => Some("rclap::parsers::TimezoneValueParser::new()"),
+        "email" => Some("rclap::parsers::email"),
+        "hostname" => Some("rclap::parsers::hostname"),
+        "port" => Some("rclap::parsers::PortValueParser::new()"),
+        "bytesize" => Some("rclap::parsers::byte_size"),
+        "cidr" => Some("rclap::parsers::cidr"),
+        "secret_string" => Some("rclap::parsers::secret_string"),
+        _ if ty == TIMEZONE => Some("rclap::parsers::TimezoneValueParser::new()"),
+        _ => None,
+    }
+}
+/// Checks at macro expansion time that a default can be parsed into the field type, with the
+/// parsers of `rclap::parsers` for the types that have one (`parser`, as given by
+/// [`value_parser_for`]). The privileges a port needs are only known at runtime.
+pub(crate) fn validate_default(
+    ty: &str,
+    parser: Option<&str>,
+    default: &str,
+) -> Result<(), String> {
+    let check = |kind: &str, result: Result<(), String>| {
+        result.map_err(|e| format!("'{default}' is not a valid {kind}: {e}"))
+    };
+    match parser.map(|parser| parser.split_once('(').map_or(parser, |(path, _)| path)) {
+        Some("rclap::parsers::email") => {
+            return check("e-mail address", parsers::email(default).map(drop));
+        }
+        Some("rclap::parsers::hostname") => {
+            return check("host name", parsers::hostname(default).map(drop));
+        }
+        Some("rclap::parsers::byte_size") => {
+            return check("byte size", parsers::byte_size(default).map(drop));
+        }
+        Some("rclap::parsers::cidr") => {
+            return check("CIDR network", parsers::cidr(default).map(drop));
+        }
+        _ => {}
+    }
+    match ty {
+        TIMEZONE => default
+            .parse::<chrono_tz::Tz>()
+            .map(drop)
+            .map_err(|_| format!("'{default}' is not a known IANA time zone")),
+        LANGUAGE_ID => default
+            .parse::<unic_langid::LanguageIdentifier>()
+            .map(drop)
+            .map_err(|e| format!("'{default}' is not a valid language tag: {e}")),
+        COLOR => check("color", parsers::color(default).map(drop)),
+        URL => url::Url::parse(default)
+            .map(|_| ())
+            .map_err(|e| format!("'{default}' is not a valid URL: {e}")),
+        UUID => uuid::Uuid::parse_str(default)
+            .map(|_| ())
+            .map_err(|e| format!("'{default}' is not a valid UUID: {e}")),
+        DATETIME => default
+            .parse::<toml::value::Datetime>()
+            .map(|_| ())
+            .map_err(|e| format!("'{default}' is not a valid datetime: {e}")),
+        _ => match integer_bounds(ty) {
+            Some((min, max, nonzero)) => {
+                let value: i128 = default
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("'{default}' is not an integer"))?;
+                if nonzero && value == 0 {
+                    Err(format!("'{default}' is not allowed for {ty}"))
+                } else if value < min || value > max {
+                    Err(format!(
+                        "'{default}' is out of range for {ty} ({min}..={max})"
+                    ))
+                } else {
+                    Ok(())
+                }
+            }
+            None => Ok(()),
+        },
+    }
+}
+/// Builds the `PortValueParser` expression for `type = "port"`, folding in the optional
+/// `min`/`max` range and `strict_ports` so a single parser handles every check.
+pub(crate) fn port_value_parser(strict: bool, min: Option<i128>, max: Option<i128>) -> String {
+    let mut parser = "rclap::parsers::PortValueParser::new()".to_string();
+    match (min, max) {
+        (None, None) => {}
+        (Some(min), Some(max)) => parser.push_str(&format!(".range({min}..={max})")),
+        (Some(min), None) => parser.push_str(&format!(".range({min}..)")),
+        (None, Some(max)) => parser.push_str(&format!(".range(..={max})")),
+    }
+    if strict {
+        parser.push_str(".strict(true)");
     }
+    parser
 }
 pub(crate) fn get_field_type(
     table: &toml::map::Map<String, toml::Value>,
     has_sub: bool,
     field_name: String,
-) -> RawField {
-    let field_type = table.get("type").and_then(|v| v.as_str()).map(to_type);
+) -> Result<RawField, String> {
+    if table.get("external").and_then(|v| v.as_bool()) == Some(true) {
+        let Some(args) = table.get("type").and_then(|v| v.as_str()) else {
+            return Err(format!(
+                "Field '{field_name}' sets `external = true` but names no `type`"
+            ));
+        };
+        if !is_rust_path(args) {
+            return Err(format!(
+                "Type '{args}' of field '{field_name}' is not a valid Rust path"
+            ));
+        }
+        return Ok(RawField {
+            type_name: if args.contains("::") {
+                args.to_string()
+            } else {
+                format!("super::{args}")
+            },
+            is_native: false,
+            is_vec: false,
+            value_parser: None,
+        });
+    }
+    if let Some(external) = table.get("external").and_then(|v| v.as_str()) {
+        if table.contains_key("type") {
+            return Err(format!(
+                "Field '{field_name}' cannot set both `type` and `external`"
+            ));
+        }
+        return Ok(RawField {
+            type_name: external.to_string(),
+            is_native: false,
+            is_vec: false,
+            value_parser: None,
+        });
+    }
+    let declared_type = table.get("type").and_then(|v| v.as_str());
+    let field_type = declared_type.map(to_type);
     let enum_type = table.get("enum").and_then(|v| v.as_str());
     if let Some(ft) = field_type {
         if ft.starts_with('[') && ft.ends_with(']') {
             let inner_type = &ft[1..ft.len() - 1].trim();
             if is_native_type(inner_type) {
-                return RawField {
+                return Ok(RawField {
                     type_name: format!("Vec<{}>", to_type(inner_type)),
                     is_native: true,
                     is_vec: true,
-                };
+                    value_parser: value_parser_for(inner_type).map(String::from),
+                });
             } else {
                 // TODO:
-                panic!("Non-native inner types in Vec are not supported yet");
+                return Err("Non-native inner types in Vec are not supported yet".to_string());
                 // return format!("Vec<{}Config>", to_pascal_case(inner_type));
             }
         }
-        return RawField {
+        return Ok(RawField {
             type_name: ft.to_string(),
             is_native: is_native_type(&ft),
             is_vec: false,
-        };
+            value_parser: declared_type.and_then(value_parser_for).map(String::from),
+        });
     }
     if let Some(et) = enum_type {
-        return RawField {
-            type_name: et.to_string(),
+        // The generated types live in a module of their own: an existing enum named without a
+        // path is looked up next to the config struct.
+        let external = !table.contains_key("variants");
+        return Ok(RawField {
+            type_name: if external && !et.contains("::") {
+                format!("super::{et}")
+            } else {
+                et.to_string()
+            },
             is_native: false,
             is_vec: false,
-        };
+            value_parser: None,
+        });
     }
     if has_sub {
-        RawField {
+        Ok(RawField {
             type_name: format!("{}Config", to_pascal_case(&field_name)),
             is_native: false,
             is_vec: false,
-        }
+            value_parser: None,
+        })
     } else {
-        RawField {
+        Ok(RawField {
             type_name: "String".to_string(),
             is_native: true,
             is_vec: false,
+            value_parser: None,
+        })
+    }
+}
+/// Whether `s` is a Rust identifier (keywords are not rejected).
+pub(crate) fn is_rust_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && s != "_"
+}
+
+/// Whether `s` is a Rust type path such as `crate::cli::Format` or `::log::Level`.
+pub(crate) fn is_rust_path(s: &str) -> bool {
+    s.strip_prefix("::")
+        .unwrap_or(s)
+        .split("::")
+        .all(is_rust_ident)
+}
+
+/// Whether `cfg` is a `#[cfg(...)]` predicate: an option such as `unix` or `feature = "x"`,
+/// or `all(...)`, `any(...)` and `not(...)` of predicates.
+pub(crate) fn is_cfg_predicate(cfg: &str) -> bool {
+    fn predicate(s: &str) -> Option<&str> {
+        let s = s.trim_start();
+        let end = s
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(s.len());
+        let (name, rest) = s.split_at(end);
+        if !is_rust_ident(name) {
+            return None;
+        }
+        let rest = rest.trim_start();
+        if let Some(mut rest) = rest.strip_prefix('(') {
+            if !matches!(name, "all" | "any" | "not") {
+                return None;
+            }
+            let mut count = 0;
+            loop {
+                rest = rest.trim_start();
+                if let Some(rest) = rest.strip_prefix(')') {
+                    return (name != "not" || count == 1).then_some(rest);
+                }
+                rest = predicate(rest)?.trim_start();
+                count += 1;
+                match rest.strip_prefix(',') {
+                    Some(after) => rest = after,
+                    None if rest.starts_with(')') => {}
+                    None => return None,
+                }
+            }
+        }
+        match rest.strip_prefix('=') {
+            Some(value) => {
+                let value = value.trim_start().strip_prefix('"')?;
+                let mut escaped = false;
+                let (end, _) = value.char_indices().find(|&(_, c)| {
+                    let closes = c == '"' && !escaped;
+                    escaped = c == '\\' && !escaped;
+                    closes
+                })?;
+                Some(&value[end + 1..])
+            }
+            None => Some(rest),
         }
     }
+    predicate(cfg).is_some_and(|rest| rest.trim().is_empty())
 }
+
+/// Fails unless an inline enum (with `variants`) has a plain name, identifier variants and
+/// a default among them, or an existing enum is named by a valid path with a variant as
+/// default.
+pub(crate) fn validate_enum(
+    id: &str,
+    name: &str,
+    variants: &[&str],
+    default: Option<&str>,
+) -> Result<(), String> {
+    if variants.is_empty() {
+        if !is_rust_path(name) {
+            return Err(format!(
+                "Enum '{name}' of field '{id}' is not a valid Rust path"
+            ));
+        }
+        if let Some(default) = default
+            && !is_rust_ident(default)
+        {
+            return Err(format!(
+                "Default '{default}' of field '{id}' must be a variant name of {name}"
+            ));
+        }
+        return Ok(());
+    }
+    if !is_rust_ident(name) {
+        return Err(format!(
+            "Inline enum '{name}' of field '{id}' must be a plain name; drop `variants` to use an existing enum"
+        ));
+    }
+    if let Some(variant) = variants.iter().find(|v| !is_rust_ident(v)) {
+        return Err(format!(
+            "Variant '{variant}' of enum {name} is not a valid Rust identifier"
+        ));
+    }
+    if let Some(default) = default
+        && !variants.contains(&default)
+    {
+        return Err(format!(
+            "Default '{default}' of field '{id}' is not a variant of {name}: {variants:?}"
+        ));
+    }
+    Ok(())
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct RawField {
     pub type_name: String,
     pub is_native: bool,
     pub is_vec: bool,
+    pub value_parser: Option<String>,
 }
-pub(crate) fn to_pascal_case(s: &str) -> String {
+pub fn to_pascal_case(s: &str) -> String {
     let mut chars = s.chars();
     match chars.next() {
         Some(first) => first.to_uppercase().chain(chars).collect(),
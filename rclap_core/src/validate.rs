@@ -0,0 +1,178 @@
+//! Checks of a spec file reporting every problem found, with its line, where the build stops
+//! at the first one.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use toml::de::{DeTable, DeValue};
+
+use crate::{AppSpec, ConfigSpec, FIELD_KEYS, GenericConfigSpec};
+
+/// A problem of a spec, at a position of `file` when it can be told.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub file: PathBuf,
+    /// 1-based line and column.
+    pub position: Option<(usize, usize)>,
+    /// The path of the field in error from the root of the spec, e.g. `database.port`.
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl Problem {
+    /// A problem of the field `id`, e.g. `config.database.port`, or of the whole spec.
+    pub(crate) fn of(id: Option<&str>, message: String) -> Problem {
+        Problem {
+            file: PathBuf::new(),
+            position: None,
+            field: id.map(|id| id.split_once('.').map_or(id, |(_, path)| path).to_string()),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.file.as_os_str().is_empty() {
+            return write!(f, "{}", self.message);
+        }
+        write!(f, "{}", self.file.display())?;
+        if let Some((line, column)) = self.position {
+            write!(f, ":{line}:{column}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// The messages of `problems`, one per line, as the build reports them.
+pub fn messages(problems: &[Problem]) -> String {
+    let messages: Vec<&str> = problems.iter().map(|p| p.message.as_str()).collect();
+    messages.join("\n")
+}
+
+/// Checks the spec at `path` as `#[config]` would for the struct `struct_name`: unknown keys
+/// and invalid shorts, which the build ignores, then every error the build reports.
+pub fn validate(path: &Path, struct_name: &str) -> Vec<Problem> {
+    let problem = |position, message: String| Problem {
+        file: path.to_path_buf(),
+        position,
+        field: None,
+        message,
+    };
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => return vec![problem(None, format!("cannot read spec: {e}"))],
+    };
+    let root = match DeTable::parse(&content) {
+        Ok(root) => root,
+        Err(e) => {
+            let position = e.span().map(|span| position(&content, span.start));
+            return vec![problem(position, e.message().to_string())];
+        }
+    };
+    let mut problems = Vec::new();
+    for (key, value) in root.get_ref() {
+        match (key.get_ref().as_ref(), value.get_ref()) {
+            ("include" | "struct_name" | "profiles", _) => {}
+            (name, DeValue::Table(_)) if AppSpec::TABLES.contains(&name) => {}
+            (name, value) if is_tables(value) => lint(name, value, &mut |offset, message| {
+                problems.push(problem(Some(position(&content, offset)), message))
+            }),
+            (name, _) => problems.push(problem(
+                Some(position(&content, key.span().start)),
+                format!("'{name}' is neither a field nor a section"),
+            )),
+        }
+    }
+    let mut generic = match GenericConfigSpec::from_file(path) {
+        Ok(generic) => generic,
+        Err(e) => {
+            problems.push(problem(None, e.to_string()));
+            return problems;
+        }
+    };
+    generic
+        .fields
+        .retain(|_, value| value.is_table() || value.is_array());
+    if let Err(found) = ConfigSpec::try_from(generic.with_struct_name(struct_name.to_string())) {
+        problems.extend(found.into_iter().map(|found| {
+            let offset = found
+                .field
+                .as_deref()
+                .and_then(|field| find(root.get_ref(), field));
+            Problem {
+                position: offset.map(|o| position(&content, o)),
+                ..problem(None, found.message)
+            }
+        }));
+    }
+    problems.sort_by_key(|problem| problem.position.unwrap_or((usize::MAX, 0)));
+    problems
+}
+
+/// Reports the unknown keys and invalid shorts of the field or section `path`.
+fn lint(path: &str, value: &DeValue, report: &mut dyn FnMut(usize, String)) {
+    let table = match value {
+        DeValue::Table(table) => table,
+        DeValue::Array(items) => {
+            for item in items.iter() {
+                lint(path, item.get_ref(), report);
+            }
+            return;
+        }
+        _ => return,
+    };
+    for (key, value) in table {
+        let name = key.get_ref().as_ref();
+        match value.get_ref() {
+            DeValue::String(short) if name == "short" && short.chars().count() != 1 => report(
+                value.span().start,
+                format!("Field '{path}': short '{short}' must be a single character"),
+            ),
+            _ if FIELD_KEYS.contains(&name) => {}
+            value if is_tables(value) => lint(&format!("{path}.{name}"), value, report),
+            _ => report(
+                key.span().start,
+                format!("Field '{path}': unknown key '{name}'"),
+            ),
+        }
+    }
+}
+
+fn is_tables(value: &DeValue) -> bool {
+    match value {
+        DeValue::Table(_) => true,
+        DeValue::Array(items) => {
+            !items.is_empty()
+                && items
+                    .iter()
+                    .all(|item| matches!(item.get_ref(), DeValue::Table(_)))
+        }
+        _ => false,
+    }
+}
+
+/// The start of the key of the field `path`, e.g. `database.port`.
+fn find(table: &DeTable, path: &str) -> Option<usize> {
+    let (name, rest) = match path.split_once('.') {
+        Some((name, rest)) => (name, Some(rest)),
+        None => (path, None),
+    };
+    let (key, value) = table.iter().find(|(key, _)| key.get_ref() == name)?;
+    match (rest, value.get_ref()) {
+        (None, _) => Some(key.span().start),
+        (Some(rest), DeValue::Table(table)) => find(table, rest),
+        (Some(rest), DeValue::Array(items)) => items.iter().find_map(|item| match item.get_ref() {
+            DeValue::Table(table) => find(table, rest),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+fn position(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+    (line, column)
+}